@@ -210,5 +210,5 @@ fn main() {
 
     let mut pdf = PdfWriter::new("in", (1.0/100.0) * 72.0);
     scene.render_all(&mut pdf);
-    pdf.write_out("collar.pdf");
+    pdf.write_out("collar.pdf").unwrap();
 }
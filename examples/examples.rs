@@ -46,23 +46,23 @@ pub fn rect() -> Transformation<Polygon> {
     transform
 }
 
-pub struct Stripes;
-impl Implicit for Stripes {
-    fn sample(&self, point: Point) -> f32 {
-        point.x.sin() * point.y.sin()
-    }
-
-    fn bounding_box(&self) -> Option<Rect> { None }
-    fn follows_rules(&self) -> bool { false }
+// The slope of this is never more than 1, so it can keep the default
+// lipschitz constant.
+fn stripe_field(point: Point) -> f32 {
+    point.x.sin() * point.y.sin()
 }
 
-pub fn stripes() -> And<Stripes, Circle> {
+pub fn stripes() -> And<FnImplicit<fn(Point) -> f32>, Circle> {
     let lone = Circle {
         center: Point { x: 125.0, y: 200.0 },
         radius: 50.0
     };
 
-    And { left: Stripes, right: lone }
+    // The gradient is (cos x sin y, sin x cos y), whose squared length
+    // sin^2 x + sin^2 y - 2 sin^2 x sin^2 y never gets above 1.
+    let stripes = FnImplicit::new(stripe_field as fn(Point) -> f32)
+        .with_lipschitz_constant(1.0);
+    And { left: stripes, right: lone }
 }
 
 pub fn poly() -> Transformation<Xor<Boundary<BoxCache<Polygon>>, Boundary<BoxCache<Polygon>>>> {
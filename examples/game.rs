@@ -11,27 +11,9 @@ use implicit::geom::*;
 
 const SIZE: f32 = 1.0;
 
-fn hex(x: f32, y: f32) -> Polygon {
-    fn corner(center: (f32, f32), i: u32) -> Point {
-        use std::f32::consts::PI;
-        let i = i as f32;
-        let angle_deg = 60.0 * i + 30.0;
-        let angle_rad = PI / 180.0 * angle_deg;
-        Point {
-            x: center.0 + SIZE * angle_rad.cos(),
-            y: center.1 + SIZE * angle_rad.sin(),
-        }
-    }
-
-    let center = (x, y);
-    Polygon::new(vec![
-        corner(center, 0),
-        corner(center, 1),
-        corner(center, 2),
-        corner(center, 3),
-        corner(center, 4),
-        corner(center, 5),
-    ].into_iter())
+fn hex(x: f32, y: f32) -> RegularPolygon {
+    use std::f32::consts::PI;
+    RegularPolygon::new(Point { x: x, y: y }, 6, SIZE).with_rotation(PI / 6.0)
 }
 
 fn width() -> f32 {
@@ -43,8 +25,8 @@ fn height() -> f32 {
     2.0
 }
 
-fn grid() -> Vec<Polygon> {
-    fn row(offset: u32, h: f32, n: u32) -> Vec<Polygon> {
+fn grid() -> Vec<RegularPolygon> {
+    fn row(offset: u32, h: f32, n: u32) -> Vec<RegularPolygon> {
         let offset = offset as f32 * width();
         let mut out = vec![];
         let jagged_offset = if n % 2 == 0 { 0.0 } else { - width() * 0.5 };
@@ -73,7 +55,7 @@ fn main() {
 
         let mut pdf = PdfWriter::new("in", (1.0/200.0) * 72.0);
         scene.render_all(&mut pdf);
-        pdf.write_out(name);
+        pdf.write_out(name).unwrap();
     }
 
     let gridded = OrThese::new(grid().into_iter().map(|s| {
@@ -8,5 +8,5 @@ use implicit::formats::pdf::PdfWriter;
 
 fn main() {
     let pdf_writer = PdfWriter::new("cm", 1.0);
-    pdf_writer.write_out("mine.pdf");
+    pdf_writer.write_out("mine.pdf").unwrap();
 }
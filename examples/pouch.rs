@@ -85,7 +85,7 @@ fn main() {
 
     let mut pdf = PdfWriter::new("in", 1.0 * 72.0);
     scene.render_all(&mut pdf);
-    pdf.write_out("pouch.pdf");
+    pdf.write_out("pouch.pdf").unwrap();
 
     helper::display(&[&panels]);
 }
@@ -0,0 +1,760 @@
+//! Generating QR codes and Code 128 barcodes as shapes.
+//!
+//! QR codes are encoded in byte mode (so any UTF-8 text works), in the
+//! smallest version that holds the text at the chosen error correction
+//! level, with whichever mask scores the lowest penalty.  Code 128
+//! barcodes use code set B, which covers printable ASCII.
+
+use std::cmp::{min, max};
+use std::error::Error;
+use std::fmt;
+
+use ::Implicit;
+use ::util::geom::{Point, Rect};
+
+#[derive(Debug)]
+pub enum BarcodeError {
+    /// The text doesn't fit in the largest symbol.
+    TooLong,
+    /// The text has a character that the symbology can't encode.
+    Unsupported(String),
+}
+
+impl fmt::Display for BarcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BarcodeError::TooLong => write!(f, "the text is too long to encode"),
+            BarcodeError::Unsupported(ref message) => write!(f, "can't encode text: {}", message),
+        }
+    }
+}
+
+impl Error for BarcodeError {
+    fn description(&self) -> &str {
+        match *self {
+            BarcodeError::TooLong => "text too long",
+            BarcodeError::Unsupported(_) => "unsupported character",
+        }
+    }
+}
+
+/// How much of a QR code can be damaged or covered before it stops
+/// scanning.  Higher levels make for bigger codes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorCorrection {
+    /// About 7% of the code can be recovered.
+    Low,
+    /// About 15%.
+    Medium,
+    /// About 25%.
+    Quartile,
+    /// About 30%.
+    High,
+}
+
+impl ErrorCorrection {
+    fn index(&self) -> usize {
+        match *self {
+            ErrorCorrection::Low => 0,
+            ErrorCorrection::Medium => 1,
+            ErrorCorrection::Quartile => 2,
+            ErrorCorrection::High => 3,
+        }
+    }
+
+    /// The two bits that stand for the level in the format information.
+    fn format_bits(&self) -> u32 {
+        match *self {
+            ErrorCorrection::Low => 1,
+            ErrorCorrection::Medium => 0,
+            ErrorCorrection::Quartile => 3,
+            ErrorCorrection::High => 2,
+        }
+    }
+}
+
+/// A grid of dark modules: the squares of a QR code or the bars of a
+/// barcode.
+///
+/// The top left corner of the grid is at the origin and rows go toward
+/// +y.  Samples are exact distances to the union of the dark modules.
+/// Scanners also need a light margin around the code (four modules for
+/// QR codes and ten for Code 128), which isn't part of the shape.
+///
+/// Calling `smooth` on a code merges neighbouring modules into rounded
+/// blobs, which engrave more cleanly than sharp corners.
+#[derive(Clone, Debug)]
+pub struct Barcode {
+    columns: usize,
+    rows: usize,
+    dark: Vec<bool>,
+    module_width: f32,
+    module_height: f32,
+    bounds: Option<Rect>,
+}
+
+impl Barcode {
+    fn new(columns: usize, rows: usize, dark: Vec<bool>, module_width: f32, module_height: f32) -> Barcode {
+        assert!(module_width > 0.0 && module_height > 0.0, "modules must have a positive size");
+        let mut bounds: Option<Rect> = None;
+        for row in 0 .. rows {
+            for column in 0 .. columns {
+                if dark[row * columns + column] {
+                    let top_left = Point {
+                        x: column as f32 * module_width,
+                        y: row as f32 * module_height,
+                    };
+                    let bottom_right = Point {
+                        x: top_left.x + module_width,
+                        y: top_left.y + module_height,
+                    };
+                    let module = Rect::from_points(&top_left, &bottom_right);
+                    bounds = Some(match bounds {
+                        Some(bb) => bb.union_with(&module),
+                        None => module,
+                    });
+                }
+            }
+        }
+
+        Barcode {
+            columns: columns,
+            rows: rows,
+            dark: dark,
+            module_width: module_width,
+            module_height: module_height,
+            bounds: bounds,
+        }
+    }
+
+    /// Encodes `text` as a QR code with square modules `module_size`
+    /// units wide.
+    pub fn qr(text: &str, level: ErrorCorrection, module_size: f32) -> Result<Barcode, BarcodeError> {
+        let data = text.as_bytes();
+        let version = match (1 .. 41).find(|&v| qr_fits(data.len(), v, level)) {
+            Some(v) => v,
+            None => return Err(BarcodeError::TooLong),
+        };
+        let codewords = qr_codewords(data, version, level);
+
+        let mut matrix = QrMatrix::new(version);
+        matrix.draw_function_patterns(version);
+        matrix.draw_codewords(&codewords);
+
+        let mut best = None;
+        for mask in 0 .. 8 {
+            matrix.draw_format(level, mask);
+            matrix.apply_mask(mask);
+            let penalty = matrix.penalty();
+            matrix.apply_mask(mask);
+            if best.map(|(_, lowest)| penalty < lowest).unwrap_or(true) {
+                best = Some((mask, penalty));
+            }
+        }
+        let mask = best.unwrap().0;
+        matrix.draw_format(level, mask);
+        matrix.apply_mask(mask);
+
+        Ok(Barcode::new(matrix.size, matrix.size, matrix.dark, module_size, module_size))
+    }
+
+    /// Encodes `text` as a Code 128 barcode whose narrowest bars are
+    /// `module_width` wide and `height` tall.
+    ///
+    /// Only printable ASCII can be encoded.
+    pub fn code128(text: &str, module_width: f32, height: f32) -> Result<Barcode, BarcodeError> {
+        let mut values = vec![CODE128_START_B];
+        for c in text.chars() {
+            let code = c as usize;
+            if code < 32 || code > 127 {
+                return Err(BarcodeError::Unsupported(format!("{:?} isn't in Code 128 code set B", c)));
+            }
+            values.push(code - 32);
+        }
+        let checksum = values.iter().enumerate().fold(0, |sum, (i, &value)| {
+            sum + value * max(i, 1)
+        }) % 103;
+        values.push(checksum);
+
+        let mut dark = vec![];
+        {
+            let mut add = |widths: &str| {
+                for (i, width) in widths.bytes().enumerate() {
+                    for _ in 0 .. width - b'0' {
+                        dark.push(i % 2 == 0);
+                    }
+                }
+            };
+            for &value in &values {
+                add(CODE128[value]);
+            }
+            add(CODE128_STOP);
+        }
+
+        Ok(Barcode::new(dark.len(), 1, dark, module_width, height))
+    }
+
+    /// The number of modules across the code.
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// The number of modules down the code.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// True if the module in `column` and `row` is dark.  Modules outside
+    /// of the code are light.
+    pub fn is_dark(&self, column: usize, row: usize) -> bool {
+        self.dark_at(column as isize, row as isize)
+    }
+
+    fn dark_at(&self, column: isize, row: isize) -> bool {
+        column >= 0 && row >= 0 &&
+        (column as usize) < self.columns && (row as usize) < self.rows &&
+        self.dark[row as usize * self.columns + column as usize]
+    }
+
+    fn distance_to_module(&self, pos: Point, column: isize, row: isize) -> f32 {
+        let left = column as f32 * self.module_width;
+        let top = row as f32 * self.module_height;
+        let dx = (left - pos.x).max(pos.x - left - self.module_width).max(0.0);
+        let dy = (top - pos.y).max(pos.y - top - self.module_height).max(0.0);
+        (dx * dx + dy * dy).sqrt()
+    }
+}
+
+impl Implicit for Barcode {
+    fn sample(&self, pos: Point) -> f32 {
+        if self.bounds.is_none() {
+            return ::std::f32::INFINITY;
+        }
+
+        let (columns, rows) = (self.columns as isize, self.rows as isize);
+        let column = (pos.x / self.module_width).floor().max(-1.0).min(columns as f32) as isize;
+        let row = (pos.y / self.module_height).floor().max(-1.0).min(rows as f32) as isize;
+        let inside = self.dark_at(column, row);
+
+        // Search outward in square rings of modules for the nearest one of
+        // the other colour.  Dark modules are all in the grid, and when
+        // looking for light ones, the ring just outside of the grid is as
+        // far out as it's worth going.
+        let (first_column, last_column, first_row, last_row) = if inside {
+            (-1, columns, -1, rows)
+        } else {
+            (0, columns - 1, 0, rows - 1)
+        };
+        let step = self.module_width.min(self.module_height);
+        let mut best = ::std::f32::INFINITY;
+        for ring in 0 .. max(columns, rows) + 2 {
+            if best <= (ring - 1) as f32 * step {
+                break;
+            }
+            for r in max(row - ring, first_row) .. min(row + ring, last_row) + 1 {
+                // Rows on the edge of the ring are searched all the way
+                // across, and the rest only at the two ends.
+                let (from, to, stride) = if (r - row).abs() == ring {
+                    (max(column - ring, first_column), min(column + ring, last_column), 1)
+                } else {
+                    (column - ring, column + ring, 2 * ring)
+                };
+                let mut c = from;
+                while c <= to {
+                    if c >= first_column && c <= last_column && self.dark_at(c, r) != inside {
+                        best = best.min(self.distance_to_module(pos, c, r));
+                    }
+                    c += stride;
+                }
+            }
+        }
+
+        if inside { -best } else { best }
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        self.bounds
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+const CODE128_START_B: usize = 104;
+const CODE128_STOP: &'static str = "2331112";
+
+/// The widths of the bars and spaces (alternating, starting with a bar)
+/// for each Code 128 value, up to and including the three start codes.
+static CODE128: [&'static str; 106] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312",
+    "132212", "221213", "221312", "231212", "112232", "122132", "122231", "113222",
+    "123122", "123221", "223211", "221132", "221231", "213212", "223112", "312131",
+    "311222", "321122", "321221", "312212", "322112", "322211", "212123", "212321",
+    "232121", "111323", "131123", "131321", "112313", "132113", "132311", "211313",
+    "231113", "231311", "112133", "112331", "132131", "113123", "113321", "133121",
+    "313121", "211331", "231131", "213113", "213311", "213131", "311123", "311321",
+    "331121", "312113", "312311", "332111", "314111", "221411", "431111", "111224",
+    "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111",
+    "111242", "121142", "121241", "114212", "124112", "124211", "411212", "421112",
+    "421211", "212141", "214121", "412121", "111143", "111341", "131141", "114113",
+    "114311", "411113", "411311", "113141", "114131", "311141", "411131", "211412",
+    "211214", "211232",
+];
+
+/// Error correction codewords in each block, by level and then version.
+static QR_ECC_PER_BLOCK: [[u8; 40]; 4] = [
+    [7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28,
+     28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26,
+     26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+    [13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30,
+     28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28,
+     30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+];
+
+/// The number of error correction blocks, by level and then version.
+static QR_BLOCKS: [[u8; 40]; 4] = [
+    [1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8,
+     8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25],
+    [1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16,
+     17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49],
+    [1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20,
+     23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68],
+    [1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25,
+     25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81],
+];
+
+/// The number of modules that hold codewords (and remainder bits) in a
+/// symbol: everything except the function patterns.
+fn qr_raw_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let alignments = version / 7 + 2;
+        modules -= (25 * alignments - 10) * alignments - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+fn qr_ecc_per_block(version: usize, level: ErrorCorrection) -> usize {
+    QR_ECC_PER_BLOCK[level.index()][version - 1] as usize
+}
+
+fn qr_blocks(version: usize, level: ErrorCorrection) -> usize {
+    QR_BLOCKS[level.index()][version - 1] as usize
+}
+
+fn qr_data_codewords(version: usize, level: ErrorCorrection) -> usize {
+    qr_raw_modules(version) / 8 - qr_ecc_per_block(version, level) * qr_blocks(version, level)
+}
+
+fn qr_count_bits(version: usize) -> usize {
+    if version < 10 { 8 } else { 16 }
+}
+
+fn qr_fits(bytes: usize, version: usize, level: ErrorCorrection) -> bool {
+    4 + qr_count_bits(version) + 8 * bytes <= 8 * qr_data_codewords(version, level)
+}
+
+/// Packs `data` into a byte mode segment, pads it out, and interleaves it
+/// with its error correction codewords.
+fn qr_codewords(data: &[u8], version: usize, level: ErrorCorrection) -> Vec<u8> {
+    let capacity = qr_data_codewords(version, level);
+
+    let mut bits = vec![];
+    {
+        let mut push = |value: u32, count: usize| {
+            for i in (0 .. count).rev() {
+                bits.push((value >> i) & 1 == 1);
+            }
+        };
+        push(0b0100, 4);
+        push(data.len() as u32, qr_count_bits(version));
+        for &b in data {
+            push(b as u32, 8);
+        }
+    }
+    let terminator = min(4, capacity * 8 - bits.len());
+    bits.extend((0 .. terminator).map(|_| false));
+    while bits.len() % 8 != 0 {
+        bits.push(false);
+    }
+
+    let mut codewords: Vec<u8> = bits.chunks(8).map(|byte| {
+        byte.iter().fold(0, |acc, &bit| (acc << 1) | bit as u8)
+    }).collect();
+    let mut pad = [0xEC, 0x11].iter().cycle();
+    while codewords.len() < capacity {
+        codewords.push(*pad.next().unwrap());
+    }
+
+    // The last few blocks hold one more data codeword than the rest.
+    let blocks = qr_blocks(version, level);
+    let ecc = qr_ecc_per_block(version, level);
+    let raw = qr_raw_modules(version) / 8;
+    let short_blocks = blocks - raw % blocks;
+    let short_length = raw / blocks - ecc;
+    let divisor = reed_solomon_divisor(ecc);
+
+    let mut data_blocks = vec![];
+    let mut ecc_blocks = vec![];
+    let mut offset = 0;
+    for i in 0 .. blocks {
+        let length = short_length + if i < short_blocks { 0 } else { 1 };
+        let block = &codewords[offset .. offset + length];
+        offset += length;
+        ecc_blocks.push(reed_solomon_remainder(block, &divisor));
+        data_blocks.push(block);
+    }
+
+    let mut out = Vec::with_capacity(raw);
+    for i in 0 .. short_length + 1 {
+        for block in &data_blocks {
+            if i < block.len() {
+                out.push(block[i]);
+            }
+        }
+    }
+    for i in 0 .. ecc {
+        for block in &ecc_blocks {
+            out.push(block[i]);
+        }
+    }
+    out
+}
+
+/// Multiplies in GF(256), modulo the QR code polynomial
+/// x^8 + x^4 + x^3 + x^2 + 1.
+fn gf_multiply(a: u8, b: u8) -> u8 {
+    let mut product: u32 = 0;
+    for i in (0 .. 8).rev() {
+        product = (product << 1) ^ ((product >> 7) * 0x11D);
+        product ^= ((b as u32 >> i) & 1) * a as u32;
+    }
+    product as u8
+}
+
+/// The coefficients of the Reed-Solomon generator polynomial of `degree`,
+/// from the highest power down, without the leading 1.
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0; degree];
+    result[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0 .. degree {
+        for j in 0 .. degree {
+            result[j] = gf_multiply(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_multiply(root, 2);
+    }
+    result
+}
+
+/// The error correction codewords for `data`.
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; divisor.len()];
+    for &b in data {
+        let factor = b ^ result.remove(0);
+        result.push(0);
+        for (r, &d) in result.iter_mut().zip(divisor.iter()) {
+            *r ^= gf_multiply(d, factor);
+        }
+    }
+    result
+}
+
+/// The 15 bits of format information, before they're placed.
+fn qr_format_bits(level: ErrorCorrection, mask: usize) -> u32 {
+    let data = level.format_bits() << 3 | mask as u32;
+    let mut remainder = data;
+    for _ in 0 .. 10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    (data << 10 | remainder) ^ 0x5412
+}
+
+/// The 18 bits of version information, for versions 7 and up.
+fn qr_version_bits(version: usize) -> u32 {
+    let mut remainder = version as u32;
+    for _ in 0 .. 12 {
+        remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+    }
+    (version as u32) << 12 | remainder
+}
+
+/// The centres of the alignment patterns along each axis.
+fn qr_alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return vec![];
+    }
+    let count = version / 7 + 2;
+    let size = version * 4 + 17;
+    let step = if version == 32 {
+        26
+    } else {
+        (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+    };
+    let mut positions: Vec<usize> = (0 .. count - 1).map(|i| size - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+/// A QR symbol while it's being built, along with which modules belong
+/// to function patterns (and so are left alone by data and masks).
+struct QrMatrix {
+    size: usize,
+    dark: Vec<bool>,
+    function: Vec<bool>,
+}
+
+impl QrMatrix {
+    fn new(version: usize) -> QrMatrix {
+        let size = version * 4 + 17;
+        QrMatrix {
+            size: size,
+            dark: vec![false; size * size],
+            function: vec![false; size * size],
+        }
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.dark[y * self.size + x]
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.dark[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn draw_function_patterns(&mut self, version: usize) {
+        let size = self.size;
+        for i in 0 .. size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+
+        self.draw_finder(3, 3);
+        self.draw_finder(size - 4, 3);
+        self.draw_finder(3, size - 4);
+
+        let positions = qr_alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                let on_finder = (i == 0 && j == 0) || (i == 0 && j == last) || (i == last && j == 0);
+                if !on_finder {
+                    self.draw_alignment(x, y);
+                }
+            }
+        }
+
+        // Reserve the format areas until the mask is chosen.
+        self.draw_format(ErrorCorrection::Medium, 0);
+
+        if version >= 7 {
+            let bits = qr_version_bits(version);
+            for i in 0 .. 18 {
+                let dark = (bits >> i) & 1 == 1;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                self.set_function(a, b, dark);
+                self.set_function(b, a, dark);
+            }
+        }
+    }
+
+    /// A finder pattern centred on `x`, `y`, along with its light separator.
+    fn draw_finder(&mut self, x: usize, y: usize) {
+        for dy in -4 .. 5 {
+            for dx in -4 .. 5 {
+                let (fx, fy) = (x as isize + dx, y as isize + dy);
+                if fx >= 0 && fy >= 0 && (fx as usize) < self.size && (fy as usize) < self.size {
+                    let ring = max(dx.abs(), dy.abs());
+                    self.set_function(fx as usize, fy as usize, ring != 2 && ring != 4);
+                }
+            }
+        }
+    }
+
+    fn draw_alignment(&mut self, x: usize, y: usize) {
+        for dy in -2isize .. 3 {
+            for dx in -2isize .. 3 {
+                let ring = max(dx.abs(), dy.abs());
+                self.set_function((x as isize + dx) as usize, (y as isize + dy) as usize, ring != 1);
+            }
+        }
+    }
+
+    fn draw_format(&mut self, level: ErrorCorrection, mask: usize) {
+        let bits = qr_format_bits(level, mask);
+        let bit = |i: usize| (bits >> i) & 1 == 1;
+        let size = self.size;
+
+        // Around the top left finder.
+        for i in 0 .. 6 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9 .. 15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        // Split between the other two finders.
+        for i in 0 .. 8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8 .. 15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Places codewords in zigzag order: two columns at a time from the
+    /// right, alternating upward and downward, and skipping the vertical
+    /// timing pattern.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let size = self.size;
+        let total = codewords.len() * 8;
+        let mut i = 0;
+        let mut right = size - 1;
+        loop {
+            for vertical in 0 .. size {
+                for j in 0 .. 2 {
+                    let x = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward { size - 1 - vertical } else { vertical };
+                    if !self.function[y * size + x] && i < total {
+                        self.dark[y * size + x] = (codewords[i / 8] >> (7 - i % 8)) & 1 == 1;
+                        i += 1;
+                    }
+                }
+            }
+            if right == 1 {
+                break;
+            }
+            right -= 2;
+            if right == 6 {
+                right = 5;
+            }
+        }
+    }
+
+    /// Flips the data modules that `mask` selects.  Applying the same mask
+    /// twice undoes it.
+    fn apply_mask(&mut self, mask: usize) {
+        let size = self.size;
+        for y in 0 .. size {
+            for x in 0 .. size {
+                let flip = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                if flip && !self.function[y * size + x] {
+                    self.dark[y * size + x] = !self.dark[y * size + x];
+                }
+            }
+        }
+    }
+
+    /// Scores how hard the symbol would be to scan; lower is better.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let finder = [true, false, true, true, true, false, true];
+        let light = |modules: &[bool]| modules.iter().all(|&d| !d);
+        let mut score = 0;
+
+        for &transpose in &[false, true] {
+            for i in 0 .. size {
+                let line: Vec<bool> = (0 .. size).map(|j| {
+                    if transpose { self.get(i, j) } else { self.get(j, i) }
+                }).collect();
+
+                // Long runs of one colour.
+                let mut run = 1;
+                for j in 1 .. size + 1 {
+                    if j < size && line[j] == line[j - 1] {
+                        run += 1;
+                    } else {
+                        if run >= 5 {
+                            score += run - 2;
+                        }
+                        run = 1;
+                    }
+                }
+
+                // Anything that looks like a finder pattern.
+                for window in line.windows(11) {
+                    if (&window[.. 7] == &finder[..] && light(&window[7 ..])) ||
+                       (light(&window[.. 4]) && &window[4 ..] == &finder[..]) {
+                        score += 40;
+                    }
+                }
+            }
+        }
+
+        for y in 0 .. size - 1 {
+            for x in 0 .. size - 1 {
+                let color = self.get(x, y);
+                if color == self.get(x + 1, y) && color == self.get(x, y + 1) &&
+                   color == self.get(x + 1, y + 1) {
+                    score += 3;
+                }
+            }
+        }
+
+        // Every 5% that the dark modules stray from half of the symbol.
+        let dark = self.dark.iter().filter(|&&d| d).count();
+        let total = size * size;
+        let deviation = max(dark * 20, total * 10) - min(dark * 20, total * 10);
+        score + 10 * (deviation / total)
+    }
+}
+
+#[test]
+fn codes_are_encoded_into_modules() {
+    // The worked example of a 1-M symbol holding "HELLO WORLD".
+    let data = [32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17];
+    assert_eq!(reed_solomon_remainder(&data, &reed_solomon_divisor(10)),
+               vec![196, 35, 39, 119, 235, 215, 231, 226, 93, 23]);
+    assert_eq!(qr_format_bits(ErrorCorrection::Low, 0), 0b111011111000100);
+    assert_eq!(qr_format_bits(ErrorCorrection::High, 0), 0b001011010001001);
+    assert_eq!(qr_version_bits(7), 0b000111110010010100);
+
+    let qr = Barcode::qr("HELLO", ErrorCorrection::Medium, 2.0).unwrap();
+    assert_eq!((qr.columns(), qr.rows()), (21, 21));
+    assert!(qr.is_dark(0, 0) && !qr.is_dark(1, 1) && qr.is_dark(3, 3) && !qr.is_dark(7, 3));
+    assert!(qr.is_dark(8, 13));
+    assert_eq!(qr.sample(Point { x: 1.0, y: 1.0 }), -1.0);
+    assert_eq!(qr.sample(Point { x: 15.0, y: 7.0 }), 1.0);
+    let bb = qr.bounding_box().unwrap();
+    assert_eq!((bb.width(), bb.height()), (42.0, 42.0));
+
+    // 100 bytes don't fit in version 4 at the lowest level, but do in 5.
+    let long: String = ::std::iter::repeat('x').take(100).collect();
+    assert_eq!(Barcode::qr(&long, ErrorCorrection::Low, 1.0).unwrap().columns(), 37);
+    let too_long: String = ::std::iter::repeat('x').take(3000).collect();
+    assert!(Barcode::qr(&too_long, ErrorCorrection::Low, 1.0).is_err());
+
+    // Start B, 'H', 'i', the checksum (84) and the stop code.
+    let bars = Barcode::code128("Hi", 0.5, 10.0).unwrap();
+    assert_eq!((bars.columns(), bars.rows()), (57, 1));
+    let pattern: String = (0 .. 11).map(|c| if bars.is_dark(c, 0) { '1' } else { '0' }).collect();
+    assert_eq!(pattern, "11010010000");
+    let checksum: String = (33 .. 44).map(|c| if bars.is_dark(c, 0) { '1' } else { '0' }).collect();
+    assert_eq!(checksum, "10011110100");
+    assert_eq!(bars.sample(Point { x: 0.5, y: 5.0 }), -0.5);
+    assert!(Barcode::code128("caf\u{e9}", 0.5, 10.0).is_err());
+}
@@ -0,0 +1,678 @@
+//! Glyph outlines from the `CFF ` table of OpenType fonts, which are
+//! stored as Type 2 charstrings.
+
+use super::font::{FontError, OutlineBuilder, malformed, read_u8, read_u16, read_i16, read_u32};
+
+/// The locations of the items of a CFF INDEX, as ranges of the font data.
+#[derive(Clone, Debug)]
+struct Index {
+    items: Vec<(usize, usize)>,
+}
+
+impl Index {
+    /// Reads the INDEX at `pos`, returning it and where it ends.
+    fn read(data: &[u8], pos: usize) -> Result<(Index, usize), FontError> {
+        let count = try!(read_u16(data, pos)) as usize;
+        if count == 0 {
+            return Ok((Index { items: vec![] }, pos + 2));
+        }
+        let offset_size = try!(read_u8(data, pos + 2)) as usize;
+        if offset_size < 1 || offset_size > 4 {
+            return malformed("invalid CFF offset size");
+        }
+        let mut offsets = Vec::with_capacity(count + 1);
+        for i in 0 .. count + 1 {
+            let mut offset = 0;
+            for j in 0 .. offset_size {
+                offset = offset << 8 | try!(read_u8(data, pos + 3 + i * offset_size + j)) as usize;
+            }
+            offsets.push(offset);
+        }
+        // Offsets count from 1, starting at the byte before the data.
+        let base = pos + 2 + (count + 1) * offset_size;
+        let end = base + offsets[count];
+        if end > data.len() || offsets.windows(2).any(|w| w[0] > w[1] || w[0] == 0) {
+            return malformed("invalid CFF index");
+        }
+        let items = offsets.windows(2).map(|w| (base + w[0], base + w[1])).collect();
+        Ok((Index { items: items }, end))
+    }
+
+    fn get(&self, i: usize) -> Result<(usize, usize), FontError> {
+        match self.items.get(i) {
+            Some(&range) => Ok(range),
+            None => malformed("CFF index out of range"),
+        }
+    }
+
+    /// The number added to subroutine numbers, which are stored around 0
+    /// so that more of them fit in short encodings.
+    fn bias(&self) -> i32 {
+        match self.items.len() {
+            0 ... 1239 => 107,
+            1240 ... 33899 => 1131,
+            _ => 32768,
+        }
+    }
+}
+
+/// The operators and operands of a DICT.  Two byte operators are stored
+/// as 1200 plus their second byte.
+fn read_dict(data: &[u8], range: (usize, usize)) -> Result<Vec<(u16, Vec<f64>)>, FontError> {
+    let mut entries = vec![];
+    let mut operands = vec![];
+    let mut pos = range.0;
+    while pos < range.1 {
+        let b0 = try!(read_u8(data, pos));
+        pos += 1;
+        match b0 {
+            0 ... 11 | 13 ... 21 => entries.push((b0 as u16, ::std::mem::replace(&mut operands, vec![]))),
+            12 => {
+                let b1 = try!(read_u8(data, pos));
+                pos += 1;
+                entries.push((1200 + b1 as u16, ::std::mem::replace(&mut operands, vec![])));
+            }
+            28 => {
+                operands.push(try!(read_i16(data, pos)) as f64);
+                pos += 2;
+            }
+            29 => {
+                operands.push(try!(read_u32(data, pos)) as i32 as f64);
+                pos += 4;
+            }
+            30 => {
+                // A real number, as a string of nibbles.
+                let mut text = String::new();
+                'nibbles: loop {
+                    let byte = try!(read_u8(data, pos));
+                    pos += 1;
+                    for &nibble in &[byte >> 4, byte & 0xf] {
+                        match nibble {
+                            0 ... 9 => text.push((b'0' + nibble) as char),
+                            0xa => text.push('.'),
+                            0xb => text.push('E'),
+                            0xc => text.push_str("E-"),
+                            0xe => text.push('-'),
+                            0xf => break 'nibbles,
+                            _ => return malformed("invalid real number in a CFF dict"),
+                        }
+                    }
+                }
+                match text.parse::<f64>() {
+                    Ok(v) => operands.push(v),
+                    Err(_) => return malformed("invalid real number in a CFF dict"),
+                }
+            }
+            32 ... 246 => operands.push(b0 as f64 - 139.0),
+            247 ... 250 => {
+                let b1 = try!(read_u8(data, pos));
+                pos += 1;
+                operands.push((b0 as f64 - 247.0) * 256.0 + b1 as f64 + 108.0);
+            }
+            251 ... 254 => {
+                let b1 = try!(read_u8(data, pos));
+                pos += 1;
+                operands.push(-(b0 as f64 - 251.0) * 256.0 - b1 as f64 - 108.0);
+            }
+            _ => return malformed("invalid byte in a CFF dict"),
+        }
+    }
+    Ok(entries)
+}
+
+fn dict_get<'a>(dict: &'a [(u16, Vec<f64>)], op: u16) -> Option<&'a [f64]> {
+    dict.iter().find(|e| e.0 == op).map(|e| &e.1[..])
+}
+
+/// The local subroutines of the Private DICT described by `dict`.
+fn local_subrs(data: &[u8], cff: usize, dict: &[(u16, Vec<f64>)]) -> Result<Index, FontError> {
+    const PRIVATE: u16 = 18;
+    const SUBRS: u16 = 19;
+    let private = match dict_get(dict, PRIVATE) {
+        Some(p) if p.len() == 2 => (cff + p[1] as usize, cff + p[1] as usize + p[0] as usize),
+        _ => return Ok(Index { items: vec![] }),
+    };
+    let private_dict = try!(read_dict(data, private));
+    match dict_get(&private_dict, SUBRS) {
+        Some(s) if s.len() == 1 => Index::read(data, private.0 + s[0] as usize).map(|i| i.0),
+        _ => Ok(Index { items: vec![] }),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct CffFont {
+    charstrings: Index,
+    global_subrs: Index,
+    /// The local subroutines for each font DICT.  Fonts that aren't CID
+    /// keyed only have one.
+    local_subrs: Vec<Index>,
+    /// The font DICT of each glyph, for CID keyed fonts.
+    fd_select: Vec<u8>,
+}
+
+impl CffFont {
+    /// Reads the CFF table at `offset` in the font data.
+    pub(crate) fn new(data: &[u8], offset: usize, length: usize) -> Result<CffFont, FontError> {
+        const CHARSTRING_TYPE: u16 = 1206;
+        const CHARSTRINGS: u16 = 17;
+        const FD_ARRAY: u16 = 1236;
+        const FD_SELECT: u16 = 1237;
+
+        let data = &data[.. offset + length];
+        let header_size = try!(read_u8(data, offset + 2)) as usize;
+        let (_names, pos) = try!(Index::read(data, offset + header_size));
+        let (top_dicts, pos) = try!(Index::read(data, pos));
+        let (_strings, pos) = try!(Index::read(data, pos));
+        let (global_subrs, _) = try!(Index::read(data, pos));
+
+        let top = try!(read_dict(data, try!(top_dicts.get(0))));
+        match dict_get(&top, CHARSTRING_TYPE) {
+            Some(t) if t.len() != 1 || t[0] != 2.0 => return Err(FontError::Unsupported("Type 1 charstrings".to_string())),
+            _ => {}
+        }
+        let charstrings = match dict_get(&top, CHARSTRINGS) {
+            Some(c) if c.len() == 1 => try!(Index::read(data, offset + c[0] as usize)).0,
+            _ => return malformed("CFF font without charstrings"),
+        };
+
+        let (local, fd_select) = match (dict_get(&top, FD_ARRAY), dict_get(&top, FD_SELECT)) {
+            (Some(array), Some(select)) if array.len() == 1 && select.len() == 1 => {
+                let (font_dicts, _) = try!(Index::read(data, offset + array[0] as usize));
+                let mut local = vec![];
+                for &range in &font_dicts.items {
+                    let dict = try!(read_dict(data, range));
+                    local.push(try!(local_subrs(data, offset, &dict)));
+                }
+                let select = try!(read_fd_select(data, offset + select[0] as usize, charstrings.items.len()));
+                if select.iter().any(|&fd| fd as usize >= local.len()) {
+                    return malformed("glyph uses a missing font dict");
+                }
+                (local, select)
+            }
+            _ => (vec![try!(local_subrs(data, offset, &top))], vec![]),
+        };
+
+        Ok(CffFont {
+            charstrings: charstrings,
+            global_subrs: global_subrs,
+            local_subrs: local,
+            fd_select: fd_select,
+        })
+    }
+
+    pub(crate) fn outline(&self, data: &[u8], glyph: u16, builder: &mut OutlineBuilder) -> Result<(), FontError> {
+        let charstring = try!(self.charstrings.get(glyph as usize));
+        let fd = self.fd_select.get(glyph as usize).map(|&fd| fd as usize).unwrap_or(0);
+        let mut interpreter = Interpreter {
+            data: data,
+            builder: builder,
+            global_subrs: &self.global_subrs,
+            local_subrs: &self.local_subrs[fd],
+            stack: vec![],
+            x: 0.0,
+            y: 0.0,
+            stems: 0,
+            seen_width: false,
+            open: false,
+        };
+        try!(interpreter.run(charstring, 0));
+        if interpreter.open {
+            interpreter.builder.close();
+        }
+        Ok(())
+    }
+}
+
+fn read_fd_select(data: &[u8], pos: usize, glyphs: usize) -> Result<Vec<u8>, FontError> {
+    match try!(read_u8(data, pos)) {
+        0 => {
+            let mut select = Vec::with_capacity(glyphs);
+            for i in 0 .. glyphs {
+                select.push(try!(read_u8(data, pos + 1 + i)));
+            }
+            Ok(select)
+        }
+        3 => {
+            let ranges = try!(read_u16(data, pos + 1)) as usize;
+            let mut select = vec![0; glyphs];
+            for i in 0 .. ranges {
+                let range = pos + 3 + 3 * i;
+                let first = try!(read_u16(data, range)) as usize;
+                let fd = try!(read_u8(data, range + 2));
+                let end = try!(read_u16(data, range + 3)) as usize;
+                for g in first .. end.min(glyphs) {
+                    select[g] = fd;
+                }
+            }
+            Ok(select)
+        }
+        _ => malformed("unknown FDSelect format"),
+    }
+}
+
+struct Interpreter<'a, 'b: 'a> {
+    data: &'a [u8],
+    builder: &'a mut OutlineBuilder<'b>,
+    global_subrs: &'a Index,
+    local_subrs: &'a Index,
+    stack: Vec<f32>,
+    x: f32,
+    y: f32,
+    /// The number of stem hints so far, which sets the size of hint masks.
+    stems: usize,
+    seen_width: bool,
+    open: bool,
+}
+
+impl <'a, 'b> Interpreter<'a, 'b> {
+    /// The first operator that clears the stack can have the glyph's
+    /// width before its arguments, which shows up as an extra argument.
+    fn skip_width(&mut self, odd_arguments: bool) {
+        if !self.seen_width {
+            self.seen_width = true;
+            if (self.stack.len() % 2 == 1) != odd_arguments && !self.stack.is_empty() {
+                self.stack.remove(0);
+            }
+        }
+    }
+
+    fn move_by(&mut self, dx: f32, dy: f32) {
+        if self.open {
+            self.builder.close();
+        }
+        self.x += dx;
+        self.y += dy;
+        self.builder.move_to(self.x, self.y);
+        self.open = true;
+    }
+
+    fn line_by(&mut self, dx: f32, dy: f32) {
+        self.x += dx;
+        self.y += dy;
+        self.builder.line_to(self.x, self.y);
+    }
+
+    fn curve_by(&mut self, dx1: f32, dy1: f32, dx2: f32, dy2: f32, dx3: f32, dy3: f32) {
+        let (x1, y1) = (self.x + dx1, self.y + dy1);
+        let (x2, y2) = (x1 + dx2, y1 + dy2);
+        self.x = x2 + dx3;
+        self.y = y2 + dy3;
+        self.builder.cubic_to(x1, y1, x2, y2, self.x, self.y);
+    }
+
+    fn pop(&mut self) -> Result<f32, FontError> {
+        match self.stack.pop() {
+            Some(v) => Ok(v),
+            None => malformed("charstring stack underflow"),
+        }
+    }
+
+    /// Runs the charstring in `range`.  Returns true once the glyph ends.
+    fn run(&mut self, range: (usize, usize), depth: u32) -> Result<bool, FontError> {
+        if depth > 10 {
+            return malformed("charstring subroutines nested too deeply");
+        }
+        let mut pos = range.0;
+        while pos < range.1 {
+            let b0 = try!(read_u8(self.data, pos));
+            pos += 1;
+            match b0 {
+                32 ... 246 => {
+                    self.stack.push(b0 as f32 - 139.0);
+                    continue;
+                }
+                247 ... 250 => {
+                    let b1 = try!(read_u8(self.data, pos));
+                    pos += 1;
+                    self.stack.push((b0 as f32 - 247.0) * 256.0 + b1 as f32 + 108.0);
+                    continue;
+                }
+                251 ... 254 => {
+                    let b1 = try!(read_u8(self.data, pos));
+                    pos += 1;
+                    self.stack.push(-(b0 as f32 - 251.0) * 256.0 - b1 as f32 - 108.0);
+                    continue;
+                }
+                28 => {
+                    self.stack.push(try!(read_i16(self.data, pos)) as f32);
+                    pos += 2;
+                    continue;
+                }
+                255 => {
+                    self.stack.push(try!(read_u32(self.data, pos)) as i32 as f32 / 65536.0);
+                    pos += 4;
+                    continue;
+                }
+                10 | 29 => {
+                    let subrs = if b0 == 10 { self.local_subrs } else { self.global_subrs };
+                    let n = try!(self.pop()) as i32 + subrs.bias();
+                    if n < 0 {
+                        return malformed("negative subroutine number");
+                    }
+                    let subr = try!(subrs.get(n as usize));
+                    if try!(self.run(subr, depth + 1)) {
+                        return Ok(true);
+                    }
+                    continue;
+                }
+                11 => return Ok(false),
+                12 => {
+                    let b1 = try!(read_u8(self.data, pos));
+                    pos += 1;
+                    if try!(self.escape(b1)) {
+                        continue;
+                    }
+                }
+                _ => {
+                    if let Some(done) = try!(self.operator(b0, &mut pos)) {
+                        return Ok(done);
+                    }
+                }
+            }
+            self.stack.clear();
+        }
+        Ok(false)
+    }
+
+    /// Runs a one byte operator.  Returns Some(true) for `endchar`.
+    fn operator(&mut self, op: u8, pos: &mut usize) -> Result<Option<bool>, FontError> {
+        let n = self.stack.len();
+        match op {
+            // Hints don't change the outline, but hint masks need to know
+            // how many there have been.
+            1 | 3 | 18 | 23 => {
+                self.skip_width(false);
+                self.stems += self.stack.len() / 2;
+            }
+            19 | 20 => {
+                self.skip_width(false);
+                self.stems += self.stack.len() / 2;
+                *pos += (self.stems + 7) / 8;
+            }
+            21 => {
+                self.skip_width(false);
+                if self.stack.len() < 2 {
+                    return malformed("rmoveto without enough arguments");
+                }
+                let (dx, dy) = (self.stack[0], self.stack[1]);
+                self.move_by(dx, dy);
+            }
+            22 | 4 => {
+                self.skip_width(true);
+                let d = match self.stack.first() {
+                    Some(&d) => d,
+                    None => return malformed("moveto without an argument"),
+                };
+                if op == 22 { self.move_by(d, 0.0) } else { self.move_by(0.0, d) }
+            }
+            5 => {
+                for i in 0 .. n / 2 {
+                    let (dx, dy) = (self.stack[2 * i], self.stack[2 * i + 1]);
+                    self.line_by(dx, dy);
+                }
+            }
+            6 | 7 => {
+                let mut horizontal = op == 6;
+                for i in 0 .. n {
+                    let d = self.stack[i];
+                    if horizontal { self.line_by(d, 0.0) } else { self.line_by(0.0, d) }
+                    horizontal = !horizontal;
+                }
+            }
+            8 => {
+                for i in 0 .. n / 6 {
+                    let a = self.stack[6 * i .. 6 * i + 6].to_vec();
+                    self.curve_by(a[0], a[1], a[2], a[3], a[4], a[5]);
+                }
+            }
+            24 => {
+                // Curves, then a line.
+                let mut i = 0;
+                while i + 6 <= n.saturating_sub(2) {
+                    let a = self.stack[i .. i + 6].to_vec();
+                    self.curve_by(a[0], a[1], a[2], a[3], a[4], a[5]);
+                    i += 6;
+                }
+                if i + 2 <= n {
+                    let (dx, dy) = (self.stack[i], self.stack[i + 1]);
+                    self.line_by(dx, dy);
+                }
+            }
+            25 => {
+                // Lines, then a curve.
+                let mut i = 0;
+                while i + 2 <= n.saturating_sub(6) {
+                    let (dx, dy) = (self.stack[i], self.stack[i + 1]);
+                    self.line_by(dx, dy);
+                    i += 2;
+                }
+                if i + 6 <= n {
+                    let a = self.stack[i .. i + 6].to_vec();
+                    self.curve_by(a[0], a[1], a[2], a[3], a[4], a[5]);
+                }
+            }
+            26 | 27 => {
+                // Curves that start and end vertically (26) or horizontally
+                // (27), with an optional first offset across that.
+                let (mut across, mut i) = if n % 2 == 1 { (self.stack[0], 1) } else { (0.0, 0) };
+                while i + 4 <= n {
+                    let a = self.stack[i .. i + 4].to_vec();
+                    if op == 26 {
+                        self.curve_by(across, a[0], a[1], a[2], 0.0, a[3]);
+                    } else {
+                        self.curve_by(a[0], across, a[1], a[2], a[3], 0.0);
+                    }
+                    across = 0.0;
+                    i += 4;
+                }
+            }
+            30 | 31 => {
+                // Curves that alternate between starting vertically and
+                // horizontally, with an optional offset at the very end.
+                let mut horizontal = op == 31;
+                let mut i = 0;
+                while i + 4 <= n {
+                    let a = self.stack[i .. i + 4].to_vec();
+                    let last = if n - i == 5 { self.stack[i + 4] } else { 0.0 };
+                    if horizontal {
+                        self.curve_by(a[0], 0.0, a[1], a[2], last, a[3]);
+                    } else {
+                        self.curve_by(0.0, a[0], a[1], a[2], a[3], last);
+                    }
+                    horizontal = !horizontal;
+                    i += 4;
+                }
+            }
+            14 => {
+                self.skip_width(false);
+                if self.stack.len() >= 4 {
+                    return Err(FontError::Unsupported("accented characters built with endchar".to_string()));
+                }
+                if self.open {
+                    self.builder.close();
+                    self.open = false;
+                }
+                return Ok(Some(true));
+            }
+            _ => return Err(FontError::Malformed(format!("reserved charstring operator {}", op))),
+        }
+        Ok(None)
+    }
+
+    /// Runs a two byte operator.  Returns true if the stack should be
+    /// kept, as it is after arithmetic.
+    fn escape(&mut self, op: u8) -> Result<bool, FontError> {
+        let n = self.stack.len();
+        let a: Vec<f32> = self.stack.clone();
+        match op {
+            35 if n >= 13 => {
+                self.curve_by(a[0], a[1], a[2], a[3], a[4], a[5]);
+                self.curve_by(a[6], a[7], a[8], a[9], a[10], a[11]);
+            }
+            34 if n >= 7 => {
+                self.curve_by(a[0], 0.0, a[1], a[2], a[3], 0.0);
+                self.curve_by(a[4], 0.0, a[5], -a[2], a[6], 0.0);
+            }
+            36 if n >= 9 => {
+                self.curve_by(a[0], a[1], a[2], a[3], a[4], 0.0);
+                self.curve_by(a[5], 0.0, a[6], a[7], a[8], -(a[1] + a[3] + a[7]));
+            }
+            37 if n >= 11 => {
+                let dx = a[0] + a[2] + a[4] + a[6] + a[8];
+                let dy = a[1] + a[3] + a[5] + a[7] + a[9];
+                self.curve_by(a[0], a[1], a[2], a[3], a[4], a[5]);
+                if dx.abs() > dy.abs() {
+                    self.curve_by(a[6], a[7], a[8], a[9], a[10], -dy);
+                } else {
+                    self.curve_by(a[6], a[7], a[8], a[9], -dx, a[10]);
+                }
+            }
+            34 ... 37 => return malformed("flex without enough arguments"),
+            // Arithmetic, which only a few fonts use.
+            9 | 14 | 26 | 18 | 27 => {
+                let v = try!(self.pop());
+                match op {
+                    9 => self.stack.push(v.abs()),
+                    14 => self.stack.push(-v),
+                    26 => self.stack.push(v.sqrt()),
+                    27 => {
+                        self.stack.push(v);
+                        self.stack.push(v);
+                    }
+                    _ => {}
+                }
+                return Ok(true);
+            }
+            10 | 11 | 12 | 24 | 28 => {
+                let b = try!(self.pop());
+                let a = try!(self.pop());
+                match op {
+                    10 => self.stack.push(a + b),
+                    11 => self.stack.push(a - b),
+                    12 => self.stack.push(a / b),
+                    24 => self.stack.push(a * b),
+                    _ => {
+                        self.stack.push(b);
+                        self.stack.push(a);
+                    }
+                }
+                return Ok(true);
+            }
+            _ => return Err(FontError::Unsupported(format!("charstring operator 12 {}", op))),
+        }
+        Ok(false)
+    }
+}
+
+#[test]
+fn charstrings_are_interpreted() {
+    use ::{Implicit, Path};
+    use ::util::geom::Point;
+
+    fn number(v: i32) -> Vec<u8> {
+        match v {
+            -107 ... 107 => vec![(v + 139) as u8],
+            108 ... 1131 => vec![((v - 108) / 256 + 247) as u8, ((v - 108) % 256) as u8],
+            -1131 ... -108 => vec![((-v - 108) / 256 + 251) as u8, ((-v - 108) % 256) as u8],
+            _ => panic!("{} needs a longer encoding", v),
+        }
+    }
+    // A charstring or dict, from numbers and operators.
+    fn program(parts: &[&[i32]], op: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        for (part, &op) in parts.iter().zip(op.iter()) {
+            for &v in *part {
+                out.extend(number(v));
+            }
+            out.push(op);
+        }
+        out
+    }
+    fn dict_offset(v: usize) -> Vec<u8> {
+        vec![29, (v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8]
+    }
+    fn index(items: &[&[u8]]) -> Vec<u8> {
+        if items.is_empty() {
+            return vec![0, 0];
+        }
+        let mut out = vec![0, items.len() as u8, 1, 1];
+        let mut offset = 1;
+        for item in items {
+            offset += item.len();
+            out.push(offset as u8);
+        }
+        for item in items {
+            out.extend_from_slice(item);
+        }
+        out
+    }
+
+    // Glyph 1 has its width (500) in front of the rmoveto, draws an
+    // rrcurveto and an hvcurveto, closes with a line from a local
+    // subroutine and ends in a global one.
+    let glyph1 = program(&[&[500, 100, 100], &[0, 200, 100, 100, 200, 0], &[100, 50, -100, -200],
+                               &[-107], &[-107]],
+                             &[21, 8, 31, 10, 29]);
+    let local_subr = program(&[&[-450, 0], &[]], &[5, 11]);
+    let global_subr = vec![14];
+    // Glyph 2 has its width in front of a stem hint, and a hint mask
+    // that has to be skipped.
+    let mut glyph2 = program(&[&[600, 10, 20]], &[1]);
+    glyph2.extend(vec![19, 0x80]);
+    glyph2.extend(program(&[&[50, 50], &[100, 100, -100], &[]], &[21, 6, 14]));
+    let charstrings = index(&[&[14], &glyph1, &glyph2]);
+
+    let header = [1, 0, 4, 1];
+    let names = index(&[b"T"]);
+    let strings = index(&[]);
+    let global_subrs = index(&[&global_subr]);
+    // The top dict is always 17 bytes long.
+    let top_size = 17;
+    let charstrings_at = header.len() + names.len() + (5 + top_size) + strings.len() + global_subrs.len();
+    let private_at = charstrings_at + charstrings.len();
+    let private_size = 6;
+    let mut private = dict_offset(private_size);
+    private.push(19);
+    let mut top = dict_offset(charstrings_at);
+    top.push(17);
+    top.extend(dict_offset(private_size));
+    top.extend(dict_offset(private_at));
+    top.push(18);
+    assert_eq!(top.len(), top_size);
+
+    let table = [&header[..], &names, &index(&[&top]), &strings, &global_subrs,
+                 &charstrings, &private, &index(&[&local_subr])].concat();
+    let cff = CffFont::new(&table, 0, table.len()).unwrap();
+
+    let outline = |glyph: u16| {
+        let mut path = Path::new();
+        cff.outline(&table, glyph, &mut OutlineBuilder::new(&mut path, 1.0, Point { x: 0.0, y: 0.0 })).unwrap();
+        path
+    };
+    let p = |x: f32, y: f32| Point { x: x, y: -y };
+
+    let mut curves = Path::new();
+    curves.move_to(p(100.0, 100.0))
+          .cubic_to(p(100.0, 300.0), p(200.0, 400.0), p(400.0, 400.0))
+          .cubic_to(p(500.0, 400.0), p(550.0, 300.0), p(550.0, 100.0))
+          .line_to(p(100.0, 100.0))
+          .close();
+    let mut square = Path::new();
+    square.move_to(p(50.0, 50.0))
+          .line_to(p(150.0, 50.0))
+          .line_to(p(150.0, 150.0))
+          .line_to(p(50.0, 150.0))
+          .close();
+
+    assert!(outline(0).bounding_box().is_none());
+    for &(glyph, ref expected) in &[(1, curves), (2, square)] {
+        let path = outline(glyph);
+        assert!(path.bounding_box().unwrap().close_to(&expected.bounding_box().unwrap(), 0.001));
+        for i in 0 .. 30 {
+            for j in 0 .. 30 {
+                let q = p(i as f32 * 25.0 - 50.0, j as f32 * 25.0 - 50.0);
+                assert!((path.sample(q) - expected.sample(q)).abs() < 0.001, "glyph {} at {:?}", glyph, q);
+            }
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use std::io::{self, Write, BufWriter};
+use std::fs::File;
+
+use ::{OutputDevice, Operation, PathInfo};
+use ::util::geom::Point;
+use super::units::Unit;
+
+const EPSILON: f32 = 0.001;
+
+struct Polyline {
+    points: Vec<Point>,
+    closed: bool,
+    layer: &'static str,
+}
+
+pub struct DxfWriter {
+    size: (f32, f32),
+    unit: Option<Unit>,
+    conversion: f32,
+    current: Vec<Point>,
+    // Set by `start_path`; plain lines are guessed to be closed if they
+    // end where they started.
+    current_info: Option<PathInfo>,
+    polylines: Vec<Polyline>,
+}
+
+fn group<W: Write, V: ::std::fmt::Display>(out: &mut W, code: u32, value: V) -> io::Result<()> {
+    write!(out, "{}\r\n{}\r\n", code, value)
+}
+
+/// An object handle, written in hexadecimal.
+#[derive(Copy, Clone)]
+struct Handle(u32);
+
+impl Handle {
+    /// Returns this handle and moves on to the next one.
+    fn take(&mut self) -> Handle {
+        let handle = *self;
+        self.0 += 1;
+        handle
+    }
+}
+
+impl ::std::fmt::Display for Handle {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{:X}", self.0)
+    }
+}
+
+/// The handles of everything in the file besides the entities, which
+/// are numbered from `first_entity` on.
+struct Handles {
+    ltype_table: Handle,
+    ltypes: [Handle; 3],
+    layer_table: Handle,
+    layers: [Handle; 4],
+    block_record_table: Handle,
+    model_space: Handle,
+    paper_space: Handle,
+    appid_table: Handle,
+    acad_appid: Handle,
+    // The BLOCK and ENDBLK of each space.
+    model_space_block: (Handle, Handle),
+    paper_space_block: (Handle, Handle),
+    root_dictionary: Handle,
+    group_dictionary: Handle,
+    first_entity: Handle,
+}
+
+/// The layers written to every file, with their ACI colours.
+fn layers() -> [(&'static str, u32); 4] {
+    [("0", 7),
+     (Operation::Cut.name(), 1),
+     (Operation::Score.name(), 5),
+     (Operation::Engrave.name(), 7)]
+}
+
+fn start_table<W: Write>(out: &mut W, name: &str, handle: Handle, entries: usize) -> io::Result<()> {
+    try!(group(out, 0, "TABLE"));
+    try!(group(out, 2, name));
+    try!(group(out, 5, handle));
+    try!(group(out, 330, 0));
+    try!(group(out, 100, "AcDbSymbolTable"));
+    group(out, 70, entries)
+}
+
+fn start_record<W: Write>(out: &mut W, kind: &str, subclass: &str, handle: Handle, table: Handle) -> io::Result<()> {
+    try!(group(out, 0, kind));
+    try!(group(out, 5, handle));
+    try!(group(out, 330, table));
+    try!(group(out, 100, "AcDbSymbolTableRecord"));
+    group(out, 100, subclass)
+}
+
+fn write_tables<W: Write>(out: &mut W, handles: &Handles) -> io::Result<()> {
+    try!(start_table(out, "LTYPE", handles.ltype_table, handles.ltypes.len()));
+    let ltypes = ["ByBlock", "ByLayer", "Continuous"];
+    for (&name, &handle) in ltypes.iter().zip(handles.ltypes.iter()) {
+        try!(start_record(out, "LTYPE", "AcDbLinetypeTableRecord", handle, handles.ltype_table));
+        try!(group(out, 2, name));
+        try!(group(out, 70, 0));
+        try!(group(out, 3, if name == "Continuous" { "Solid line" } else { "" }));
+        try!(group(out, 72, 65));
+        try!(group(out, 73, 0));
+        try!(group(out, 40, 0.0));
+    }
+    try!(group(out, 0, "ENDTAB"));
+
+    try!(start_table(out, "LAYER", handles.layer_table, handles.layers.len()));
+    for (&(name, color), &handle) in layers().iter().zip(handles.layers.iter()) {
+        try!(start_record(out, "LAYER", "AcDbLayerTableRecord", handle, handles.layer_table));
+        try!(group(out, 2, name));
+        try!(group(out, 70, 0));
+        try!(group(out, 62, color));
+        try!(group(out, 6, "Continuous"));
+    }
+    try!(group(out, 0, "ENDTAB"));
+
+    try!(start_table(out, "BLOCK_RECORD", handles.block_record_table, 2));
+    for &(name, handle) in &[("*Model_Space", handles.model_space), ("*Paper_Space", handles.paper_space)] {
+        try!(start_record(out, "BLOCK_RECORD", "AcDbBlockTableRecord", handle, handles.block_record_table));
+        try!(group(out, 2, name));
+    }
+    try!(group(out, 0, "ENDTAB"));
+
+    try!(start_table(out, "APPID", handles.appid_table, 1));
+    try!(start_record(out, "APPID", "AcDbRegAppTableRecord", handles.acad_appid, handles.appid_table));
+    try!(group(out, 2, "ACAD"));
+    try!(group(out, 70, 0));
+    group(out, 0, "ENDTAB")
+}
+
+fn write_block<W: Write>(out: &mut W, name: &str, (begin, end): (Handle, Handle), record: Handle,
+                         paper_space: bool) -> io::Result<()> {
+    try!(group(out, 0, "BLOCK"));
+    try!(group(out, 5, begin));
+    try!(group(out, 330, record));
+    try!(group(out, 100, "AcDbEntity"));
+    if paper_space {
+        try!(group(out, 67, 1));
+    }
+    try!(group(out, 8, "0"));
+    try!(group(out, 100, "AcDbBlockBegin"));
+    try!(group(out, 2, name));
+    try!(group(out, 70, 0));
+    try!(group(out, 10, 0.0));
+    try!(group(out, 20, 0.0));
+    try!(group(out, 30, 0.0));
+    try!(group(out, 3, name));
+    try!(group(out, 1, ""));
+    try!(group(out, 0, "ENDBLK"));
+    try!(group(out, 5, end));
+    try!(group(out, 330, record));
+    try!(group(out, 100, "AcDbEntity"));
+    if paper_space {
+        try!(group(out, 67, 1));
+    }
+    try!(group(out, 8, "0"));
+    group(out, 100, "AcDbBlockEnd")
+}
+
+impl DxfWriter {
+    /// Creates a writer whose coordinates are multiplied by
+    /// `conversion_factor`.  The result is declared to be in `units`
+    /// ("in", "mm", "cm", ...) in the drawing header, or left unitless if
+    /// the name isn't recognized.
+    pub fn new(units: &str, conversion_factor: f32) -> DxfWriter {
+        DxfWriter {
+            size: (800.0, 800.0),
+            unit: Unit::from_name(units),
+            conversion: conversion_factor,
+            current: vec![],
+            current_info: None,
+            polylines: vec![],
+        }
+    }
+
+    /// The value of the `$INSUNITS` header variable.
+    fn insunits(&self) -> u32 {
+        match self.unit {
+            None | Some(Unit::Points) => 0,
+            Some(Unit::Inches) => 1,
+            Some(Unit::Feet) => 2,
+            Some(Unit::Millimeters) => 4,
+            Some(Unit::Centimeters) => 5,
+            Some(Unit::Meters) => 6,
+        }
+    }
+
+    /// Writes the document to a file at `path`.
+    pub fn write_out(&self, path: &str) -> io::Result<()> {
+        let file = try!(File::create(path));
+        let mut out = BufWriter::new(file);
+        try!(self.write_to(&mut out));
+        out.flush()
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        // Every table, record, block and entity needs a handle, and the
+        // header has to say which handle is free next, so they are all
+        // handed out before anything is written.
+        let mut next = Handle(1);
+        let handles = Handles {
+            ltype_table: next.take(),
+            ltypes: [next.take(), next.take(), next.take()],
+            layer_table: next.take(),
+            layers: [next.take(), next.take(), next.take(), next.take()],
+            block_record_table: next.take(),
+            model_space: next.take(),
+            paper_space: next.take(),
+            appid_table: next.take(),
+            acad_appid: next.take(),
+            model_space_block: (next.take(), next.take()),
+            paper_space_block: (next.take(), next.take()),
+            root_dictionary: next.take(),
+            group_dictionary: next.take(),
+            first_entity: next,
+        };
+        let seed = Handle(next.0 + self.polylines.len() as u32);
+
+        try!(group(out, 0, "SECTION"));
+        try!(group(out, 2, "HEADER"));
+        try!(group(out, 9, "$ACADVER"));
+        try!(group(out, 1, "AC1015"));
+        try!(group(out, 9, "$HANDSEED"));
+        try!(group(out, 5, seed));
+        try!(group(out, 9, "$INSUNITS"));
+        try!(group(out, 70, self.insunits()));
+        try!(group(out, 9, "$EXTMIN"));
+        try!(group(out, 10, 0.0));
+        try!(group(out, 20, 0.0));
+        try!(group(out, 9, "$EXTMAX"));
+        try!(group(out, 10, self.size.0));
+        try!(group(out, 20, self.size.1));
+        try!(group(out, 0, "ENDSEC"));
+
+        try!(group(out, 0, "SECTION"));
+        try!(group(out, 2, "TABLES"));
+        try!(write_tables(out, &handles));
+        try!(group(out, 0, "ENDSEC"));
+
+        try!(group(out, 0, "SECTION"));
+        try!(group(out, 2, "BLOCKS"));
+        try!(write_block(out, "*Model_Space", handles.model_space_block, handles.model_space, false));
+        try!(write_block(out, "*Paper_Space", handles.paper_space_block, handles.paper_space, true));
+        try!(group(out, 0, "ENDSEC"));
+
+        try!(group(out, 0, "SECTION"));
+        try!(group(out, 2, "ENTITIES"));
+        for (i, polyline) in self.polylines.iter().enumerate() {
+            try!(group(out, 0, "LWPOLYLINE"));
+            try!(group(out, 5, Handle(handles.first_entity.0 + i as u32)));
+            try!(group(out, 330, handles.model_space));
+            try!(group(out, 100, "AcDbEntity"));
+            try!(group(out, 8, polyline.layer));
+            try!(group(out, 100, "AcDbPolyline"));
+            try!(group(out, 90, polyline.points.len()));
+            try!(group(out, 70, if polyline.closed { 1 } else { 0 }));
+            for &Point{x, y} in &polyline.points {
+                try!(group(out, 10, x));
+                try!(group(out, 20, y));
+            }
+        }
+        try!(group(out, 0, "ENDSEC"));
+
+        try!(group(out, 0, "SECTION"));
+        try!(group(out, 2, "OBJECTS"));
+        try!(group(out, 0, "DICTIONARY"));
+        try!(group(out, 5, handles.root_dictionary));
+        try!(group(out, 330, 0));
+        try!(group(out, 100, "AcDbDictionary"));
+        try!(group(out, 281, 1));
+        try!(group(out, 3, "ACAD_GROUP"));
+        try!(group(out, 350, handles.group_dictionary));
+        try!(group(out, 0, "DICTIONARY"));
+        try!(group(out, 5, handles.group_dictionary));
+        try!(group(out, 330, handles.root_dictionary));
+        try!(group(out, 100, "AcDbDictionary"));
+        try!(group(out, 281, 1));
+        try!(group(out, 0, "ENDSEC"));
+        group(out, 0, "EOF")
+    }
+
+    // DXF has y pointing up.
+    fn transform_point(&self, x: f32, y: f32) -> Point {
+        Point {
+            x: x * self.conversion,
+            y: self.size.1 - y * self.conversion,
+        }
+    }
+}
+
+impl OutputDevice for DxfWriter {
+    fn start_line(&mut self) {
+        self.current.clear();
+    }
+
+    fn add_point(&mut self, Point{x, y}: Point) {
+        let p = self.transform_point(x, y);
+        self.current.push(p);
+    }
+
+    fn end_line(&mut self) {
+        let mut points = ::std::mem::replace(&mut self.current, vec![]);
+        let info = self.current_info.take();
+        if points.len() < 2 {
+            return;
+        }
+
+        let ends_at_start = points.len() > 2 && points[0].close_to(points.last().unwrap(), EPSILON);
+        let (closed, layer) = match info {
+            Some(info) => (info.closed, info.operation.name()),
+            None => (ends_at_start, "0"),
+        };
+        if closed && ends_at_start {
+            // The closed flag draws the final segment for us.
+            points.pop();
+        }
+        self.polylines.push(Polyline { points: points, closed: closed, layer: layer });
+    }
+
+    fn start_path(&mut self, info: &PathInfo) {
+        self.start_line();
+        self.current_info = Some(*info);
+    }
+
+    fn set_size(&mut self, w: f32, h: f32) {
+        self.size = (w * self.conversion, h * self.conversion);
+    }
+}
+
+#[test]
+fn polylines_read_back_with_all_vertices() {
+    let mut dxf = DxfWriter::new("mm", 2.0);
+    dxf.set_size(10.0, 10.0);
+
+    let triangle = [Point { x: 0.0, y: 0.0 },
+                    Point { x: 5.0, y: 0.0 },
+                    Point { x: 5.0, y: 5.0 },
+                    Point { x: 0.0, y: 0.0 }];
+    let info = PathInfo::new(Operation::Cut, true, 0);
+    dxf.start_path(&info);
+    for &p in &triangle {
+        dxf.add_point(p);
+    }
+    dxf.end_path(&info);
+
+    dxf.start_line();
+    dxf.add_point(Point { x: 1.0, y: 1.0 });
+    dxf.add_point(Point { x: 2.0, y: 1.0 });
+    dxf.end_line();
+
+    let mut buffer = vec![];
+    dxf.write_to(&mut buffer).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+    let lines: Vec<&str> = text.lines().map(|l| l.trim()).collect();
+    assert_eq!(lines.len() % 2, 0);
+
+    // (layer, declared vertex count, vertices read, closed flag)
+    let mut polylines: Vec<(String, usize, usize, u32)> = vec![];
+    // (name, colour)
+    let mut layers: Vec<(String, u32)> = vec![];
+    let mut handles = vec![];
+    let mut sections = vec![];
+    let mut entity = "";
+    let mut variable = "";
+    let mut insunits = None;
+    let mut seed = None;
+    for pair in lines.chunks(2) {
+        match (pair[0], pair[1]) {
+            ("0", name) => {
+                entity = name;
+                variable = "";
+                match name {
+                    "LWPOLYLINE" => polylines.push((String::new(), 0, 0, 0)),
+                    "LAYER" => layers.push((String::new(), 0)),
+                    _ => {}
+                }
+            }
+            ("2", name) if entity == "SECTION" => sections.push(name.to_string()),
+            ("9", name) => variable = name,
+            ("70", value) if variable == "$INSUNITS" => insunits = Some(value.to_string()),
+            ("5", value) if variable == "$HANDSEED" => seed = Some(u32::from_str_radix(value, 16).unwrap()),
+            ("5", value) => handles.push(u32::from_str_radix(value, 16).unwrap()),
+            ("2", name) if entity == "LAYER" => layers.last_mut().unwrap().0 = name.to_string(),
+            ("62", value) if entity == "LAYER" => layers.last_mut().unwrap().1 = value.parse().unwrap(),
+            ("8", name) if entity == "LWPOLYLINE" => polylines.last_mut().unwrap().0 = name.to_string(),
+            ("90", value) if entity == "LWPOLYLINE" => polylines.last_mut().unwrap().1 = value.parse().unwrap(),
+            ("10", _) if entity == "LWPOLYLINE" => polylines.last_mut().unwrap().2 += 1,
+            ("70", value) if entity == "LWPOLYLINE" => polylines.last_mut().unwrap().3 = value.parse().unwrap(),
+            _ => {}
+        }
+    }
+
+    assert_eq!(sections, vec!["HEADER", "TABLES", "BLOCKS", "ENTITIES", "OBJECTS"]);
+    assert_eq!(insunits, Some("4".to_string()));
+    assert_eq!(layers, vec![("0".to_string(), 7),
+                            ("cut".to_string(), 1),
+                            ("score".to_string(), 5),
+                            ("engrave".to_string(), 7)]);
+    assert_eq!(polylines, vec![("cut".to_string(), 3, 3, 1), ("0".to_string(), 2, 2, 0)]);
+
+    // Handles are unique, and the seed is past all of them.
+    let count = handles.len();
+    handles.sort();
+    handles.dedup();
+    assert_eq!(handles.len(), count);
+    assert!(seed.unwrap() > *handles.last().unwrap());
+}
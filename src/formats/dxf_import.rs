@@ -0,0 +1,549 @@
+use std::io::{self, Read};
+use std::fs::File;
+use std::path::Path;
+use std::error::Error;
+use std::fmt;
+use std::f32::consts::PI;
+
+use ::{PolyGroup, Circle};
+use ::util::geom::{Point, Vector, Polygon};
+use super::import::{self, ImportedShape};
+
+// How far the extrusion direction can lean before a shape is taken to
+// be out of the XY plane.
+const EXTRUSION_EPSILON: f32 = 0.000001;
+
+/// Everything that can go wrong while reading a DXF drawing.
+#[derive(Debug)]
+pub enum DxfError {
+    Io(io::Error),
+    /// The file isn't made of well formed group code / value pairs.
+    Syntax { line: usize, message: String },
+    /// An entity type that can't be turned into an outline, like TEXT or
+    /// INSERT.
+    UnsupportedEntity { name: String, line: usize },
+    /// An entity with missing or unreadable values.
+    InvalidEntity { name: String, line: usize, message: String },
+    /// A chain of entities that ends at `at` (in drawing coordinates)
+    /// without getting back to where it started.
+    OpenLoop { at: Point },
+    /// More than two entities meet at `at` (in drawing coordinates), so
+    /// there's no single way to chain them into loops.
+    AmbiguousLoop { at: Point },
+    /// The drawing doesn't contain any entities.
+    Empty,
+}
+
+impl fmt::Display for DxfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DxfError::Io(ref e) => write!(f, "{}", e),
+            DxfError::Syntax { line, ref message } =>
+                write!(f, "line {}: {}", line, message),
+            DxfError::UnsupportedEntity { ref name, line } =>
+                write!(f, "line {}: {} entities can't be imported", line, name),
+            DxfError::InvalidEntity { ref name, line, ref message } =>
+                write!(f, "line {}: invalid {}: {}", line, name, message),
+            DxfError::OpenLoop { at } =>
+                write!(f, "the outline isn't closed at ({}, {})", at.x, at.y),
+            DxfError::AmbiguousLoop { at } =>
+                write!(f, "more than two entities meet at ({}, {})", at.x, at.y),
+            DxfError::Empty => write!(f, "the drawing doesn't contain any entities"),
+        }
+    }
+}
+
+impl Error for DxfError {
+    fn description(&self) -> &str {
+        match *self {
+            DxfError::Io(ref e) => e.description(),
+            DxfError::Syntax { .. } => "malformed dxf file",
+            DxfError::UnsupportedEntity { .. } => "unsupported dxf entity",
+            DxfError::InvalidEntity { .. } => "invalid dxf entity",
+            DxfError::OpenLoop { .. } => "open outline in dxf drawing",
+            DxfError::AmbiguousLoop { .. } => "ambiguous outline in dxf drawing",
+            DxfError::Empty => "dxf drawing without entities",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            DxfError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DxfError {
+    fn from(e: io::Error) -> DxfError {
+        DxfError::Io(e)
+    }
+}
+
+/// Reads the outlines out of the DXF file at `path`.
+///
+/// See `read_dxf`.
+pub fn read_dxf_file<P: AsRef<Path>>(path: P, tolerance: f32) -> Result<ImportedShape, DxfError> {
+    let mut source = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut source));
+    read_dxf(&source, tolerance)
+}
+
+/// Reads the LINE, LWPOLYLINE, ARC, CIRCLE and SPLINE entities out of an
+/// ASCII DXF drawing and chains them into closed loops.
+///
+/// Endpoints closer than `tolerance` are joined, and curves are flattened
+/// to within `tolerance` of the real outline.  The loops are filled with
+/// the even-odd rule.  A drawing that is just one circle comes back as an
+/// exact `Circle`.
+///
+/// Coordinates are kept in drawing units, but y is flipped so that the
+/// drawing isn't upside down; error locations are given in the drawing's
+/// own coordinates so that they can be found in a CAD program.
+pub fn read_dxf(source: &str, tolerance: f32) -> Result<ImportedShape, DxfError> {
+    let tolerance = tolerance.max(0.000001);
+    let pairs = try!(read_pairs(source));
+    let entities = try!(entities(&pairs));
+    if entities.is_empty() {
+        return Err(DxfError::Empty);
+    }
+
+    let mut loops = vec![];
+    let mut pieces = vec![];
+    let mut circles = vec![];
+    for entity in &entities {
+        match try!(read_entity(entity, tolerance)) {
+            Outline::Circle(circle) => circles.push(circle),
+            Outline::Closed(points) => loops.push(points),
+            Outline::Open(points) => pieces.push(points),
+        }
+    }
+    loops.extend(try!(chain(pieces, tolerance)));
+
+    let flip = |p: Point| Point { x: p.x, y: -p.y };
+    if loops.is_empty() && circles.len() == 1 {
+        return Ok(ImportedShape::Circle(Circle {
+            center: flip(circles[0].center),
+            radius: circles[0].radius,
+        }));
+    }
+
+    for circle in circles {
+        let start = Point { x: circle.center.x + circle.radius, y: circle.center.y };
+        let mut points = vec![start];
+        points.extend(import::arc_points(circle.center, circle.radius, 0.0, 2.0 * PI, tolerance));
+        points.pop();
+        loops.push(points);
+    }
+
+    let polys = loops.into_iter()
+                     .filter(|points| points.len() >= 3)
+                     .map(|points| Polygon::new(points.into_iter().map(&flip)))
+                     .collect();
+    Ok(ImportedShape::Polygons(PolyGroup { polys: polys }))
+}
+
+/// A group code and its value, with the line that the code is on.
+struct Pair<'a> {
+    code: i32,
+    value: &'a str,
+    line: usize,
+}
+
+fn read_pairs(source: &str) -> Result<Vec<Pair>, DxfError> {
+    let mut pairs = vec![];
+    let mut lines = source.lines().enumerate();
+    while let Some((i, code)) = lines.next() {
+        let code = code.trim();
+        if code.is_empty() {
+            continue;
+        }
+        let code = match code.parse() {
+            Ok(c) => c,
+            Err(_) => return Err(DxfError::Syntax {
+                line: i + 1,
+                message: format!("expected a group code, found \"{}\"", code),
+            }),
+        };
+        let value = match lines.next() {
+            Some((_, value)) => value.trim(),
+            None => return Err(DxfError::Syntax {
+                line: i + 1,
+                message: "group code without a value".to_string(),
+            }),
+        };
+        pairs.push(Pair { code: code, value: value, line: i + 1 });
+    }
+    Ok(pairs)
+}
+
+/// Splits the ENTITIES section into one slice of pairs per entity, each
+/// starting with its type.
+fn entities<'a, 'b>(pairs: &'b [Pair<'a>]) -> Result<Vec<&'b [Pair<'a>]>, DxfError> {
+    let start = pairs.windows(2).position(|w| {
+        w[0].code == 0 && w[0].value == "SECTION" && w[1].code == 2 && w[1].value == "ENTITIES"
+    });
+    let start = match start {
+        Some(i) => i + 2,
+        None => return Err(DxfError::Empty),
+    };
+
+    let mut entities = vec![];
+    let mut i = start;
+    while i < pairs.len() {
+        if pairs[i].code != 0 {
+            return Err(DxfError::Syntax {
+                line: pairs[i].line,
+                message: "expected the start of an entity".to_string(),
+            });
+        }
+        if pairs[i].value == "ENDSEC" {
+            return Ok(entities);
+        }
+        let len = pairs[i + 1 ..].iter().position(|p| p.code == 0).unwrap_or(pairs.len() - i - 1);
+        entities.push(&pairs[i .. i + 1 + len]);
+        i += 1 + len;
+    }
+    Err(DxfError::Syntax {
+        line: pairs.last().map(|p| p.line).unwrap_or(1),
+        message: "missing ENDSEC".to_string(),
+    })
+}
+
+enum Outline {
+    Circle(Circle),
+    Closed(Vec<Point>),
+    Open(Vec<Point>),
+}
+
+fn read_entity(entity: &[Pair], tolerance: f32) -> Result<Outline, DxfError> {
+    let name = entity[0].value;
+    let line = entity[0].line;
+    let invalid = |message: &str| DxfError::InvalidEntity {
+        name: name.to_string(),
+        line: line,
+        message: message.to_string(),
+    };
+
+    // Every value of a group code, parsed as a number.
+    let values = |code: i32| -> Result<Vec<f32>, DxfError> {
+        let mut out = vec![];
+        for pair in entity.iter().filter(|p| p.code == code) {
+            match pair.value.parse() {
+                Ok(v) => out.push(v),
+                Err(_) => return Err(DxfError::InvalidEntity {
+                    name: name.to_string(),
+                    line: pair.line,
+                    message: format!("\"{}\" isn't a number", pair.value),
+                }),
+            }
+        }
+        Ok(out)
+    };
+    let value = |code: i32| -> Result<f32, DxfError> {
+        match try!(values(code)).first() {
+            Some(&v) => Ok(v),
+            None => Err(invalid(&format!("missing group code {}", code))),
+        }
+    };
+    let flags = |code: i32| -> Result<i32, DxfError> {
+        Ok(try!(values(code)).first().map(|&v| v as i32).unwrap_or(0))
+    };
+    // Arcs, circles and polylines are drawn in their own coordinate
+    // system, around an extrusion direction (groups 210, 220 and 230).
+    // Pointing down the z axis mirrors x; any other tilt takes the shape
+    // out of the XY plane.
+    let mirrored = || -> Result<bool, DxfError> {
+        let x = try!(values(210)).first().cloned().unwrap_or(0.0);
+        let y = try!(values(220)).first().cloned().unwrap_or(0.0);
+        let z = try!(values(230)).first().cloned().unwrap_or(1.0);
+        if x.abs() > EXTRUSION_EPSILON || y.abs() > EXTRUSION_EPSILON || z == 0.0 {
+            return Err(invalid("the extrusion direction isn't along the z axis"));
+        }
+        Ok(z < 0.0)
+    };
+    let to_world = |mirrored: bool, points: Vec<Point>| -> Vec<Point> {
+        if mirrored {
+            points.into_iter().map(|p| Point { x: -p.x, y: p.y }).collect()
+        } else {
+            points
+        }
+    };
+
+    match name {
+        "LINE" => {
+            let a = Point { x: try!(value(10)), y: try!(value(20)) };
+            let b = Point { x: try!(value(11)), y: try!(value(21)) };
+            Ok(Outline::Open(vec![a, b]))
+        }
+        "CIRCLE" => {
+            let radius = try!(value(40));
+            if radius <= 0.0 {
+                return Err(invalid("the radius isn't positive"));
+            }
+            let center = Point { x: try!(value(10)), y: try!(value(20)) };
+            let center = to_world(try!(mirrored()), vec![center])[0];
+            Ok(Outline::Circle(Circle { center: center, radius: radius }))
+        }
+        "ARC" => {
+            let center = Point { x: try!(value(10)), y: try!(value(20)) };
+            let radius = try!(value(40));
+            let start = try!(value(50)).to_radians();
+            let mut sweep = try!(value(51)).to_radians() - start;
+            // Arcs always run counterclockwise from the start angle.
+            while sweep <= 0.0 {
+                sweep += 2.0 * PI;
+            }
+            let first = Point {
+                x: center.x + radius * start.cos(),
+                y: center.y + radius * start.sin(),
+            };
+            let mut points = vec![first];
+            points.extend(import::arc_points(center, radius, start, sweep, tolerance));
+            Ok(Outline::Open(to_world(try!(mirrored()), points)))
+        }
+        "LWPOLYLINE" => {
+            let mut vertices: Vec<(Point, f32)> = vec![];
+            for pair in &entity[1..] {
+                let v = match pair.code {
+                    10 | 20 | 42 => match pair.value.parse() {
+                        Ok(v) => v,
+                        Err(_) => return Err(invalid(&format!("\"{}\" isn't a number", pair.value))),
+                    },
+                    _ => continue,
+                };
+                match (pair.code, vertices.last_mut()) {
+                    (10, _) => vertices.push((Point { x: v, y: 0.0 }, 0.0)),
+                    (20, Some(last)) => last.0.y = v,
+                    (42, Some(last)) => last.1 = v,
+                    _ => return Err(invalid("vertex data before the first vertex")),
+                }
+            }
+            if vertices.len() < 2 {
+                return Err(invalid("fewer than two vertices"));
+            }
+
+            let closed = try!(flags(70)) & 1 != 0;
+            let segments = if closed { vertices.len() } else { vertices.len() - 1 };
+            let mut points = vec![vertices[0].0];
+            for i in 0 .. segments {
+                let (from, bulge) = vertices[i];
+                let to = vertices[(i + 1) % vertices.len()].0;
+                points.extend(bulge_points(from, to, bulge, tolerance));
+            }
+            let mut points = to_world(try!(mirrored()), points);
+            if closed {
+                points.pop();
+                Ok(Outline::Closed(points))
+            } else {
+                Ok(Outline::Open(points))
+            }
+        }
+        "SPLINE" => {
+            let closed = try!(flags(70)) & 1 != 0;
+            let degree = try!(flags(71));
+            let knots = try!(values(40));
+            let weights = try!(values(41));
+            let control: Vec<_> = try!(values(10)).into_iter()
+                                                  .zip(try!(values(20)))
+                                                  .map(|(x, y)| Point { x: x, y: y })
+                                                  .collect();
+            let fit: Vec<_> = try!(values(11)).into_iter()
+                                              .zip(try!(values(21)))
+                                              .map(|(x, y)| Point { x: x, y: y })
+                                              .collect();
+
+            let mut points = if !control.is_empty() {
+                let degree = if degree > 0 { degree as usize } else { 3 };
+                if knots.len() != control.len() + degree + 1 {
+                    return Err(invalid("the number of knots doesn't match the control points"));
+                }
+                let weights = if weights.len() == control.len() { weights } else { vec![1.0; control.len()] };
+                flatten_spline(degree, &knots, &control, &weights, tolerance)
+            } else if fit.len() >= 2 {
+                // Without control points, the best we can do is to connect
+                // the points that the curve passes through.
+                fit
+            } else {
+                return Err(invalid("no control or fit points"));
+            };
+
+            if closed {
+                if points.len() > 1 && points[0].close_to(points.last().unwrap(), tolerance) {
+                    points.pop();
+                }
+                Ok(Outline::Closed(points))
+            } else {
+                Ok(Outline::Open(points))
+            }
+        }
+        _ => Err(DxfError::UnsupportedEntity { name: name.to_string(), line: line }),
+    }
+}
+
+/// The points of a polyline segment from `from` to `to`, not including
+/// `from`.  The bulge is the tangent of a quarter of the included angle
+/// of an arc, positive for counterclockwise arcs; zero is a straight line.
+fn bulge_points(from: Point, to: Point, bulge: f32, tolerance: f32) -> Vec<Point> {
+    let chord = to - from;
+    let length = chord.magnitude();
+    if bulge == 0.0 || length == 0.0 {
+        return vec![to];
+    }
+
+    let normal = Vector { x: -chord.y, y: chord.x } / length;
+    let mid = from + chord / 2.0;
+    let center = mid + normal * (length / 2.0 * (1.0 - bulge * bulge) / (2.0 * bulge));
+    let radius = (from - center).magnitude();
+    let start = (from.y - center.y).atan2(from.x - center.x);
+    let sweep = 4.0 * bulge.atan();
+
+    let mut points = import::arc_points(center, radius, start, sweep, tolerance);
+    if let Some(last) = points.last_mut() {
+        *last = to;
+    }
+    points
+}
+
+/// Evaluates a (possibly rational) B-spline at evenly spaced parameters
+/// in each knot span.
+fn flatten_spline(degree: usize, knots: &[f32], control: &[Point], weights: &[f32], tolerance: f32) -> Vec<Point> {
+    // Wang's formula over the control polygon gives a conservative number
+    // of steps per span.
+    let mut dd: f32 = 0.0;
+    for w in control.windows(3) {
+        let v = Vector { x: w[0].x - 2.0 * w[1].x + w[2].x, y: w[0].y - 2.0 * w[1].y + w[2].y };
+        dd = dd.max(v.magnitude());
+    }
+    let steps = ((0.75 * dd / tolerance).sqrt().ceil() as usize).max(1).min(256);
+
+    let mut points = vec![];
+    for span in degree .. control.len() {
+        let (t0, t1) = (knots[span], knots[span + 1]);
+        if t1 <= t0 {
+            continue;
+        }
+        let first = if points.is_empty() { 0 } else { 1 };
+        for i in first .. steps + 1 {
+            let t = t0 + (t1 - t0) * (i as f32 / steps as f32);
+            points.push(de_boor(degree, knots, control, weights, span, t));
+        }
+    }
+    points
+}
+
+fn de_boor(degree: usize, knots: &[f32], control: &[Point], weights: &[f32], span: usize, t: f32) -> Point {
+    // Homogeneous coordinates take care of the weights.
+    let mut d: Vec<(f32, f32, f32)> = (0 .. degree + 1).map(|j| {
+        let p = control[j + span - degree];
+        let w = weights[j + span - degree];
+        (p.x * w, p.y * w, w)
+    }).collect();
+
+    for r in 1 .. degree + 1 {
+        for j in (r .. degree + 1).rev() {
+            let i = j + span - degree;
+            let denominator = knots[i + degree + 1 - r] - knots[i];
+            let alpha = if denominator == 0.0 { 0.0 } else { (t - knots[i]) / denominator };
+            let (a, b) = (d[j - 1], d[j]);
+            d[j] = ((1.0 - alpha) * a.0 + alpha * b.0,
+                    (1.0 - alpha) * a.1 + alpha * b.1,
+                    (1.0 - alpha) * a.2 + alpha * b.2);
+        }
+    }
+
+    let (x, y, w) = d[degree];
+    Point { x: x / w, y: y / w }
+}
+
+/// Joins open pieces end to end into closed loops.
+fn chain(mut pieces: Vec<Vec<Point>>, tolerance: f32) -> Result<Vec<Vec<Point>>, DxfError> {
+    let mut loops = vec![];
+
+    while let Some(mut current) = pieces.pop() {
+        loop {
+            let start = current[0];
+            let end = *current.last().unwrap();
+            let closes = current.len() > 2 && start.close_to(&end, tolerance);
+            let next: Vec<usize> = (0 .. pieces.len()).filter(|&i| {
+                pieces[i][0].close_to(&end, tolerance) ||
+                pieces[i].last().unwrap().close_to(&end, tolerance)
+            }).collect();
+
+            if next.len() + (closes as usize) > 1 {
+                return Err(DxfError::AmbiguousLoop { at: end });
+            }
+
+            if closes {
+                current.pop();
+                loops.push(current);
+                break;
+            }
+
+            match next.first() {
+                Some(&i) => {
+                    let mut piece = pieces.swap_remove(i);
+                    if !piece[0].close_to(&end, tolerance) {
+                        piece.reverse();
+                    }
+                    current.extend(piece.into_iter().skip(1));
+                }
+                None => return Err(DxfError::OpenLoop { at: end }),
+            }
+        }
+    }
+
+    Ok(loops)
+}
+
+#[test]
+fn entities_are_chained_into_loops() {
+    use ::Implicit;
+
+    fn drawing(entities: &str) -> String {
+        format!("0\nSECTION\n2\nENTITIES\n{}0\nENDSEC\n0\nEOF\n", entities)
+    }
+
+    // A 10x10 square with its top edge replaced by an arc, written out of
+    // order and with one line reversed.
+    let outline = drawing("0\nLINE\n10\n0\n20\n0\n11\n10\n21\n0\n\
+                           0\nARC\n10\n5\n20\n10\n40\n5\n50\n0\n51\n180\n\
+                           0\nLINE\n10\n0\n20\n10\n11\n0\n21\n0\n\
+                           0\nLINE\n10\n10\n20\n0\n11\n10\n21\n10\n");
+    let shape = read_dxf(&outline, 0.001).unwrap();
+    assert!(shape.sample(Point { x: 5.0, y: -12.0 }) < 0.0);
+    assert!(shape.sample(Point { x: 5.0, y: 2.0 }) > 0.0);
+
+    let circle = drawing("0\nCIRCLE\n10\n1\n20\n2\n40\n3\n");
+    match read_dxf(&circle, 0.001).unwrap() {
+        ImportedShape::Circle(c) => assert_eq!((c.center.x, c.center.y, c.radius), (1.0, -2.0, 3.0)),
+        other => panic!("expected a circle, got {:?}", other),
+    }
+
+    // The same shape, with the arc drawn looking up from below.
+    let mirrored = drawing("0\nLINE\n10\n0\n20\n0\n11\n10\n21\n0\n\
+                            0\nARC\n10\n-5\n20\n10\n40\n5\n50\n0\n51\n180\n230\n-1\n\
+                            0\nLINE\n10\n0\n20\n10\n11\n0\n21\n0\n\
+                            0\nLINE\n10\n10\n20\n0\n11\n10\n21\n10\n");
+    let shape = read_dxf(&mirrored, 0.001).unwrap();
+    assert!(shape.sample(Point { x: 5.0, y: -12.0 }) < 0.0);
+    assert!(shape.sample(Point { x: 5.0, y: 2.0 }) > 0.0);
+
+    let circle = drawing("0\nCIRCLE\n10\n1\n20\n2\n40\n3\n210\n0\n220\n0\n230\n-1\n");
+    match read_dxf(&circle, 0.001).unwrap() {
+        ImportedShape::Circle(c) => assert_eq!((c.center.x, c.center.y, c.radius), (-1.0, -2.0, 3.0)),
+        other => panic!("expected a circle, got {:?}", other),
+    }
+
+    let tilted = drawing("0\nCIRCLE\n10\n1\n20\n2\n40\n3\n210\n1\n220\n0\n230\n0\n");
+    match read_dxf(&tilted, 0.001) {
+        Err(DxfError::InvalidEntity { .. }) => {}
+        other => panic!("expected an invalid entity, got {:?}", other.map(|_| ())),
+    }
+
+    let open = drawing("0\nLINE\n10\n0\n20\n0\n11\n10\n21\n0\n0\nLINE\n10\n10\n20\n0\n11\n10\n21\n10\n");
+    match read_dxf(&open, 0.001) {
+        Err(DxfError::OpenLoop { .. }) => {}
+        other => panic!("expected an open loop, got {:?}", other),
+    }
+}
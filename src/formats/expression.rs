@@ -0,0 +1,422 @@
+//! A small expression language for writing fields without recompiling,
+//! like `sqrt(x * x + y * y) - radius`.
+//!
+//! Expressions can use `x` and `y`, numbers, `pi`, named parameters,
+//! `+ - * / ^`, parentheses, and the functions `sin`, `cos`, `abs`,
+//! `sqrt`, `min` and `max`.
+
+use std::error::Error;
+use std::fmt;
+
+use ::Implicit;
+use ::util::geom::{Point, Rect};
+
+/// A mistake in the source of an expression.
+///
+/// Lines and columns both start at 1, and columns count characters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for ParseError {
+    fn description(&self) -> &str {
+        "invalid expression"
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Function {
+    Sin,
+    Cos,
+    Abs,
+    Sqrt,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn named(name: &str) -> Option<Function> {
+        match name {
+            "sin" => Some(Function::Sin),
+            "cos" => Some(Function::Cos),
+            "abs" => Some(Function::Abs),
+            "sqrt" => Some(Function::Sqrt),
+            "min" => Some(Function::Min),
+            "max" => Some(Function::Max),
+            _ => None,
+        }
+    }
+
+    fn arguments(&self) -> usize {
+        match *self {
+            Function::Min | Function::Max => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Constant(f32),
+    X,
+    Y,
+    Parameter(usize),
+    Negate(Box<Node>),
+    Binary(Operator, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+impl Node {
+    fn evaluate(&self, pos: Point, parameters: &[(String, f32)]) -> f32 {
+        match *self {
+            Node::Constant(c) => c,
+            Node::X => pos.x,
+            Node::Y => pos.y,
+            Node::Parameter(i) => parameters[i].1,
+            Node::Negate(ref a) => -a.evaluate(pos, parameters),
+            Node::Binary(op, ref a, ref b) => {
+                let (a, b) = (a.evaluate(pos, parameters), b.evaluate(pos, parameters));
+                match op {
+                    Operator::Add => a + b,
+                    Operator::Subtract => a - b,
+                    Operator::Multiply => a * b,
+                    Operator::Divide => a / b,
+                    Operator::Power => a.powf(b),
+                }
+            }
+            Node::Call(function, ref args) => {
+                let a = args[0].evaluate(pos, parameters);
+                match function {
+                    Function::Sin => a.sin(),
+                    Function::Cos => a.cos(),
+                    Function::Abs => a.abs(),
+                    Function::Sqrt => a.sqrt(),
+                    Function::Min => a.min(args[1].evaluate(pos, parameters)),
+                    Function::Max => a.max(args[1].evaluate(pos, parameters)),
+                }
+            }
+        }
+    }
+}
+
+/// A field parsed from an expression.
+///
+/// Like `FnImplicit`, the field is unbounded and assumed to change no
+/// faster than distance unless told otherwise.
+#[derive(Clone, Debug)]
+pub struct Expression {
+    root: Node,
+    parameters: Vec<(String, f32)>,
+    bounds: Option<Rect>,
+    lipschitz: f32,
+}
+
+impl Expression {
+    /// Parses `source`, which can refer to any of `parameters` by name.
+    pub fn parse(source: &str, parameters: &[(&str, f32)]) -> Result<Expression, ParseError> {
+        let mut parser = Parser {
+            source: source,
+            pos: 0,
+            parameters: parameters.iter().map(|&(name, value)| (name.to_string(), value)).collect(),
+        };
+        let root = try!(parser.expression());
+        parser.skip_whitespace();
+        if parser.pos < source.len() {
+            return Err(parser.error(parser.pos, "expected an operator"));
+        }
+
+        Ok(Expression {
+            root: root,
+            parameters: parser.parameters,
+            bounds: None,
+            lipschitz: 1.0,
+        })
+    }
+
+    /// Changes the value of a parameter.  Returns false if the expression
+    /// was parsed without a parameter called `name`.
+    pub fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match self.parameters.iter_mut().find(|p| p.0 == name) {
+            Some(p) => {
+                p.1 = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn with_bounding_box(self, bounds: Rect) -> Expression {
+        Expression { bounds: Some(bounds), .. self }
+    }
+
+    pub fn with_lipschitz_constant(self, lipschitz: f32) -> Expression {
+        assert!(lipschitz > 0.0, "the lipschitz constant must be positive");
+        Expression { lipschitz: lipschitz, .. self }
+    }
+}
+
+impl Implicit for Expression {
+    fn sample(&self, pos: Point) -> f32 {
+        self.root.evaluate(pos, &self.parameters)
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        self.bounds
+    }
+
+    fn follows_rules(&self) -> bool {
+        self.lipschitz <= 1.0
+    }
+
+    fn lipschitz_constant(&self) -> f32 {
+        self.lipschitz
+    }
+}
+
+/// A recursive descent parser, from the lowest precedence up:
+///
+/// ```text
+/// expression = term (("+" | "-") term)*
+/// term       = unary (("*" | "/") unary)*
+/// unary      = "-" unary | power
+/// power      = atom ("^" unary)?
+/// atom       = number | name | name "(" expression ("," expression)* ")" | "(" expression ")"
+/// ```
+struct Parser<'a> {
+    source: &'a str,
+    pos: usize,
+    parameters: Vec<(String, f32)>,
+}
+
+impl <'a> Parser<'a> {
+    fn error(&self, pos: usize, message: &str) -> ParseError {
+        let before = &self.source[..pos];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        ParseError {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.pos..];
+        self.pos += rest.len() - rest.trim_left().len();
+    }
+
+    /// The next character after any whitespace, without consuming it.
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.source[self.pos..].chars().next()
+    }
+
+    /// Consumes `c` if it's next.
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            let pos = self.pos;
+            Err(self.unexpected(pos, &format!("expected '{}'", c)))
+        }
+    }
+
+    /// An error for whatever is at `pos`, which says so if it's the end.
+    fn unexpected(&self, pos: usize, message: &str) -> ParseError {
+        if pos >= self.source.len() {
+            self.error(pos, &format!("{}, but the expression ended", message))
+        } else {
+            self.error(pos, message)
+        }
+    }
+
+    fn expression(&mut self) -> Result<Node, ParseError> {
+        let mut left = try!(self.term());
+        loop {
+            let op = if self.eat('+') {
+                Operator::Add
+            } else if self.eat('-') {
+                Operator::Subtract
+            } else {
+                return Ok(left);
+            };
+            let right = try!(self.term());
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn term(&mut self) -> Result<Node, ParseError> {
+        let mut left = try!(self.unary());
+        loop {
+            let op = if self.eat('*') {
+                Operator::Multiply
+            } else if self.eat('/') {
+                Operator::Divide
+            } else {
+                return Ok(left);
+            };
+            let right = try!(self.unary());
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ParseError> {
+        if self.eat('-') {
+            Ok(Node::Negate(Box::new(try!(self.unary()))))
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Node, ParseError> {
+        let base = try!(self.atom());
+        if self.eat('^') {
+            // Right associative, so 2^3^2 is 2^9.
+            let exponent = try!(self.unary());
+            Ok(Node::Binary(Operator::Power, Box::new(base), Box::new(exponent)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn atom(&mut self) -> Result<Node, ParseError> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let inner = try!(self.expression());
+                try!(self.expect(')'));
+                Ok(inner)
+            }
+            Some(c) if c.is_digit(10) || c == '.' => self.number(),
+            Some(c) if c.is_alphabetic() || c == '_' => self.name(),
+            _ => {
+                let pos = self.pos;
+                Err(self.unexpected(pos, "expected a number, a name or '('"))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Node, ParseError> {
+        let start = self.pos;
+        let bytes = self.source.as_bytes();
+        let mut end = start;
+        while end < bytes.len() && (is_digit(bytes[end]) || bytes[end] == b'.') {
+            end += 1;
+        }
+        // An exponent, but only if digits follow so that `2e` isn't eaten.
+        if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+            let mut digits = end + 1;
+            if digits < bytes.len() && (bytes[digits] == b'+' || bytes[digits] == b'-') {
+                digits += 1;
+            }
+            if digits < bytes.len() && is_digit(bytes[digits]) {
+                end = digits;
+                while end < bytes.len() && is_digit(bytes[end]) {
+                    end += 1;
+                }
+            }
+        }
+
+        match self.source[start..end].parse::<f32>() {
+            Ok(value) => {
+                self.pos = end;
+                Ok(Node::Constant(value))
+            }
+            Err(_) => Err(self.error(start, &format!("invalid number \"{}\"", &self.source[start..end]))),
+        }
+    }
+
+    fn name(&mut self) -> Result<Node, ParseError> {
+        let start = self.pos;
+        let length = self.source[start..]
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(self.source.len() - start);
+        self.pos += length;
+        let name = &self.source[start..start + length];
+
+        if self.peek() == Some('(') {
+            let function = match Function::named(name) {
+                Some(f) => f,
+                None => return Err(self.error(start, &format!("unknown function \"{}\"", name))),
+            };
+            self.pos += 1;
+            let mut args = vec![try!(self.expression())];
+            while self.eat(',') {
+                args.push(try!(self.expression()));
+            }
+            try!(self.expect(')'));
+            if args.len() != function.arguments() {
+                let plural = if function.arguments() == 1 { "" } else { "s" };
+                return Err(self.error(start, &format!("\"{}\" takes {} argument{}, not {}",
+                                                      name, function.arguments(), plural, args.len())));
+            }
+            return Ok(Node::Call(function, args));
+        }
+
+        match name {
+            "x" => Ok(Node::X),
+            "y" => Ok(Node::Y),
+            "pi" => Ok(Node::Constant(::std::f32::consts::PI)),
+            _ => match self.parameters.iter().position(|p| p.0 == name) {
+                Some(i) => Ok(Node::Parameter(i)),
+                None if Function::named(name).is_some() =>
+                    Err(self.error(start, &format!("\"{}\" is a function and needs arguments", name))),
+                None => Err(self.error(start, &format!("unknown name \"{}\"", name))),
+            },
+        }
+    }
+}
+
+fn is_digit(b: u8) -> bool {
+    b'0' <= b && b <= b'9'
+}
+
+#[test]
+fn expressions_parse_into_fields() {
+    let stripes = Expression::parse("sin(x)*sin(y)", &[]).unwrap();
+    assert_eq!(stripes.sample(Point { x: 1.0, y: 2.0 }), 1.0f32.sin() * 2.0f32.sin());
+
+    let mut circle = Expression::parse("sqrt(x^2 + y^2) - r", &[("r", 2.0)]).unwrap();
+    assert_eq!(circle.sample(Point { x: 3.0, y: 4.0 }), 3.0);
+    assert!(circle.set_parameter("r", 5.0));
+    assert_eq!(circle.sample(Point { x: 3.0, y: 4.0 }), 0.0);
+    assert!(!circle.set_parameter("radius", 1.0));
+
+    let precedence = Expression::parse("-2^2 + 10 / 4 * 2 - max(1, -abs(-3)) + 1.5e1", &[]).unwrap();
+    assert_eq!(precedence.sample(Point { x: 0.0, y: 0.0 }), -4.0 + 5.0 - 1.0 + 15.0);
+
+    let error = Expression::parse("min(x,\n  (y + ))", &[]).unwrap_err();
+    assert_eq!((error.line, error.column), (2, 8));
+    let error = Expression::parse("x * spin(y)", &[]).unwrap_err();
+    assert_eq!((error.column, &error.message[..]), (5, "unknown function \"spin\""));
+    let error = Expression::parse("max(x)", &[]).unwrap_err();
+    assert_eq!(error.column, 1);
+    let error = Expression::parse("x y", &[]).unwrap_err();
+    assert_eq!(error.column, 3);
+}
@@ -0,0 +1,778 @@
+//! Reading TrueType and OpenType fonts, and laying out text with them.
+//!
+//! Outlines come from either the `glyf` table (quadratic curves) or the
+//! `CFF ` table (cubic curves).  Characters are mapped with `cmap`
+//! formats 4 and 12, and spaced with `hmtx` and format 0 `kern` tables.
+//! Kerning that is only in `GPOS` isn't applied.
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::fs::File;
+use std::path::Path as FilePath;
+use std::error::Error;
+use std::fmt;
+
+use ::{Implicit, Path};
+use ::util::geom::{Point, Rect};
+use super::cff::CffFont;
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(io::Error),
+    /// The data isn't a TrueType or OpenType font.
+    UnknownFormat,
+    /// The font is damaged or truncated.
+    Malformed(String),
+    /// The font uses a feature that isn't implemented.
+    Unsupported(String),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FontError::Io(ref e) => write!(f, "{}", e),
+            FontError::UnknownFormat => write!(f, "the file isn't a TrueType or OpenType font"),
+            FontError::Malformed(ref message) => write!(f, "malformed font: {}", message),
+            FontError::Unsupported(ref message) => write!(f, "unsupported font: {}", message),
+        }
+    }
+}
+
+impl Error for FontError {
+    fn description(&self) -> &str {
+        match *self {
+            FontError::Io(ref e) => e.description(),
+            FontError::UnknownFormat => "unknown font format",
+            FontError::Malformed(_) => "malformed font",
+            FontError::Unsupported(_) => "unsupported font",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            FontError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FontError {
+    fn from(e: io::Error) -> FontError {
+        FontError::Io(e)
+    }
+}
+
+pub(crate) fn malformed<T>(message: &str) -> Result<T, FontError> {
+    Err(FontError::Malformed(message.to_string()))
+}
+
+pub(crate) fn read_u8(data: &[u8], pos: usize) -> Result<u8, FontError> {
+    match data.get(pos) {
+        Some(&b) => Ok(b),
+        None => malformed("unexpected end of data"),
+    }
+}
+
+pub(crate) fn read_u16(data: &[u8], pos: usize) -> Result<u16, FontError> {
+    Ok((try!(read_u8(data, pos)) as u16) << 8 | try!(read_u8(data, pos + 1)) as u16)
+}
+
+pub(crate) fn read_i16(data: &[u8], pos: usize) -> Result<i16, FontError> {
+    read_u16(data, pos).map(|v| v as i16)
+}
+
+pub(crate) fn read_u32(data: &[u8], pos: usize) -> Result<u32, FontError> {
+    Ok((try!(read_u16(data, pos)) as u32) << 16 | try!(read_u16(data, pos + 2)) as u32)
+}
+
+/// Where a table is in the font data.
+#[derive(Copy, Clone, Debug)]
+struct Table {
+    offset: usize,
+    length: usize,
+}
+
+#[derive(Clone, Debug)]
+enum Outlines {
+    TrueType { glyf: Table, loca: Vec<usize> },
+    Cff(CffFont),
+}
+
+/// A TrueType or OpenType font.
+#[derive(Clone, Debug)]
+pub struct Font {
+    data: Vec<u8>,
+    units_per_em: f32,
+    ascender: f32,
+    descender: f32,
+    line_gap: f32,
+    glyph_count: u16,
+    /// The format and offset of the best character map.
+    cmap: Option<(u16, usize)>,
+    advances: Vec<u16>,
+    kerning: HashMap<(u16, u16), i16>,
+    outlines: Outlines,
+}
+
+/// Turns outlines in font units, with y going up, into a `Path` in
+/// drawing units with y going down.
+pub(crate) struct OutlineBuilder<'a> {
+    path: &'a mut Path,
+    scale: f32,
+    origin: Point,
+}
+
+impl <'a> OutlineBuilder<'a> {
+    pub(crate) fn new(path: &'a mut Path, scale: f32, origin: Point) -> OutlineBuilder<'a> {
+        OutlineBuilder {
+            path: path,
+            scale: scale,
+            origin: origin,
+        }
+    }
+
+    fn point(&self, x: f32, y: f32) -> Point {
+        Point { x: self.origin.x + x * self.scale, y: self.origin.y - y * self.scale }
+    }
+
+    pub(crate) fn move_to(&mut self, x: f32, y: f32) {
+        let p = self.point(x, y);
+        self.path.move_to(p);
+    }
+
+    pub(crate) fn line_to(&mut self, x: f32, y: f32) {
+        let p = self.point(x, y);
+        self.path.line_to(p);
+    }
+
+    pub(crate) fn quad_to(&mut self, cx: f32, cy: f32, x: f32, y: f32) {
+        let (c, p) = (self.point(cx, cy), self.point(x, y));
+        self.path.quad_to(c, p);
+    }
+
+    pub(crate) fn cubic_to(&mut self, c1x: f32, c1y: f32, c2x: f32, c2y: f32, x: f32, y: f32) {
+        let (c1, c2, p) = (self.point(c1x, c1y), self.point(c2x, c2y), self.point(x, y));
+        self.path.cubic_to(c1, c2, p);
+    }
+
+    pub(crate) fn close(&mut self) {
+        self.path.close();
+    }
+}
+
+impl Font {
+    /// Reads the font file at `path`.
+    pub fn open<P: AsRef<FilePath>>(path: P) -> Result<Font, FontError> {
+        let mut data = vec![];
+        try!(try!(File::open(path)).read_to_end(&mut data));
+        Font::from_bytes(data)
+    }
+
+    /// Reads a font from the contents of a font file.  Only the first
+    /// font of a collection is read.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Font, FontError> {
+        let mut start = 0;
+        if data.starts_with(b"ttcf") {
+            start = try!(read_u32(&data, 12)) as usize;
+        }
+        let version = try!(read_u32(&data, start));
+        let is_cff = match version {
+            0x00010000 | 0x74727565 => false,
+            0x4f54544f => true,
+            _ => return Err(FontError::UnknownFormat),
+        };
+
+        let mut tables = HashMap::new();
+        let count = try!(read_u16(&data, start + 4)) as usize;
+        for i in 0 .. count {
+            let record = start + 12 + 16 * i;
+            let tag = try!(read_u32(&data, record));
+            let table = Table {
+                offset: try!(read_u32(&data, record + 8)) as usize,
+                length: try!(read_u32(&data, record + 12)) as usize,
+            };
+            if table.offset + table.length > data.len() {
+                return malformed("a table runs past the end of the file");
+            }
+            tables.insert(tag, table);
+        }
+        let find = |tag: &[u8; 4]| -> Result<Table, FontError> {
+            let key = (tag[0] as u32) << 24 | (tag[1] as u32) << 16 | (tag[2] as u32) << 8 | tag[3] as u32;
+            match tables.get(&key) {
+                Some(&t) => Ok(t),
+                None => Err(FontError::Malformed(format!("missing the {} table", String::from_utf8_lossy(tag)))),
+            }
+        };
+
+        let head = try!(find(b"head"));
+        let units_per_em = try!(read_u16(&data, head.offset + 18));
+        if units_per_em == 0 {
+            return malformed("zero units per em");
+        }
+        let long_offsets = try!(read_i16(&data, head.offset + 50)) != 0;
+
+        let glyph_count = try!(read_u16(&data, try!(find(b"maxp")).offset + 4));
+
+        let hhea = try!(find(b"hhea"));
+        let ascender = try!(read_i16(&data, hhea.offset + 4));
+        let descender = try!(read_i16(&data, hhea.offset + 6));
+        let line_gap = try!(read_i16(&data, hhea.offset + 8));
+        let metric_count = try!(read_u16(&data, hhea.offset + 34)) as usize;
+
+        let hmtx = try!(find(b"hmtx"));
+        let mut advances = Vec::with_capacity(metric_count);
+        for i in 0 .. metric_count {
+            advances.push(try!(read_u16(&data, hmtx.offset + 4 * i)));
+        }
+        if advances.is_empty() {
+            return malformed("no horizontal metrics");
+        }
+
+        let cmap = try!(choose_cmap(&data, try!(find(b"cmap"))));
+        let kerning = match find(b"kern") {
+            Ok(kern) => try!(read_kerning(&data, kern)),
+            Err(_) => HashMap::new(),
+        };
+
+        let outlines = if is_cff {
+            let cff = match find(b"CFF ") {
+                Ok(cff) => cff,
+                Err(_) if find(b"CFF2").is_ok() => return Err(FontError::Unsupported("CFF2 outlines".to_string())),
+                Err(e) => return Err(e),
+            };
+            Outlines::Cff(try!(CffFont::new(&data, cff.offset, cff.length)))
+        } else {
+            let loca = try!(find(b"loca"));
+            let mut offsets = Vec::with_capacity(glyph_count as usize + 1);
+            for i in 0 .. glyph_count as usize + 1 {
+                offsets.push(if long_offsets {
+                    try!(read_u32(&data, loca.offset + 4 * i)) as usize
+                } else {
+                    try!(read_u16(&data, loca.offset + 2 * i)) as usize * 2
+                });
+            }
+            Outlines::TrueType { glyf: try!(find(b"glyf")), loca: offsets }
+        };
+
+        Ok(Font {
+            data: data,
+            units_per_em: units_per_em as f32,
+            ascender: ascender as f32,
+            descender: descender as f32,
+            line_gap: line_gap as f32,
+            glyph_count: glyph_count,
+            cmap: cmap,
+            advances: advances,
+            kerning: kerning,
+            outlines: outlines,
+        })
+    }
+
+    /// The glyph for `c`, or 0 (the missing glyph) if the font doesn't
+    /// have one.
+    pub fn glyph_index(&self, c: char) -> u16 {
+        let glyph = match self.cmap {
+            Some((4, offset)) => cmap_format_4(&self.data, offset, c as u32),
+            Some((12, offset)) => cmap_format_12(&self.data, offset, c as u32),
+            _ => Ok(0),
+        };
+        match glyph {
+            Ok(g) if g < self.glyph_count => g,
+            _ => 0,
+        }
+    }
+
+    /// How far the pen moves after drawing `glyph`, in font units.
+    pub fn advance(&self, glyph: u16) -> f32 {
+        let i = (glyph as usize).min(self.advances.len() - 1);
+        self.advances[i] as f32
+    }
+
+    /// The adjustment to the space between two glyphs, in font units.
+    pub fn kerning(&self, left: u16, right: u16) -> f32 {
+        self.kerning.get(&(left, right)).map(|&k| k as f32).unwrap_or(0.0)
+    }
+
+    pub fn units_per_em(&self) -> f32 {
+        self.units_per_em
+    }
+
+    /// The distance between baselines, in font units.
+    pub fn line_height(&self) -> f32 {
+        self.ascender - self.descender + self.line_gap
+    }
+
+    /// Adds the outline of `glyph` to `path`, with the glyph's origin at
+    /// `origin` and `scale` drawing units per font unit.
+    pub fn add_glyph(&self, glyph: u16, origin: Point, scale: f32, path: &mut Path) -> Result<(), FontError> {
+        let mut builder = OutlineBuilder::new(path, scale, origin);
+        match self.outlines {
+            Outlines::TrueType { glyf, ref loca } => {
+                let mut contours = vec![];
+                try!(self.truetype_contours(glyf, loca, glyph, &Transform::identity(), 0, &mut contours));
+                for contour in &contours {
+                    add_quadratic_contour(contour, &mut builder);
+                }
+                Ok(())
+            }
+            Outlines::Cff(ref cff) => cff.outline(&self.data, glyph, &mut builder),
+        }
+    }
+
+    /// Reads the points of a `glyf` glyph, which is either a list of
+    /// contours or built out of other glyphs.
+    fn truetype_contours(&self, glyf: Table, loca: &[usize], glyph: u16, transform: &Transform,
+                         depth: u32, out: &mut Vec<Vec<GlyphPoint>>) -> Result<(), FontError> {
+        const MORE_COMPONENTS: u16 = 0x20;
+        if depth > 8 {
+            return malformed("composite glyphs nested too deeply");
+        }
+        let i = glyph as usize;
+        if i + 1 >= loca.len() {
+            return malformed("glyph index out of range");
+        }
+        if loca[i + 1] <= loca[i] {
+            // Glyphs without outlines, like spaces.
+            return Ok(());
+        }
+        if loca[i + 1] > glyf.length {
+            return malformed("glyph runs past the end of the glyf table");
+        }
+        let data = &self.data[glyf.offset + loca[i] .. glyf.offset + loca[i + 1]];
+
+        let contour_count = try!(read_i16(data, 0));
+        if contour_count >= 0 {
+            let first = out.len();
+            try!(simple_glyph(data, contour_count as usize, out));
+            for contour in &mut out[first..] {
+                for p in contour.iter_mut() {
+                    let (x, y) = transform.apply(p.x, p.y);
+                    p.x = x;
+                    p.y = y;
+                }
+            }
+            return Ok(());
+        }
+
+        let glyph_start = out.len();
+        let mut pos = 10;
+        loop {
+            let flags = try!(read_u16(data, pos));
+            let component = try!(read_u16(data, pos + 2));
+            pos += 4;
+            let (arg1, arg2) = if flags & 0x1 != 0 {
+                pos += 4;
+                (try!(read_i16(data, pos - 4)) as i32, try!(read_i16(data, pos - 2)) as i32)
+            } else {
+                pos += 2;
+                (try!(read_u8(data, pos - 2)) as i8 as i32, try!(read_u8(data, pos - 1)) as i8 as i32)
+            };
+
+            let f2dot14 = |pos: usize| read_i16(data, pos).map(|v| v as f32 / 16384.0);
+            let mut local = Transform::identity();
+            if flags & 0x8 != 0 {
+                local.a = try!(f2dot14(pos));
+                local.d = local.a;
+                pos += 2;
+            } else if flags & 0x40 != 0 {
+                local.a = try!(f2dot14(pos));
+                local.d = try!(f2dot14(pos + 2));
+                pos += 4;
+            } else if flags & 0x80 != 0 {
+                local.a = try!(f2dot14(pos));
+                local.b = try!(f2dot14(pos + 2));
+                local.c = try!(f2dot14(pos + 4));
+                local.d = try!(f2dot14(pos + 6));
+                pos += 8;
+            }
+
+            let first = out.len();
+            let combined = transform.then_after(&local);
+            try!(self.truetype_contours(glyf, loca, component, &combined, depth + 1, out));
+
+            // The component is either moved by an offset, or so that one of
+            // its points lands on one of the points already drawn.
+            let (dx, dy) = if flags & 0x2 != 0 {
+                transform.apply(arg1 as f32, arg2 as f32)
+            } else {
+                let find = |contours: &[Vec<GlyphPoint>], n: i32| {
+                    contours.iter().flat_map(|c| c.iter()).nth(n as u16 as usize).map(|p| (p.x, p.y))
+                };
+                match (find(&out[glyph_start..first], arg1), find(&out[first..], arg2)) {
+                    (Some(parent), Some(child)) => (parent.0 - child.0, parent.1 - child.1),
+                    _ => return malformed("composite glyph anchored to a missing point"),
+                }
+            };
+            for contour in &mut out[first..] {
+                for p in contour.iter_mut() {
+                    p.x += dx;
+                    p.y += dy;
+                }
+            }
+
+            if flags & MORE_COMPONENTS == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct GlyphPoint {
+    x: f32,
+    y: f32,
+    on_curve: bool,
+}
+
+/// The scaling and rotation of a composite glyph's component.  Offsets
+/// are applied separately, since they can depend on the points.
+#[derive(Copy, Clone, Debug)]
+struct Transform {
+    a: f32, b: f32, c: f32, d: f32,
+}
+
+impl Transform {
+    fn identity() -> Transform {
+        Transform { a: 1.0, b: 0.0, c: 0.0, d: 1.0 }
+    }
+
+    fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.a * x + self.c * y, self.b * x + self.d * y)
+    }
+
+    /// Applies `inner` first, then `self`.
+    fn then_after(&self, inner: &Transform) -> Transform {
+        Transform {
+            a: self.a * inner.a + self.c * inner.b,
+            b: self.b * inner.a + self.d * inner.b,
+            c: self.a * inner.c + self.c * inner.d,
+            d: self.b * inner.c + self.d * inner.d,
+        }
+    }
+}
+
+fn simple_glyph(data: &[u8], contour_count: usize, out: &mut Vec<Vec<GlyphPoint>>) -> Result<(), FontError> {
+    let mut ends = Vec::with_capacity(contour_count);
+    for i in 0 .. contour_count {
+        ends.push(try!(read_u16(data, 10 + 2 * i)) as usize);
+    }
+    let point_count = match ends.last() {
+        Some(&end) => end + 1,
+        None => return Ok(()),
+    };
+    let instructions = try!(read_u16(data, 10 + 2 * contour_count)) as usize;
+    let mut pos = 12 + 2 * contour_count + instructions;
+
+    let mut flags = Vec::with_capacity(point_count);
+    while flags.len() < point_count {
+        let flag = try!(read_u8(data, pos));
+        pos += 1;
+        flags.push(flag);
+        if flag & 0x8 != 0 {
+            let repeat = try!(read_u8(data, pos));
+            pos += 1;
+            for _ in 0 .. repeat {
+                flags.push(flag);
+            }
+        }
+    }
+    flags.truncate(point_count);
+
+    // Coordinates are deltas, either a byte with the sign in the flags or
+    // a 16 bit value that can be left out when it's the same as before.
+    let mut read_coordinates = |short: u8, same_or_positive: u8| -> Result<Vec<f32>, FontError> {
+        let mut values = Vec::with_capacity(point_count);
+        let mut value = 0i32;
+        for &flag in &flags {
+            if flag & short != 0 {
+                let delta = try!(read_u8(data, pos)) as i32;
+                pos += 1;
+                value += if flag & same_or_positive != 0 { delta } else { -delta };
+            } else if flag & same_or_positive == 0 {
+                value += try!(read_i16(data, pos)) as i32;
+                pos += 2;
+            }
+            values.push(value as f32);
+        }
+        Ok(values)
+    };
+    let xs = try!(read_coordinates(0x2, 0x10));
+    let ys = try!(read_coordinates(0x4, 0x20));
+
+    let mut start = 0;
+    for &end in &ends {
+        if end < start || end >= point_count {
+            return malformed("contour ends out of order");
+        }
+        out.push((start .. end + 1).map(|i| GlyphPoint {
+            x: xs[i],
+            y: ys[i],
+            on_curve: flags[i] & 0x1 != 0,
+        }).collect());
+        start = end + 1;
+    }
+    Ok(())
+}
+
+/// Draws a TrueType contour, where two off curve points in a row have
+/// an implied on curve point halfway between them.
+fn add_quadratic_contour(contour: &[GlyphPoint], builder: &mut OutlineBuilder) {
+    let n = contour.len();
+    if n == 0 {
+        return;
+    }
+    let midpoint = |a: &GlyphPoint, b: &GlyphPoint| GlyphPoint {
+        x: (a.x + b.x) / 2.0,
+        y: (a.y + b.y) / 2.0,
+        on_curve: true,
+    };
+
+    // Start on a point that's on the curve, making one up if there isn't one.
+    let (start, first) = match contour.iter().position(|p| p.on_curve) {
+        Some(i) => (contour[i], i),
+        None => (midpoint(&contour[n - 1], &contour[0]), n - 1),
+    };
+    builder.move_to(start.x, start.y);
+
+    let mut control: Option<GlyphPoint> = None;
+    for k in 1 .. n + 1 {
+        let p = contour[(first + k) % n];
+        match (p.on_curve, control) {
+            (true, None) => builder.line_to(p.x, p.y),
+            (true, Some(c)) => {
+                builder.quad_to(c.x, c.y, p.x, p.y);
+                control = None;
+            }
+            (false, None) => control = Some(p),
+            (false, Some(c)) => {
+                let m = midpoint(&c, &p);
+                builder.quad_to(c.x, c.y, m.x, m.y);
+                control = Some(p);
+            }
+        }
+    }
+    if let Some(c) = control {
+        builder.quad_to(c.x, c.y, start.x, start.y);
+    }
+    builder.close();
+}
+
+/// Picks the most complete Unicode character map that's implemented.
+fn choose_cmap(data: &[u8], cmap: Table) -> Result<Option<(u16, usize)>, FontError> {
+    let count = try!(read_u16(data, cmap.offset + 2)) as usize;
+    let mut best: Option<(u32, u16, usize)> = None;
+    for i in 0 .. count {
+        let record = cmap.offset + 4 + 8 * i;
+        let platform = try!(read_u16(data, record));
+        let encoding = try!(read_u16(data, record + 2));
+        let offset = cmap.offset + try!(read_u32(data, record + 4)) as usize;
+        let format = try!(read_u16(data, offset));
+
+        let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+        let rank = match format {
+            12 if unicode => 2,
+            4 if unicode => 1,
+            _ => continue,
+        };
+        if best.map(|b| rank > b.0).unwrap_or(true) {
+            best = Some((rank, format, offset));
+        }
+    }
+    Ok(best.map(|(_, format, offset)| (format, offset)))
+}
+
+fn cmap_format_4(data: &[u8], offset: usize, c: u32) -> Result<u16, FontError> {
+    if c > 0xffff {
+        return Ok(0);
+    }
+    let segments = try!(read_u16(data, offset + 6)) as usize / 2;
+    let ends = offset + 14;
+    let starts = ends + 2 * segments + 2;
+    let deltas = starts + 2 * segments;
+    let range_offsets = deltas + 2 * segments;
+
+    for i in 0 .. segments {
+        if (try!(read_u16(data, ends + 2 * i)) as u32) < c {
+            continue;
+        }
+        let start = try!(read_u16(data, starts + 2 * i)) as u32;
+        if start > c {
+            return Ok(0);
+        }
+        let delta = try!(read_u16(data, deltas + 2 * i));
+        let range_offset = try!(read_u16(data, range_offsets + 2 * i)) as usize;
+        if range_offset == 0 {
+            return Ok((c as u16).wrapping_add(delta));
+        }
+        let at = range_offsets + 2 * i + range_offset + 2 * (c - start) as usize;
+        let glyph = try!(read_u16(data, at));
+        return Ok(if glyph == 0 { 0 } else { glyph.wrapping_add(delta) });
+    }
+    Ok(0)
+}
+
+fn cmap_format_12(data: &[u8], offset: usize, c: u32) -> Result<u16, FontError> {
+    let groups = try!(read_u32(data, offset + 12)) as usize;
+    let (mut low, mut high) = (0, groups);
+    while low < high {
+        let middle = (low + high) / 2;
+        let group = offset + 16 + 12 * middle;
+        let start = try!(read_u32(data, group));
+        let end = try!(read_u32(data, group + 4));
+        if c < start {
+            high = middle;
+        } else if c > end {
+            low = middle + 1;
+        } else {
+            return Ok((try!(read_u32(data, group + 8)) + (c - start)) as u16);
+        }
+    }
+    Ok(0)
+}
+
+/// Reads the horizontal format 0 subtables of a Microsoft style `kern`
+/// table.  Apple's newer layout isn't read.
+fn read_kerning(data: &[u8], kern: Table) -> Result<HashMap<(u16, u16), i16>, FontError> {
+    let mut pairs = HashMap::new();
+    if try!(read_u16(data, kern.offset)) != 0 {
+        return Ok(pairs);
+    }
+    let count = try!(read_u16(data, kern.offset + 2));
+    let mut pos = kern.offset + 4;
+    for _ in 0 .. count {
+        let length = try!(read_u16(data, pos + 2)) as usize;
+        let coverage = try!(read_u16(data, pos + 4));
+        let horizontal = coverage & 0x1 != 0;
+        let special = coverage & 0x6 != 0;
+        if coverage >> 8 == 0 && horizontal && !special {
+            let n = try!(read_u16(data, pos + 6)) as usize;
+            for i in 0 .. n {
+                let pair = pos + 14 + 6 * i;
+                let key = (try!(read_u16(data, pair)), try!(read_u16(data, pair + 2)));
+                let value = try!(read_i16(data, pair + 4));
+                *pairs.entry(key).or_insert(0) += value;
+            }
+        }
+        if length == 0 {
+            break;
+        }
+        pos += length;
+    }
+    Ok(pairs)
+}
+
+/// A string drawn in a font, filled with the nonzero rule.
+///
+/// The first line's baseline starts at the origin and goes along +x,
+/// with the letters above it (toward -y).  Each newline starts a new
+/// line one line height further down.
+#[derive(Clone, Debug)]
+pub struct Text {
+    path: Path,
+    width: f32,
+}
+
+impl Text {
+    /// Lays out `text` with an em size of `size` drawing units.
+    pub fn new(font: &Font, text: &str, size: f32) -> Result<Text, FontError> {
+        let scale = size / font.units_per_em();
+        let mut path = Path::new();
+        let (mut x, mut y) = (0.0, 0.0);
+        let mut width: f32 = 0.0;
+        let mut previous = None;
+
+        for c in text.chars() {
+            if c == '\n' {
+                x = 0.0;
+                y += font.line_height() * scale;
+                previous = None;
+                continue;
+            }
+            let glyph = font.glyph_index(c);
+            if let Some(previous) = previous {
+                x += font.kerning(previous, glyph) * scale;
+            }
+            try!(font.add_glyph(glyph, Point { x: x, y: y }, scale, &mut path));
+            x += font.advance(glyph) * scale;
+            width = width.max(x);
+            previous = Some(glyph);
+        }
+
+        Ok(Text { path: path, width: width })
+    }
+
+    /// How far the pen moved along the longest line.
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+}
+
+impl Implicit for Text {
+    fn sample(&self, pos: Point) -> f32 {
+        self.path.sample(pos)
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        self.path.bounding_box()
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+#[test]
+fn glyphs_are_laid_out_with_kerning() {
+    fn words(values: &[i32]) -> Vec<u8> {
+        values.iter().flat_map(|&v| vec![(v >> 8) as u8, v as u8]).collect()
+    }
+
+    // Glyph 1 is a 500 unit square, and glyph 2 is a rounded diamond
+    // drawn with only off curve points.
+    let square = words(&[1, 0, 0, 500, 500, 3, 0, 0x0101, 0x0101, 0, 0, 500, 0, 0, 500, 0, -500]);
+    let diamond = words(&[1, 0, 0, 500, 500, 3, 0, 0, 0, 250, 250, -250, -250, 0, 250, 250, -250]);
+    let glyphs = [&square[..], &diamond[..]].concat();
+
+    let mut head = vec![0; 54];
+    head[18..20].copy_from_slice(&words(&[1000]));
+    head[50..52].copy_from_slice(&words(&[1]));
+    let mut hhea = vec![0; 36];
+    hhea[4..10].copy_from_slice(&words(&[800, -200, 0]));
+    hhea[34..36].copy_from_slice(&words(&[3]));
+    let maxp = words(&[0, 0x5000, 3]);
+    let hmtx = words(&[600, 0, 600, 0, 600, 0]);
+    let loca = words(&[0, 0, 0, 0, 0, square.len() as i32, 0, glyphs.len() as i32]);
+    // 'A' and 'B' map to glyphs 1 and 2.
+    let cmap = words(&[0, 1, 3, 1, 0, 12,
+                       4, 32, 0, 4, 0, 0, 0, 0x42, 0xffff, 0, 0x41, 0xffff, 1 - 0x41, 1, 0, 0]);
+    let kern = words(&[0, 1, 0, 20, 1, 1, 0, 0, 0, 1, 2, -100]);
+
+    let tables: Vec<(&[u8], &[u8])> = vec![
+        (b"cmap", &cmap), (b"glyf", &glyphs), (b"head", &head), (b"hhea", &hhea),
+        (b"hmtx", &hmtx), (b"kern", &kern), (b"loca", &loca), (b"maxp", &maxp),
+    ];
+    let mut font = words(&[1, 0, tables.len() as i32, 0, 0, 0]);
+    let mut offset = 12 + 16 * tables.len();
+    for &(tag, table) in &tables {
+        font.extend_from_slice(tag);
+        font.extend(words(&[0, 0, (offset >> 16) as i32, offset as i32, 0, table.len() as i32]));
+        offset += table.len();
+    }
+    for &(_, table) in &tables {
+        font.extend_from_slice(table);
+    }
+
+    let font = Font::from_bytes(font).unwrap();
+    assert_eq!((font.glyph_index('A'), font.glyph_index('B'), font.glyph_index('C')), (1, 2, 0));
+
+    // At a tenth of the size, the kerning pulls the diamond up against
+    // the square, and the second line is 100 units further down.
+    let text = Text::new(&font, "AB\nA", 100.0).unwrap();
+    assert_eq!(text.width(), 110.0);
+    let close = |p: Point, d: f32| (text.sample(p) - d).abs() < 0.001;
+    assert!(close(Point { x: 25.0, y: -25.0 }, -25.0));
+    assert!(close(Point { x: 25.0, y: 75.0 }, -25.0));
+    assert!(close(Point { x: 25.0, y: 25.0 }, 25.0));
+    assert!(text.sample(Point { x: 75.0, y: -25.0 }) < -10.0);
+    assert!(close(Point { x: 75.0, y: -60.0 }, 60.0 - 43.75));
+    let bb = text.bounding_box().unwrap();
+    assert!(bb.close_to(&Rect::from_points(&Point { x: 0.0, y: -50.0 }, &Point { x: 93.75, y: 100.0 }), 0.001));
+}
@@ -0,0 +1,197 @@
+use std::io::{self, Write, BufWriter};
+use std::fs::File;
+
+use ::OutputDevice;
+use ::util::geom::Point;
+use super::units::Unit;
+
+/// Machine settings for a `GcodeWriter`.
+#[derive(Clone, Debug)]
+pub struct GcodeSettings {
+    /// The units that coordinates and feed rates are written in.
+    ///
+    /// Inches are written with G20; everything else is converted to
+    /// millimeters and written with G21.
+    pub units: Unit,
+    /// Cutting speed per minute, in the units that are written out.
+    pub feed_rate: f32,
+    /// The `S` value given to the laser (or spindle) while cutting.
+    pub power: f32,
+    /// Lines written at the very start of the program.
+    pub preamble: Vec<String>,
+    /// Lines written at the very end of the program.
+    pub postamble: Vec<String>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum PathState {
+    /// Not inside of a line.
+    Idle,
+    /// Moved to the first point of a line with the laser off.
+    Travelled,
+    /// The laser is on.
+    Cutting,
+}
+
+/// Turns lines into a GRBL-style toolpath.
+///
+/// Every line starts with a G0 travel move to its first point, then the
+/// laser is switched on and the rest of the line is cut with G1 moves.
+/// The laser is switched off again at the end of each line, so the gaps
+/// in a dashed outline are travelled over without cutting.
+pub struct GcodeWriter {
+    settings: GcodeSettings,
+    size: (f32, f32),
+    conversion: f32,
+    state: PathState,
+    commands: String,
+}
+
+impl GcodeSettings {
+    pub fn new(units: Unit) -> GcodeSettings {
+        GcodeSettings {
+            units: units,
+            feed_rate: 1000.0,
+            power: 1000.0,
+            preamble: vec![],
+            postamble: vec!["G0 X0 Y0".to_string(), "M2".to_string()],
+        }
+    }
+}
+
+impl GcodeWriter {
+    /// Creates a writer whose coordinates are multiplied by
+    /// `conversion_factor` to get `settings.units`.
+    pub fn new(settings: GcodeSettings, conversion_factor: f32) -> GcodeWriter {
+        // Only inches and millimeters can be expressed in g-code.
+        let conversion = match settings.units {
+            Unit::Inches | Unit::Millimeters => conversion_factor,
+            other => conversion_factor * Unit::Millimeters.per_inch() / other.per_inch(),
+        };
+
+        GcodeWriter {
+            settings: settings,
+            size: (800.0, 800.0),
+            conversion: conversion,
+            state: PathState::Idle,
+            commands: String::new(),
+        }
+    }
+
+    /// Writes the document to a file at `path`.
+    pub fn write_out(&self, path: &str) -> io::Result<()> {
+        let file = try!(File::create(path));
+        let mut out = BufWriter::new(file);
+        try!(self.write_to(&mut out));
+        out.flush()
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for line in &self.settings.preamble {
+            try!(write!(out, "{}\n", line));
+        }
+
+        let units = if self.settings.units == Unit::Inches { "G20" } else { "G21" };
+        try!(write!(out, "{}\nG90\nM5\n", units));
+        try!(out.write_all(self.commands.as_bytes()));
+
+        // Make sure the laser is off even if a line was left unfinished.
+        if self.state == PathState::Cutting {
+            try!(write!(out, "M5\n"));
+        }
+
+        for line in &self.settings.postamble {
+            try!(write!(out, "{}\n", line));
+        }
+        Ok(())
+    }
+
+    // G-code has y pointing up.
+    fn transform_point(&self, x: f32, y: f32) -> (f32, f32) {
+        (x * self.conversion, self.size.1 - y * self.conversion)
+    }
+}
+
+impl OutputDevice for GcodeWriter {
+    fn start_line(&mut self) {
+        self.state = PathState::Idle;
+    }
+
+    fn add_point(&mut self, Point{x, y}: Point) {
+        let (x, y) = self.transform_point(x, y);
+        let cmd = match self.state {
+            PathState::Idle => {
+                self.state = PathState::Travelled;
+                format!("G0 X{:.4} Y{:.4}\n", x, y)
+            }
+            PathState::Travelled => {
+                self.state = PathState::Cutting;
+                format!("M4 S{}\nG1 X{:.4} Y{:.4} F{}\n", self.settings.power, x, y, self.settings.feed_rate)
+            }
+            PathState::Cutting => format!("G1 X{:.4} Y{:.4}\n", x, y),
+        };
+        self.commands.push_str(&cmd);
+    }
+
+    fn end_line(&mut self) {
+        if self.state == PathState::Cutting {
+            self.commands.push_str("M5\n");
+        }
+        self.state = PathState::Idle;
+    }
+
+    fn set_size(&mut self, w: f32, h: f32) {
+        self.size = (w * self.conversion, h * self.conversion);
+    }
+}
+
+#[test]
+fn lines_are_travelled_to_then_cut() {
+    let mut settings = GcodeSettings::new(Unit::Inches);
+    settings.feed_rate = 600.0;
+    settings.power = 255.0;
+    settings.preamble = vec!["; begin".to_string()];
+    settings.postamble = vec!["; end".to_string()];
+    let mut gcode = GcodeWriter::new(settings, 0.5);
+    gcode.set_size(10.0, 10.0);
+
+    // Two dashes of the same outline.
+    gcode.start_line();
+    gcode.add_point(Point { x: 0.0, y: 0.0 });
+    gcode.add_point(Point { x: 4.0, y: 0.0 });
+    gcode.add_point(Point { x: 4.0, y: 2.0 });
+    gcode.end_line();
+    gcode.start_line();
+    gcode.add_point(Point { x: 4.0, y: 6.0 });
+    gcode.add_point(Point { x: 0.0, y: 6.0 });
+    gcode.end_line();
+
+    let mut buffer = vec![];
+    gcode.write_to(&mut buffer).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines, vec![
+        "; begin",
+        "G20", "G90", "M5",
+        "G0 X0.0000 Y5.0000",
+        "M4 S255", "G1 X2.0000 Y5.0000 F600",
+        "G1 X2.0000 Y4.0000",
+        "M5",
+        "G0 X2.0000 Y2.0000",
+        "M4 S255", "G1 X0.0000 Y2.0000 F600",
+        "M5",
+        "; end",
+    ]);
+
+    // Units that g-code can't express are converted to millimeters.
+    let mut gcode = GcodeWriter::new(GcodeSettings::new(Unit::Centimeters), 1.0);
+    gcode.set_size(1.0, 1.0);
+    gcode.start_line();
+    gcode.add_point(Point { x: 0.5, y: 1.0 });
+    gcode.end_line();
+    let mut buffer = vec![];
+    gcode.write_to(&mut buffer).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines, vec!["G21", "G90", "M5", "G0 X5.0000 Y0.0000", "G0 X0 Y0", "M2"]);
+}
@@ -0,0 +1,271 @@
+//! Single stroke lettering for engraving and plotting.
+//!
+//! The letters come from the public domain Hershey Roman Simplex font,
+//! which draws every character with a few open polylines instead of
+//! filled outlines.  A laser or plotter can trace them in one pass.
+
+use ::{OutputDevice, PathInfo, Strokes, OrThese, Capsule};
+use ::util::geom::{Point, Rect};
+
+/// How many font units make up an em.
+const UNITS_PER_EM: f32 = 32.0;
+/// Where the baseline is, in font units.
+const BASELINE: f32 = 9.0;
+
+/// Printable ASCII (from the space up to `~`) in the Hershey encoding.
+///
+/// Each glyph starts with its left and right side bearings, followed by
+/// pairs of coordinates.  Every coordinate is stored as a character
+/// offset from `R`, and `" R"` lifts the pen.
+static SIMPLEX: [&'static str; 95] = [
+    r"JZ",
+    r"MWRFRT RRYQZR[SZRY",
+    r"JZNFNM RVFVM",
+    r"H]SBLb RYBRb RLOZO RKUYU",
+    r"H\PBP_ RTBT_ RYIWGTFPFMGKIKKLMMNOOUQWRXSYUYXWZT[P[MZKX",
+    r"F^[FI[ RNFPHPJOLMMKMIKIIJGLFNFPGSHVHYG[F RWTUUTWTYV[X[ZZ[X[VYTWT",
+    r"E_\O\N[MZMYNXPVUTXRZP[L[JZIYHWHUISJRQNRMSKSIRGPFNGMIMKNNPQUXWZY[[[\Z\Y",
+    r"MWRHQGRFSGSIRKQL",
+    r"KYVBTDRGPKOPOTPYR]T`Vb",
+    r"KYNBPDRGTKUPUTTYR]P`Nb",
+    r"JZRLRX RMOWU RWOMU",
+    r"E_RIR[ RIR[R",
+    r"NVSWRXQWRVSWSYQ[",
+    r"E_IR[R",
+    r"NVRVQWRXSWRV",
+    r"G][BIb",
+    r"H\QFNGLJKOKRLWNZQ[S[VZXWYRYOXJVGSFQF",
+    r"H\NJPISFS[",
+    r"H\LKLJMHNGPFTFVGWHXJXLWNUQK[Y[",
+    r"H\MFXFRNUNWOXPYSYUXXVZS[P[MZLYKW",
+    r"H\UFK[Z[ RUFU[",
+    r"H\WFMFLOMNPMSMVNXPYSYUXXVZS[P[MZLYKW",
+    r"H\XIWGTFRFOGMJLOLTMXOZR[S[VZXXYUYTXQVOSNRNOOMQLT",
+    r"H\YFO[ RKFYF",
+    r"H\PFMGLILKMMONSOVPXRYTYWXYWZT[P[MZLYKWKTLRNPQOUNWMXKXIWGTFPF",
+    r"H\XMWPURRSQSNRLPKMKLLINGQFRFUGWIXMXRWWUZR[P[MZLX",
+    r"NVROQPRQSPRO RRVQWRXSWRV",
+    r"NVROQPRQSPRO RSWRXQWRVSWSYQ[",
+    r"F^ZIJRZ[",
+    r"E_IO[O RIU[U",
+    r"F^JIZRJ[",
+    r"I[LKLJMHNGPFTFVGWHXJXLWNVORQRT RRYQZR[SZRY",
+    r"E`WNVLTKQKOLNMMPMSNUPVSVUUVS RQKOMNPNSOUPV RWKVSVUXVZV\T]Q]O\L[JYHWGTFQFNGLHJJILHOHRIUJWLYNZQ[T[WZYYZX RXKWSWUXV",
+    r"I[RFJ[ RRFZ[ RMTWT",
+    r"G\KFK[ RKFTFWGXHYJYLXNWOTP RKPTPWQXRYTYWXYWZT[K[",
+    r"H]ZKYIWGUFQFOGMILKKNKSLVMXOZQ[U[WZYXZV",
+    r"G\KFK[ RKFRFUGWIXKYNYSXVWXUZR[K[",
+    r"H[LFL[ RLFYF RLPTP RL[Y[",
+    r"HZLFL[ RLFYF RLPTP",
+    r"H]ZKYIWGUFQFOGMILKKNKSLVMXOZQ[U[WZYXZVZS RUSZS",
+    r"G]KFK[ RYFY[ RKPYP",
+    r"NVRFR[",
+    r"JZVFVVUYTZR[P[NZMYLVLT",
+    r"G\KFK[ RYFKT RPOY[",
+    r"HYLFL[ RL[X[",
+    r"F^JFJ[ RJFR[ RZFR[ RZFZ[",
+    r"G]KFK[ RKFY[ RYFY[",
+    r"G]PFNGLIKKJNJSKVLXNZP[T[VZXXYVZSZNYKXIVGTFPF",
+    r"G\KFK[ RKFTFWGXHYJYMXOWPTQKQ",
+    r"G]PFNGLIKKJNJSKVLXNZP[T[VZXXYVZSZNYKXIVGTFPF RSWY]",
+    r"G\KFK[ RKFTFWGXHYJYLXNWOTPKP RRPY[",
+    r"H\YIWGTFPFMGKIKKLMMNOOUQWRXSYUYXWZT[P[MZKX",
+    r"JZRFR[ RKFYF",
+    r"G]KFKULXNZQ[S[VZXXYUYF",
+    r"I[JFR[ RZFR[",
+    r"F^HFM[ RRFM[ RRFW[ R\FW[",
+    r"H\KFY[ RYFK[",
+    r"I[JFRPR[ RZFRP",
+    r"H\YFK[ RKFYF RK[Y[",
+    r"KYOBOb RPBPb ROBVB RObVb",
+    r"KYKFY^",
+    r"KYTBTb RUBUb RNBUB RNbUb",
+    r"JZRDJR RRDZR",
+    r"I[Ib[b",
+    r"NVSKQMQORPSORNQO",
+    r"I\XMX[ RXPVNTMQMONMPLSLUMXOZQ[T[VZXX",
+    r"H[LFL[ RLPNNPMSMUNWPXSXUWXUZS[P[NZLX",
+    r"I[XPVNTMQMONMPLSLUMXOZQ[T[VZXX",
+    r"I\XFX[ RXPVNTMQMONMPLSLUMXOZQ[T[VZXX",
+    r"I[LSXSXQWOVNTMQMONMPLSLUMXOZQ[T[VZXX",
+    r"MYWFUFSGRJR[ ROMVM",
+    r"I\XMX]W`VaTbQbOa RXPVNTMQMONMPLSLUMXOZQ[T[VZXX",
+    r"I\MFM[ RMQPNRMUMWNXQX[",
+    r"NVQFRGSFREQF RRMR[",
+    r"MWRFSGTFSERF RSMS^RaPbNb",
+    r"IZMFM[ RWMMW RQSX[",
+    r"NVRFR[",
+    r"CaGMG[ RGQJNLMOMQNRQR[ RRQUNWMZM\N]Q][",
+    r"I\MMM[ RMQPNRMUMWNXQX[",
+    r"I\QMONMPLSLUMXOZQ[T[VZXXYUYSXPVNTMQM",
+    r"H[LMLb RLPNNPMSMUNWPXSXUWXUZS[P[NZLX",
+    r"I\XMXb RXPVNTMQMONMPLSLUMXOZQ[T[VZXX",
+    r"KXOMO[ ROSPPRNTMWM",
+    r"J[XPWNTMQMNNMPNRPSUTWUXWXXWZT[Q[NZMX",
+    r"MYRFRWSZU[W[ ROMVM",
+    r"I\MMMWNZP[S[UZXW RXMX[",
+    r"JZLMR[ RXMR[",
+    r"G]JMN[ RRMN[ RRMV[ RZMV[",
+    r"J[MMX[ RXMM[",
+    r"JZLMR[ RXMR[P_NaLbKb",
+    r"J[XMM[ RMMXM RM[X[",
+    r"KYTBRCQDPFPHQJRKSMSOQQ RRCQEQGRISJTLTNSPORSTTVTXSZR[Q]Q_Ra RQSSUSWRYQZP\P^Q`RaTb",
+    r"NVRBRb",
+    r"KYPBRCSDTFTHSJRKQMQOSQ RRCSESGRIQJPLPNQPURQTPVPXQZR[S]S_Ra RSSQUQWRYSZT\T^S`RaPb",
+    r"F^IUISJPLONOPPTSVTXTZS[Q RISJQLPNPPQTTVUXUZT[Q[O",
+];
+
+/// A string drawn with single stroke letters.
+///
+/// The first line's baseline starts at the origin and goes along +x,
+/// with the letters above it (toward -y).  Each newline starts a new
+/// line one em further down.  Characters outside of printable ASCII are
+/// drawn as `?`.
+#[derive(Clone, Debug)]
+pub struct StrokeText {
+    lines: Vec<Vec<Point>>,
+    width: f32,
+}
+
+fn coordinate(byte: u8) -> f32 {
+    byte as f32 - b'R' as f32
+}
+
+fn glyph(c: char) -> &'static [u8] {
+    let code = c as usize;
+    if code >= 32 && code < 127 {
+        SIMPLEX[code - 32].as_bytes()
+    } else {
+        SIMPLEX['?' as usize - 32].as_bytes()
+    }
+}
+
+impl StrokeText {
+    /// Lays out `text` with an em size of `size` drawing units.  Capital
+    /// letters are about two thirds of an em tall.
+    pub fn new(text: &str, size: f32) -> StrokeText {
+        let scale = size / UNITS_PER_EM;
+        let mut lines = vec![];
+        let (mut x, mut y) = (0.0, 0.0);
+        let mut width: f32 = 0.0;
+
+        for c in text.chars() {
+            if c == '\n' {
+                x = 0.0;
+                y += size;
+                continue;
+            }
+            let glyph = glyph(c);
+            let left = coordinate(glyph[0]);
+            let mut line = vec![];
+            for pair in glyph[2..].chunks(2) {
+                if pair[0] == b' ' {
+                    lines.push(line);
+                    line = vec![];
+                } else {
+                    line.push(Point {
+                        x: x + (coordinate(pair[0]) - left) * scale,
+                        y: y + (coordinate(pair[1]) - BASELINE) * scale,
+                    });
+                }
+            }
+            if !line.is_empty() {
+                lines.push(line);
+            }
+            x += (coordinate(glyph[1]) - left) * scale;
+            width = width.max(x);
+        }
+
+        StrokeText { lines: lines, width: width }
+    }
+
+    /// How far the pen moved along the longest line.
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Every stroke, in the order it's drawn.
+    pub fn lines(&self) -> &[Vec<Point>] {
+        &self.lines
+    }
+
+    /// The smallest rectangle around every stroke, if there are any.
+    pub fn bounding_box(&self) -> Option<Rect> {
+        self.strokes().bounding_box()
+    }
+
+    /// Draws each stroke as an open path, without moving it.
+    pub fn draw<O: OutputDevice>(&self, out: &mut O, info: &PathInfo) {
+        for line in &self.lines {
+            out.start_path(info);
+            for &p in line {
+                out.add_point(p);
+            }
+            out.end_path(info);
+        }
+    }
+
+    /// The strokes as a figure that can be added to a `Scene`.
+    pub fn strokes(&self) -> Strokes {
+        Strokes::new(self.lines.clone())
+    }
+
+    /// A shape that covers every stroke with a line `thickness` wide and
+    /// round ends, for cutting the letters out instead of scoring them.
+    pub fn thicken(&self, thickness: f32) -> OrThese<Capsule> {
+        let mut capsules = vec![];
+        for line in &self.lines {
+            for pair in line.windows(2) {
+                capsules.push(Capsule {
+                    start: pair[0],
+                    end: pair[1],
+                    radius: thickness / 2.0,
+                });
+            }
+        }
+        OrThese::new(capsules)
+    }
+}
+
+#[test]
+fn strokes_are_laid_out_and_drawn_open() {
+    use ::{Implicit, Scene, Operation};
+
+    struct Recorder(Vec<(PathInfo, Vec<Point>)>);
+    impl OutputDevice for Recorder {
+        fn start_line(&mut self) { }
+        fn add_point(&mut self, p: Point) {
+            self.0.last_mut().unwrap().1.push(p);
+        }
+        fn end_line(&mut self) { }
+        fn start_path(&mut self, info: &PathInfo) {
+            self.0.push((*info, vec![]));
+        }
+    }
+
+    // At 32 units to the em, one font unit is one drawing unit.
+    let text = StrokeText::new("Hi\nA", 32.0);
+    assert_eq!(text.width(), 30.0);
+    assert_eq!(text.lines().len(), 8);
+    assert_eq!(text.lines()[0], vec![Point { x: 4.0, y: -21.0 }, Point { x: 4.0, y: 0.0 }]);
+    assert_eq!(text.lines()[5][0], Point { x: 9.0, y: 11.0 });
+
+    let info = PathInfo::new(Operation::Score, false, 0);
+    let mut direct = Recorder(vec![]);
+    text.draw(&mut direct, &info);
+    assert_eq!(direct.0.len(), 8);
+    assert_eq!(direct.0[0].1, text.lines()[0]);
+
+    let mut scene = Scene::new();
+    scene.add(text.strokes());
+    let mut rendered = Recorder(vec![]);
+    scene.render_all(&mut rendered);
+    assert_eq!(rendered.0.len(), 8);
+    assert!(rendered.0.iter().all(|&(info, ref points)| {
+        !info.closed && info.operation == Operation::Score && points.len() >= 2
+    }));
+
+    let cut = text.thicken(2.0);
+    assert!((cut.sample(Point { x: 4.0, y: -10.0 }) + 1.0).abs() < 1e-5);
+    assert!((cut.sample(Point { x: 6.0, y: -16.0 }) - 1.0).abs() < 1e-5);
+}
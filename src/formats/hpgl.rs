@@ -0,0 +1,129 @@
+use std::io::{self, Write, BufWriter};
+use std::fs::File;
+
+use ::OutputDevice;
+use ::util::geom::Point;
+use super::units::Unit;
+
+/// HPGL plotter units are 0.025mm.
+const PLOTTER_UNITS_PER_INCH: f32 = 1016.0;
+
+/// Writes lines as HPGL pen-up / pen-down moves for plotters and
+/// vinyl cutters.
+pub struct HpglWriter {
+    size: (f32, f32),
+    conversion: f32,
+    // Points of the current line, already in plotter units.
+    current: Vec<(i32, i32)>,
+    commands: String,
+}
+
+impl HpglWriter {
+    /// Creates a writer whose coordinates are multiplied by
+    /// `conversion_factor` to get `units` ("in", "mm", ...).  If the
+    /// units aren't recognized, the converted coordinates are taken to
+    /// already be in plotter units.
+    pub fn new(units: &str, conversion_factor: f32) -> HpglWriter {
+        let conversion = match Unit::from_name(units) {
+            Some(unit) => conversion_factor * PLOTTER_UNITS_PER_INCH / unit.per_inch(),
+            None => conversion_factor,
+        };
+
+        HpglWriter {
+            size: (800.0, 800.0),
+            conversion: conversion,
+            current: vec![],
+            commands: String::new(),
+        }
+    }
+
+    /// Writes the document to a file at `path`.
+    pub fn write_out(&self, path: &str) -> io::Result<()> {
+        let file = try!(File::create(path));
+        let mut out = BufWriter::new(file);
+        try!(self.write_to(&mut out));
+        out.flush()
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        try!(write!(out, "IN;SP1;\n"));
+        try!(out.write_all(self.commands.as_bytes()));
+        write!(out, "PU;SP0;\n")
+    }
+
+    // Plotters have y pointing up.
+    fn transform_point(&self, x: f32, y: f32) -> (i32, i32) {
+        let x = x * self.conversion;
+        let y = self.size.1 - y * self.conversion;
+        (x.round() as i32, y.round() as i32)
+    }
+}
+
+impl OutputDevice for HpglWriter {
+    fn start_line(&mut self) {
+        self.current.clear();
+    }
+
+    fn add_point(&mut self, Point{x, y}: Point) {
+        let p = self.transform_point(x, y);
+        // Points closer than a plotter unit would only make the pen stutter.
+        if self.current.last() != Some(&p) {
+            self.current.push(p);
+        }
+    }
+
+    fn end_line(&mut self) {
+        if self.current.len() < 2 {
+            self.current.clear();
+            return;
+        }
+
+        let (x, y) = self.current[0];
+        self.commands.push_str(&format!("PU{},{};PD", x, y));
+        for (i, &(x, y)) in self.current[1..].iter().enumerate() {
+            if i != 0 {
+                self.commands.push_str(",");
+            }
+            self.commands.push_str(&format!("{},{}", x, y));
+        }
+        self.commands.push_str(";\n");
+        self.current.clear();
+    }
+
+    fn set_size(&mut self, w: f32, h: f32) {
+        self.size = (w * self.conversion, h * self.conversion);
+    }
+}
+
+#[test]
+fn lines_are_plotted_in_plotter_units() {
+    let mut hpgl = HpglWriter::new("in", 0.5);
+    hpgl.set_size(4.0, 4.0);
+
+    hpgl.start_line();
+    hpgl.add_point(Point { x: 0.0, y: 0.0 });
+    hpgl.add_point(Point { x: 2.0, y: 0.0 });
+    hpgl.add_point(Point { x: 2.0, y: 1.0 });
+    hpgl.end_line();
+
+    hpgl.start_line();
+    hpgl.add_point(Point { x: 1.0, y: 3.0 });
+    hpgl.add_point(Point { x: 4.0, y: 4.0 });
+    hpgl.end_line();
+
+    // A line with a single point can't be drawn.
+    hpgl.start_line();
+    hpgl.add_point(Point { x: 3.0, y: 3.0 });
+    hpgl.end_line();
+
+    let mut buffer = vec![];
+    hpgl.write_to(&mut buffer).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines, vec![
+        "IN;SP1;",
+        "PU0,2032;PD1016,2032,1016,1524;",
+        "PU508,508;PD2032,0;",
+        "PU;SP0;",
+    ]);
+}
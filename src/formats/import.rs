@@ -0,0 +1,129 @@
+use ::{Implicit, PolyGroup, Circle, Rectangle};
+use ::util::geom::{Point, Vector, Rect, Matrix};
+
+/// A shape that was read out of a drawing file.
+///
+/// Simple primitives are kept in their exact form so that their distance
+/// fields stay accurate; everything else is flattened into polygons.
+#[derive(Clone, Debug)]
+pub enum ImportedShape {
+    Polygons(PolyGroup),
+    Circle(Circle),
+    Rectangle(Rectangle),
+}
+
+impl Implicit for ImportedShape {
+    fn sample(&self, pos: Point) -> f32 {
+        match *self {
+            ImportedShape::Polygons(ref p) => p.sample(pos),
+            ImportedShape::Circle(ref c) => c.sample(pos),
+            ImportedShape::Rectangle(ref r) => r.sample(pos),
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        match *self {
+            ImportedShape::Polygons(ref p) => p.bounding_box(),
+            ImportedShape::Circle(ref c) => c.bounding_box(),
+            ImportedShape::Rectangle(ref r) => r.bounding_box(),
+        }
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+/// The number of straight segments needed to keep an arc of `radius`
+/// spanning `sweep` radians within `tolerance` of the real curve.
+pub(crate) fn arc_segments(radius: f32, sweep: f32, tolerance: f32) -> usize {
+    let sweep = sweep.abs();
+    if radius <= tolerance {
+        return ((sweep / ::std::f32::consts::FRAC_PI_2).ceil() as usize).max(1);
+    }
+    let step = 2.0 * (1.0 - tolerance / radius).acos();
+    ((sweep / step).ceil() as usize).max(1).min(4096)
+}
+
+/// Points along a circular arc, not including the starting point.
+pub(crate) fn arc_points(center: Point, radius: f32, start: f32, sweep: f32, tolerance: f32) -> Vec<Point> {
+    let n = arc_segments(radius, sweep, tolerance);
+    (1 .. n + 1).map(|i| {
+        let t = start + sweep * (i as f32 / n as f32);
+        Point {
+            x: center.x + radius * t.cos(),
+            y: center.y + radius * t.sin(),
+        }
+    }).collect()
+}
+
+fn second_difference(a: Point, b: Point, c: Point) -> f32 {
+    Vector { x: a.x - 2.0 * b.x + c.x, y: a.y - 2.0 * b.y + c.y }.magnitude()
+}
+
+/// Points along a quadratic bezier, not including the starting point.
+pub(crate) fn flatten_quad(p0: Point, p1: Point, p2: Point, tolerance: f32) -> Vec<Point> {
+    // Wang's formula for the number of segments.
+    let dd = second_difference(p0, p1, p2);
+    let n = ((0.25 * dd / tolerance).sqrt().ceil() as usize).max(1).min(4096);
+    (1 .. n + 1).map(|i| {
+        let t = i as f32 / n as f32;
+        let mt = 1.0 - t;
+        Point {
+            x: mt * mt * p0.x + 2.0 * mt * t * p1.x + t * t * p2.x,
+            y: mt * mt * p0.y + 2.0 * mt * t * p1.y + t * t * p2.y,
+        }
+    }).collect()
+}
+
+/// Points along a cubic bezier, not including the starting point.
+pub(crate) fn flatten_cubic(p0: Point, p1: Point, p2: Point, p3: Point, tolerance: f32) -> Vec<Point> {
+    let dd1 = second_difference(p0, p1, p2);
+    let dd2 = second_difference(p1, p2, p3);
+    let n = ((0.75 * dd1.max(dd2) / tolerance).sqrt().ceil() as usize).max(1).min(4096);
+    (1 .. n + 1).map(|i| {
+        let t = i as f32 / n as f32;
+        let mt = 1.0 - t;
+        let a = mt * mt * mt;
+        let b = 3.0 * mt * mt * t;
+        let c = 3.0 * mt * t * t;
+        let d = t * t * t;
+        Point {
+            x: a * p0.x + b * p1.x + c * p2.x + d * p3.x,
+            y: a * p0.y + b * p1.y + c * p2.y + d * p3.y,
+        }
+    }).collect()
+}
+
+/// The images of the x and y unit vectors under `m`, ignoring translation.
+fn axes(m: &Matrix) -> (Vector, Vector) {
+    let origin = m.transform_point(&Point { x: 0.0, y: 0.0 });
+    let x = m.transform_point(&Point { x: 1.0, y: 0.0 }) - origin;
+    let y = m.transform_point(&Point { x: 0.0, y: 1.0 }) - origin;
+    (x, y)
+}
+
+/// How much `m` scales lengths, on average.
+pub(crate) fn matrix_scale(m: &Matrix) -> f32 {
+    let (x, y) = axes(m);
+    x.cross(&y).abs().sqrt()
+}
+
+/// The scale factor of `m` if it only rotates, translates, mirrors and
+/// scales uniformly, which means that circles stay circles.
+pub(crate) fn similarity_scale(m: &Matrix) -> Option<f32> {
+    const EPSILON: f32 = 0.0001;
+    let (x, y) = axes(m);
+    let (lx, ly) = (x.magnitude(), y.magnitude());
+    if (lx - ly).abs() <= EPSILON * lx.max(ly) && x.dot(&y).abs() <= EPSILON * lx * ly {
+        Some(lx)
+    } else {
+        None
+    }
+}
+
+/// True if `m` doesn't rotate or skew, which means that axis aligned
+/// rectangles stay axis aligned.
+pub(crate) fn is_axis_aligned(m: &Matrix) -> bool {
+    const EPSILON: f32 = 0.0001;
+    let (x, y) = axes(m);
+    x.y.abs() <= EPSILON * x.x.abs() && y.x.abs() <= EPSILON * y.y.abs()
+}
@@ -0,0 +1,316 @@
+//! A small decoder for zlib / DEFLATE streams (RFC 1950 and 1951), for
+//! reading compressed image data.
+
+const MAX_BITS: usize = 15;
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl <'a> BitReader<'a> {
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        while self.bit_count < count {
+            let byte = match self.data.get(self.pos) {
+                Some(&b) => b,
+                None => return Err("unexpected end of compressed data".to_string()),
+            };
+            self.pos += 1;
+            self.bit_buffer |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = self.bit_buffer & ((1u32 << count) - 1);
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.bit_buffer = 0;
+        self.bit_count = 0;
+    }
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length
+/// and the symbols in code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // Reject codes that use more bit patterns than exist.
+        let mut left: i32 = 1;
+        for len in 1 .. MAX_BITS + 1 {
+            left <<= 1;
+            left -= counts[len] as i32;
+            if left < 0 {
+                return Err("over-subscribed huffman code".to_string());
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1 .. MAX_BITS + 1 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        Ok(Huffman { counts: counts, symbols: symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        // Codes are packed most significant bit first.
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for len in 1 .. MAX_BITS + 1 {
+            code |= try!(reader.bits(1)) as i32;
+            let count = self.counts[len] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        Err("invalid huffman code".to_string())
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+/// The order that code length code lengths are stored in.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Decompresses a zlib stream that is expected to hold no more than
+/// `limit` bytes.
+pub(crate) fn zlib_decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if data.len() < 2 {
+        return Err("truncated zlib header".to_string());
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || ((cmf as u16) << 8 | flg as u16) % 31 != 0 {
+        return Err("invalid zlib header".to_string());
+    }
+    if flg & 0x20 != 0 {
+        return Err("zlib preset dictionaries aren't supported".to_string());
+    }
+    inflate(&data[2..], limit)
+}
+
+/// Decompresses a raw DEFLATE stream, giving up if it holds more than
+/// `limit` bytes rather than letting a small stream fill up memory.
+pub(crate) fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut reader = BitReader { data: data, pos: 0, bit_buffer: 0, bit_count: 0 };
+    let mut out = vec![];
+
+    loop {
+        let last = try!(reader.bits(1)) == 1;
+        match try!(reader.bits(2)) {
+            0 => try!(stored_block(&mut reader, &mut out, limit)),
+            1 => {
+                let (lengths, distances) = try!(fixed_codes());
+                try!(compressed_block(&mut reader, &mut out, limit, &lengths, &distances));
+            }
+            2 => {
+                let (lengths, distances) = try!(dynamic_codes(&mut reader));
+                try!(compressed_block(&mut reader, &mut out, limit, &lengths, &distances));
+            }
+            _ => return Err("invalid block type".to_string()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn too_long() -> Result<(), String> {
+    Err("decompressed data is longer than expected".to_string())
+}
+
+fn stored_block(reader: &mut BitReader, out: &mut Vec<u8>, limit: usize) -> Result<(), String> {
+    reader.align_to_byte();
+    let pos = reader.pos;
+    if pos + 4 > reader.data.len() {
+        return Err("truncated stored block".to_string());
+    }
+    let len = reader.data[pos] as usize | (reader.data[pos + 1] as usize) << 8;
+    let nlen = reader.data[pos + 2] as usize | (reader.data[pos + 3] as usize) << 8;
+    if len != !nlen & 0xffff {
+        return Err("corrupt stored block length".to_string());
+    }
+    let start = pos + 4;
+    if start + len > reader.data.len() {
+        return Err("truncated stored block".to_string());
+    }
+    if out.len() + len > limit {
+        return too_long();
+    }
+    out.extend_from_slice(&reader.data[start .. start + len]);
+    reader.pos = start + len;
+    Ok(())
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), String> {
+    let mut lengths = [0u8; 288];
+    for (i, len) in lengths.iter_mut().enumerate() {
+        *len = match i {
+            0 ... 143 => 8,
+            144 ... 255 => 9,
+            256 ... 279 => 7,
+            _ => 8,
+        };
+    }
+    Ok((try!(Huffman::new(&lengths)), try!(Huffman::new(&[5; 30]))))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = try!(reader.bits(5)) as usize + 257;
+    let distance_count = try!(reader.bits(5)) as usize + 1;
+    let code_count = try!(reader.bits(4)) as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err("too many length or distance codes".to_string());
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[.. code_count] {
+        code_lengths[i] = try!(reader.bits(3)) as u8;
+    }
+    let code_lengths = try!(Huffman::new(&code_lengths));
+
+    let mut lengths = vec![];
+    while lengths.len() < literal_count + distance_count {
+        let symbol = try!(code_lengths.decode(reader));
+        let (value, repeat) = match symbol {
+            0 ... 15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Some(&previous) => (previous, 3 + try!(reader.bits(2))),
+                None => return Err("repeated length without a previous length".to_string()),
+            },
+            17 => (0, 3 + try!(reader.bits(3))),
+            _ => (0, 11 + try!(reader.bits(7))),
+        };
+        for _ in 0 .. repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() != literal_count + distance_count {
+        return Err("code lengths overrun the table".to_string());
+    }
+    if lengths[256] == 0 {
+        return Err("missing end of block code".to_string());
+    }
+
+    let literals = try!(Huffman::new(&lengths[.. literal_count]));
+    let distances = try!(Huffman::new(&lengths[literal_count ..]));
+    Ok((literals, distances))
+}
+
+fn compressed_block(reader: &mut BitReader, out: &mut Vec<u8>, limit: usize,
+                    literals: &Huffman, distances: &Huffman) -> Result<(), String> {
+    loop {
+        let symbol = try!(literals.decode(reader)) as usize;
+        if symbol < 256 {
+            if out.len() >= limit {
+                return too_long();
+            }
+            out.push(symbol as u8);
+        } else if symbol == 256 {
+            return Ok(());
+        } else {
+            let symbol = symbol - 257;
+            if symbol >= 29 {
+                return Err("invalid length code".to_string());
+            }
+            let len = LENGTH_BASE[symbol] as usize + try!(reader.bits(LENGTH_EXTRA[symbol] as u32)) as usize;
+
+            let symbol = try!(distances.decode(reader)) as usize;
+            if symbol >= 30 {
+                return Err("invalid distance code".to_string());
+            }
+            let distance = DIST_BASE[symbol] as usize + try!(reader.bits(DIST_EXTRA[symbol] as u32)) as usize;
+            if distance > out.len() {
+                return Err("distance reaches before the start of the data".to_string());
+            }
+            if out.len() + len > limit {
+                return too_long();
+            }
+
+            // The copy can overlap what it's writing, so go byte by byte.
+            let start = out.len() - distance;
+            for i in 0 .. len {
+                let byte = out[start + i];
+                out.push(byte);
+            }
+        }
+    }
+}
+
+#[test]
+fn each_block_type_is_decoded() {
+    // Stored, then fixed codes, then dynamic codes, as written by zlib.
+    let stored = [0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o'];
+    assert_eq!(inflate(&stored, 5).unwrap(), b"hello");
+
+    let fixed = [0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x00];
+    assert_eq!(inflate(&fixed, 100).unwrap(), &b"hello hello hello"[..]);
+
+    let dynamic = [0x1d, 0xc4, 0x01, 0x09, 0x00, 0x00, 0x0c, 0x84, 0xc0, 0xac, 0x6a, 0xff,
+                   0x0e, 0x7b, 0x76, 0x82, 0x24, 0x64, 0x84, 0x0b, 0x87, 0xf9, 0x75];
+    assert_eq!(dynamic[0] >> 1 & 3, 2);
+    assert_eq!(inflate(&dynamic, 100).unwrap(), &b"acbaacbcacababaabbbbaaaabaaaac"[..]);
+
+    // A zlib header in front of the same stored block.
+    let mut zlib = vec![0x78, 0x01];
+    zlib.extend_from_slice(&stored);
+    assert_eq!(zlib_decompress(&zlib, 5).unwrap(), b"hello");
+}
+
+#[test]
+fn corrupt_streams_are_rejected() {
+    let stored = [0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o'];
+    let fixed = [0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x00];
+
+    // Block type 3 doesn't exist.
+    assert!(inflate(&[0x07], 100).is_err());
+    // The length and its complement disagree.
+    assert!(inflate(&[0x01, 0x05, 0x00, 0xfa, 0xfe, b'h', b'e', b'l', b'l', b'o'], 100).is_err());
+    // Truncated data.
+    assert!(inflate(&stored[.. 8], 100).is_err());
+    assert!(inflate(&fixed[.. 5], 100).is_err());
+    // A fixed block that starts with a copy of data that isn't there:
+    // length 3 (code 257, 0000001) then distance 1 (code 0, 00000).
+    assert!(inflate(&[0x03, 0x02], 100).is_err());
+    // More data than the caller expected.
+    assert!(inflate(&stored, 4).is_err());
+    assert!(inflate(&fixed, 16).is_err());
+    // A bad zlib header.
+    assert!(zlib_decompress(&[0x78, 0x02, 0x01], 100).is_err());
+}
@@ -1,2 +1,17 @@
 pub mod pdf;
+pub mod svg;
+pub mod dxf;
+pub mod gcode;
+pub mod hpgl;
+pub mod units;
 pub mod output_device;
+pub mod import;
+pub mod svg_import;
+pub mod dxf_import;
+pub mod inflate;
+pub mod raster;
+pub mod expression;
+pub mod font;
+pub mod cff;
+pub mod hershey;
+pub mod barcode;
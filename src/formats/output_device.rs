@@ -1,10 +1,114 @@
 use ::util::geom::Point;
+use ::FillRule;
 
 pub trait OutputDevice {
     fn start_line(&mut self);
     fn add_point(&mut self, Point);
     fn end_line(&mut self);
     fn set_size(&mut self, _w: f32, _h: f32) { }
+
+    /// Begins a line that is described by `info`.
+    ///
+    /// Devices that don't care about path metadata can leave this alone
+    /// and only implement `start_line`.
+    fn start_path(&mut self, _info: &PathInfo) {
+        self.start_line();
+    }
+
+    /// Ends the line started by the matching `start_path`.
+    fn end_path(&mut self, _info: &PathInfo) {
+        self.end_line();
+    }
+
+    /// Begins a filled region.
+    ///
+    /// Every path drawn before the matching `end_fill` is a closed contour
+    /// of the same region, and the contours are filled together using
+    /// `rule`.  Devices that can't fill may ignore this and stroke the
+    /// contours instead.
+    fn start_fill(&mut self, _rule: FillRule, _info: &PathInfo) { }
+    fn end_fill(&mut self) { }
+}
+
+/// The kind of work that a machine should do along a path.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Cut all the way through the material.
+    Cut,
+    /// Mark a line without cutting through (fold lines, stitch guides).
+    Score,
+    /// Fill in an area.
+    Engrave,
+}
+
+/// Everything that is known about a path before its points are drawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PathInfo {
+    /// True if the path ends where it began.
+    pub closed: bool,
+    /// The index of the figure (in the order they were added to the
+    /// scene) that the path belongs to.
+    pub figure: usize,
+    pub operation: Operation,
+    pub style: StrokeStyle,
+}
+
+/// How a line should be drawn by devices that care about appearance.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StrokeStyle {
+    /// Red, green and blue, each between 0 and 1.
+    pub color: (f32, f32, f32),
+    /// Line width, in the same units as the points being drawn.  Zero
+    /// asks for the thinnest line that the device can draw.
+    pub width: f32,
+}
+
+impl StrokeStyle {
+    pub fn new(color: (f32, f32, f32), width: f32) -> StrokeStyle {
+        StrokeStyle {
+            color: color,
+            width: width,
+        }
+    }
+
+    /// A black line that is as thin as possible.
+    pub fn hairline() -> StrokeStyle {
+        StrokeStyle::new((0.0, 0.0, 0.0), 0.0)
+    }
+}
+
+impl Operation {
+    /// A short lowercase name, suitable for layer names.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Operation::Cut => "cut",
+            Operation::Score => "score",
+            Operation::Engrave => "engrave",
+        }
+    }
+
+    /// The conventional laser colours: red for cuts, blue for scores and
+    /// black for engraving, all drawn as hairlines.
+    pub fn default_style(&self) -> StrokeStyle {
+        match *self {
+            Operation::Cut => StrokeStyle::new((1.0, 0.0, 0.0), 0.0),
+            Operation::Score => StrokeStyle::new((0.0, 0.0, 1.0), 0.0),
+            Operation::Engrave => StrokeStyle::hairline(),
+        }
+    }
+}
+
+impl PathInfo {
+    /// Describes a path that is drawn in the default style of its
+    /// operation.
+    pub fn new(operation: Operation, closed: bool, figure: usize) -> PathInfo {
+        PathInfo {
+            closed: closed,
+            figure: figure,
+            operation: operation,
+            style: operation.default_style(),
+        }
+    }
 }
 
 pub struct NullDevice;
@@ -1,11 +1,65 @@
-use ::OutputDevice;
+use std::io::{self, Write, BufWriter};
+use std::fs::File;
+
+use ::{OutputDevice, FillRule, StrokeStyle, PathInfo};
 use ::util::geom::Point;
 
-pub struct PdfWriter {
+struct Page {
     size: (f32, f32),
-    line_buffer: String,
+    content: String,
+}
+
+/// Standard sheets of paper that a tiled design can be printed on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PaperSize {
+    Letter,
+    A4,
+}
+
+/// Settings for printing a design across several sheets of paper that
+/// get taped together afterwards.
+#[derive(Copy, Clone, Debug)]
+pub struct Tiling {
+    pub paper: PaperSize,
+    /// Space left blank around the edge of each sheet, in points.
+    pub margin: f32,
+    /// How far each sheet overlaps its neighbours, in points.
+    pub overlap: f32,
+}
+
+struct Sheet {
+    page: usize,
+    tile: Option<Tile>,
+}
+
+struct Tile {
+    paper_size: (f32, f32),
+    margin: f32,
+    overlap: f32,
+    row: usize,
+    column: usize,
+    rows: usize,
+    columns: usize,
+    // How far the drawing needs to move to put this tile onto the sheet.
+    offset: (f32, f32),
+}
+
+pub struct PdfWriter {
+    // The page currently being drawn on is always the last one.
+    pages: Vec<Page>,
+    // Names of the optional content groups, in the order they were created.
+    layers: Vec<String>,
+    current_layer: Option<usize>,
+    style: StrokeStyle,
+    // The style that the current page's graphics state is set to.
+    applied_style: Option<StrokeStyle>,
     conversion: f32,
     start: bool,
+    // The fill rule of the region currently being drawn, if any.
+    filling: Option<FillRule>,
+    // True if there are lines in the buffer that haven't been stroked yet.
+    stroke_pending: bool,
+    tiling: Option<Tiling>,
 }
 
 fn append_line<S: AsRef<str>>(buffer: &mut String, line: S) {
@@ -13,108 +67,694 @@ fn append_line<S: AsRef<str>>(buffer: &mut String, line: S) {
     buffer.push_str("\r\n");
 }
 
+/// Escapes a string so that it can be used inside of a PDF `(...)` string.
+fn escape_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 impl PdfWriter {
     pub fn new(_units: &str, conversion_factor: f32) -> PdfWriter {
         PdfWriter {
-            size: (800.0, 800.0),
-            line_buffer: String::new(),
+            pages: vec![Page { size: (800.0, 800.0), content: String::new() }],
+            layers: vec![],
+            current_layer: None,
+            style: StrokeStyle::hairline(),
+            applied_style: None,
             conversion: conversion_factor,
-            start: false
+            start: false,
+            filling: None,
+            stroke_pending: false,
+            tiling: None,
+        }
+    }
+
+    /// Finishes the current page and starts drawing on a new one.
+    ///
+    /// The new page starts out the same size as the previous one, and
+    /// stays on the same layer.
+    pub fn new_page(&mut self) {
+        let size = self.pages.last().unwrap().size;
+        let tail = self.page_tail();
+        self.content().push_str(&tail);
+        self.stroke_pending = false;
+
+        self.pages.push(Page { size: size, content: String::new() });
+        self.applied_style = None;
+        if let Some(layer) = self.current_layer {
+            append_line(self.content(), format!("/OC /L{} BDC", layer));
         }
     }
 
-    pub fn write_out(&self, path: &str) {
-        use std::io::Write;
-        use std::fs::File;
+    /// Sets the colour and width of every line drawn after this call.
+    ///
+    /// Filled regions are filled with the same colour.
+    pub fn set_stroke_style(&mut self, style: StrokeStyle) {
+        self.style = style;
+    }
+
+    /// Puts everything drawn after this call onto the optional content
+    /// group (layer) called `name`, creating it if it doesn't exist yet.
+    ///
+    /// Passing `None` draws onto the page outside of any layer.
+    pub fn set_layer(&mut self, name: Option<&str>) {
+        let layer = name.map(|name| {
+            match self.layers.iter().position(|l| l == name) {
+                Some(i) => i,
+                None => {
+                    self.layers.push(name.to_string());
+                    self.layers.len() - 1
+                }
+            }
+        });
+
+        if layer == self.current_layer {
+            return;
+        }
+
+        self.flush_stroke();
+        if self.current_layer.is_some() {
+            append_line(self.content(), "EMC");
+        }
+        if let Some(layer) = layer {
+            append_line(self.content(), format!("/OC /L{} BDC", layer));
+        }
+        self.current_layer = layer;
+    }
+
+    /// Splits every page across as many sheets of `tiling.paper` as it
+    /// takes to print it at full size, or prints pages whole if `None`.
+    pub fn set_tiling(&mut self, tiling: Option<Tiling>) {
+        self.tiling = tiling;
+    }
+
+    /// Writes the document to a file at `path`.
+    pub fn write_out(&self, path: &str) -> io::Result<()> {
+        let file = try!(File::create(path));
+        let mut out = BufWriter::new(file);
+        try!(self.write_to(&mut out));
+        out.flush()
+    }
+
+    /// Writes the document to `out`.
+    ///
+    /// The content of every page is kept in memory until this is called,
+    /// but the rest of the document is written as it is produced.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        // Work out which sheets are going to be printed.
+        let mut sheets = vec![];
+        for (i, page) in self.pages.iter().enumerate() {
+            match self.tiling {
+                Some(tiling) => sheets.extend(tiling.tiles(i, page.size)),
+                None => sheets.push(Sheet { page: i, tile: None }),
+            }
+        }
+
+        // Object numbers: the catalog and page tree come first, then the
+        // layers and the label font, then the drawing of each page, and
+        // finally a page object (plus the tile decorations) for each sheet.
+        let first_layer = 3;
+        let font = first_layer + self.layers.len();
+        let first_drawing = font + 1;
+        let first_sheet = first_drawing + self.pages.len();
+        let per_sheet = if self.tiling.is_some() { 3 } else { 1 };
+        let sheet_obj = |i: usize| first_sheet + per_sheet * i;
+
+        let mut out = PdfOutput {
+            out: out,
+            position: 0,
+            offsets: vec![],
+        };
+
+        try!(out.line("%PDF-1.6"));
 
-        fn write_header(buffer: &mut String, offsets: &mut Vec<usize>, (width, height): (f32, f32)) {
-            append_line(buffer, "%PDF-1.6");
+        let layer_refs = (0 .. self.layers.len())
+            .map(|i| format!("{} 0 R", first_layer + i))
+            .collect::<Vec<_>>()
+            .join(" ");
 
-            offsets.push(buffer.len());
-            append_line(buffer, "1 0 obj");
-            append_line(buffer, "<</Type /Catalog /Pages 2 0 R>>");
-            append_line(buffer, "endobj");
+        if self.layers.is_empty() {
+            try!(out.object(1, "<</Type /Catalog /Pages 2 0 R>>"));
+        } else {
+            try!(out.object(1, format!(
+                "<</Type /Catalog /Pages 2 0 R /OCProperties <</OCGs [{0}] /D <</Order [{0}] /ON [{0}]>>>>>>",
+                layer_refs)));
+        }
 
-            offsets.push(buffer.len());
-            append_line(buffer, "2 0 obj");
-            append_line(buffer, "<</Type /Pages /Kids [3 0 R] /Count 1>>");
-            append_line(buffer, "endobj");
+        let kids = (0 .. sheets.len())
+            .map(|i| format!("{} 0 R", sheet_obj(i)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        try!(out.object(2, format!("<</Type /Pages /Kids [{}] /Count {}>>", kids, sheets.len())));
 
-            offsets.push(buffer.len());
-            append_line(buffer, "3 0 obj");
-            append_line(buffer, format!("<</Type /Page /Parent 2 0 R /Contents 4 0 R /MediaBox [0 0 {} {}] /Resources<<>>>>", width, height));
-            append_line(buffer, "endobj");
+        for (i, name) in self.layers.iter().enumerate() {
+            try!(out.object(first_layer + i, format!("<</Type /OCG /Name ({})>>", escape_string(name))));
         }
 
-        fn write_body(buffer: &mut String, drawing_lines: &str) {
-            //append_line(buffer, "/DeviceRGB CS");
-            // Set the pen width to 0
-            append_line(buffer, "0 w");
-            buffer.push_str(drawing_lines);
-            // Final stroke
-            append_line(buffer, "S");
+        try!(out.object(font, "<</Type /Font /Subtype /Type1 /BaseFont /Helvetica>>"));
+
+        let last = self.pages.len() - 1;
+        for (i, page) in self.pages.iter().enumerate() {
+            // Every earlier page was finished off by `new_page`.
+            let tail = if i == last { self.page_tail() } else { String::new() };
+            try!(out.stream(first_drawing + i, &[&page.content[..], &tail[..]]));
         }
 
-        let mut buffer = String::new();
-        let mut body = String::new();
-        let mut offsets = Vec::new();
+        let properties = (0 .. self.layers.len())
+            .map(|i| format!("/L{} {} 0 R", i, first_layer + i))
+            .collect::<Vec<_>>()
+            .join(" ");
 
-        write_header(&mut buffer, &mut offsets, self.size);
-        write_body(&mut body, &self.line_buffer);
+        for (i, sheet) in sheets.iter().enumerate() {
+            let obj = sheet_obj(i);
+            let drawing = first_drawing + sheet.page;
+            let ((width, height), contents) = match sheet.tile {
+                Some(ref tile) => (tile.paper_size, format!("[{} 0 R {} 0 R {} 0 R]", obj + 1, drawing, obj + 2)),
+                None => (self.pages[sheet.page].size, format!("{} 0 R", drawing)),
+            };
 
-        offsets.push(buffer.len());
+            try!(out.object(obj, format!(
+                "<</Type /Page /Parent 2 0 R /Contents {} /MediaBox [0 0 {} {}] /Resources <</Properties <<{}>> /Font <</F1 {} 0 R>>>>>>",
+                contents, width, height, properties, font)));
 
-        append_line(&mut buffer, "4 0 obj");
-        append_line(&mut buffer, format!("<</length {}>>", body.len()));
-        append_line(&mut buffer, "stream");
-        buffer.push_str(&body);
-        append_line(&mut buffer, "");
-        append_line(&mut buffer, "endstream");
-        append_line(&mut buffer, "endobj");
+            if let Some(ref tile) = sheet.tile {
+                let (prefix, suffix) = (tile.prefix(), tile.suffix());
+                try!(out.stream(obj + 1, &[&prefix[..]]));
+                try!(out.stream(obj + 2, &[&suffix[..]]));
+            }
+        }
 
-        let xref_location = buffer.len();
+        let xref_location = out.position;
         // +1 because of the default empty object
-        let xref_count = offsets.len() + 1; 
-        append_line(&mut buffer, "xref");
-        append_line(&mut buffer, format!("0 {}", xref_count));
+        let xref_count = out.offsets.len() + 1;
+        try!(out.line("xref"));
+        try!(out.line(format!("0 {}", xref_count)));
         // The default empty object
-        append_line(&mut buffer, "0000000000 65535 f");
+        try!(out.line("0000000000 65535 f"));
 
+        let offsets = ::std::mem::replace(&mut out.offsets, vec![]);
         for offset in offsets {
-            append_line(&mut buffer, format!("{:010} 00000 n", offset));
+            try!(out.line(format!("{:010} 00000 n", offset)));
         }
 
-        append_line(&mut buffer, format!("trailer <</Size {} /Root 1 0 R>>", xref_count));
-        append_line(&mut buffer, "startxref");
-        append_line(&mut buffer, format!("{}", xref_location));
-        buffer.push_str("%%EOF");
+        try!(out.line(format!("trailer <</Size {} /Root 1 0 R>>", xref_count)));
+        try!(out.line("startxref"));
+        try!(out.line(format!("{}", xref_location)));
+        out.raw("%%EOF")
+    }
+}
+
+/// Keeps track of where each object starts while writing them out.
+struct PdfOutput<'a, W: 'a + Write> {
+    out: &'a mut W,
+    position: usize,
+    offsets: Vec<usize>,
+}
 
-        let mut f = File::create(path).unwrap();
-        f.write(buffer.as_bytes()).unwrap();
+impl <'a, W: Write> PdfOutput<'a, W> {
+    fn raw(&mut self, s: &str) -> io::Result<()> {
+        try!(self.out.write_all(s.as_bytes()));
+        self.position += s.len();
+        Ok(())
+    }
+
+    fn line<S: AsRef<str>>(&mut self, line: S) -> io::Result<()> {
+        try!(self.raw(line.as_ref()));
+        self.raw("\r\n")
+    }
+
+    fn begin_object(&mut self, obj: usize) -> io::Result<()> {
+        // The xref table relies on objects being written in order.
+        debug_assert_eq!(obj, self.offsets.len() + 1);
+        self.offsets.push(self.position);
+        self.line(format!("{} 0 obj", obj))
+    }
+
+    fn object<S: AsRef<str>>(&mut self, obj: usize, dictionary: S) -> io::Result<()> {
+        try!(self.begin_object(obj));
+        try!(self.line(dictionary));
+        self.line("endobj")
+    }
+
+    /// Writes a stream object whose data is all of `parts` in order.
+    fn stream(&mut self, obj: usize, parts: &[&str]) -> io::Result<()> {
+        let length = parts.iter().fold(0, |total, part| total + part.len());
+        try!(self.begin_object(obj));
+        try!(self.line(format!("<</Length {}>>", length)));
+        try!(self.line("stream"));
+        for part in parts {
+            try!(self.raw(part));
+        }
+        try!(self.line(""));
+        try!(self.line("endstream"));
+        self.line("endobj")
     }
 }
 
 impl PdfWriter {
     fn transform_point(&self, x: f32, y: f32) -> (f32, f32) {
-        (x * self.conversion, self.size.1 - y * self.conversion)
+        let height = self.pages.last().unwrap().size.1;
+        (x * self.conversion, height - y * self.conversion)
+    }
+
+    fn content(&mut self) -> &mut String {
+        &mut self.pages.last_mut().unwrap().content
+    }
+
+    /// Everything that needs to be appended to finish off the current page.
+    fn page_tail(&self) -> String {
+        let mut tail = String::new();
+        if self.stroke_pending {
+            append_line(&mut tail, "S");
+        }
+        if self.current_layer.is_some() {
+            append_line(&mut tail, "EMC");
+        }
+        tail
+    }
+
+    /// Styles a path and puts it on the layer named after its operation.
+    fn use_path_info(&mut self, info: &PathInfo) {
+        self.set_stroke_style(info.style);
+        self.set_layer(Some(info.operation.name()));
+    }
+
+    fn flush_stroke(&mut self) {
+        if self.stroke_pending {
+            append_line(self.content(), "S");
+            self.stroke_pending = false;
+        }
+    }
+
+    /// Makes sure that the graphics state matches the requested style.
+    ///
+    /// This can't happen in the middle of a path, so any lines that are
+    /// waiting to be stroked are stroked first.
+    fn apply_style(&mut self) {
+        if self.applied_style == Some(self.style) {
+            return;
+        }
+
+        self.flush_stroke();
+        let StrokeStyle { color: (r, g, b), width } = self.style;
+        let state = format!("{} w\r\n{} {} {} RG\r\n{} {} {} rg", width * self.conversion, r, g, b, r, g, b);
+        append_line(self.content(), state);
+        self.applied_style = Some(self.style);
     }
 }
 
 impl OutputDevice for PdfWriter {
     fn start_line(&mut self) {
+        if self.filling.is_none() {
+            self.apply_style();
+        }
         self.start = true;
     }
 
     fn add_point(&mut self, Point{x, y}: Point) {
         let (x, y) = self.transform_point(x, y);
         let cmd = if self.start { "m" } else { "l" };
-        append_line(&mut self.line_buffer, format!("{} {} {}", x, y, cmd));
+        append_line(self.content(), format!("{} {} {}", x, y, cmd));
         self.start = false;
     }
 
-    fn end_line(&mut self) { }
+    fn end_line(&mut self) {
+        if self.filling.is_some() {
+            append_line(self.content(), "h");
+        } else {
+            self.stroke_pending = true;
+        }
+    }
+
+    fn start_path(&mut self, info: &PathInfo) {
+        // Contours of a filled region were already styled by `start_fill`.
+        if self.filling.is_none() {
+            self.use_path_info(info);
+        }
+        self.start_line();
+    }
+
+    fn start_fill(&mut self, rule: FillRule, info: &PathInfo) {
+        self.use_path_info(info);
+        // Lines that were already drawn need to be stroked before the
+        // fill path begins, or they would be filled along with it.
+        self.apply_style();
+        self.flush_stroke();
+        self.filling = Some(rule);
+    }
+
+    fn end_fill(&mut self) {
+        match self.filling.take() {
+            Some(FillRule::EvenOdd) => append_line(self.content(), "f*"),
+            Some(FillRule::NonZero) => append_line(self.content(), "f"),
+            None => {}
+        }
+    }
 
     fn set_size(&mut self, w: f32, h: f32) {
-        self.size = (w * self.conversion, h * self.conversion);
+        self.pages.last_mut().unwrap().size = (w * self.conversion, h * self.conversion);
+    }
+}
+
+impl PaperSize {
+    /// Width and height in points.
+    pub fn size(&self) -> (f32, f32) {
+        match *self {
+            PaperSize::Letter => (612.0, 792.0),
+            PaperSize::A4 => (595.28, 841.89),
+        }
+    }
+}
+
+impl Tiling {
+    /// Half-inch margins and a half-inch overlap.
+    pub fn new(paper: PaperSize) -> Tiling {
+        Tiling {
+            paper: paper,
+            margin: 36.0,
+            overlap: 36.0,
+        }
+    }
+
+    fn tiles(&self, page: usize, (width, height): (f32, f32)) -> Vec<Sheet> {
+        let (area_w, area_h) = self.printable_area();
+        let step_x = (area_w - self.overlap).max(1.0);
+        let step_y = (area_h - self.overlap).max(1.0);
+        let columns = (((width - self.overlap) / step_x).ceil() as usize).max(1);
+        let rows = (((height - self.overlap) / step_y).ceil() as usize).max(1);
+
+        let mut out = vec![];
+        for row in 0 .. rows {
+            for column in 0 .. columns {
+                // Rows are counted from the top of the design, but the
+                // y axis points up.
+                let left = column as f32 * step_x;
+                let top = height - row as f32 * step_y;
+                out.push(Sheet {
+                    page: page,
+                    tile: Some(Tile {
+                        paper_size: self.paper.size(),
+                        margin: self.margin,
+                        overlap: self.overlap,
+                        row: row,
+                        column: column,
+                        rows: rows,
+                        columns: columns,
+                        offset: (self.margin - left, self.margin + area_h - top),
+                    }),
+                });
+            }
+        }
+        out
+    }
+
+    fn printable_area(&self) -> (f32, f32) {
+        let (w, h) = self.paper.size();
+        (w - 2.0 * self.margin, h - 2.0 * self.margin)
+    }
+}
+
+fn draw_line(buffer: &mut String, (x1, y1): (f32, f32), (x2, y2): (f32, f32)) {
+    append_line(buffer, format!("{} {} m {} {} l S", x1, y1, x2, y2));
+}
+
+fn draw_circle(buffer: &mut String, (cx, cy): (f32, f32), r: f32) {
+    // The usual four-bezier approximation of a circle.
+    let k = 0.5523 * r;
+    append_line(buffer, format!("{} {} m", cx + r, cy));
+    append_line(buffer, format!("{} {} {} {} {} {} c", cx + r, cy + k, cx + k, cy + r, cx, cy + r));
+    append_line(buffer, format!("{} {} {} {} {} {} c", cx - k, cy + r, cx - r, cy + k, cx - r, cy));
+    append_line(buffer, format!("{} {} {} {} {} {} c", cx - r, cy - k, cx - k, cy - r, cx, cy - r));
+    append_line(buffer, format!("{} {} {} {} {} {} c", cx + k, cy - r, cx + r, cy - k, cx + r, cy));
+    append_line(buffer, "S");
+}
+
+impl Tile {
+    fn area(&self) -> (f32, f32) {
+        let (w, h) = self.paper_size;
+        (w - 2.0 * self.margin, h - 2.0 * self.margin)
+    }
+
+    /// Row letter followed by column number, like a spreadsheet.
+    fn name(&self) -> String {
+        let row = if self.row < 26 {
+            ((b'A' + self.row as u8) as char).to_string()
+        } else {
+            format!("R{}-", self.row + 1)
+        };
+        format!("{}{}", row, self.column + 1)
+    }
+
+    /// Clips the drawing to the printable area and moves this tile's part
+    /// of it into view.
+    fn prefix(&self) -> String {
+        let m = self.margin;
+        let (w, h) = self.area();
+        let (tx, ty) = self.offset;
+        let mut out = String::new();
+        append_line(&mut out, "q");
+        append_line(&mut out, format!("{} {} {} {} re W n", m, m, w, h));
+        append_line(&mut out, format!("1 0 0 1 {} {} cm", tx, ty));
+        out
+    }
+
+    /// Crop marks, overlap guides, registration marks and the label.
+    fn suffix(&self) -> String {
+        let m = self.margin;
+        let o = self.overlap;
+        let (w, h) = self.area();
+        let mut out = String::new();
+        append_line(&mut out, "Q");
+        append_line(&mut out, "0.5 w 0 0 0 RG 0 0 0 rg");
+
+        // Crop marks just outside each corner of the printable area.
+        let gap = 0.2 * m;
+        let len = 0.6 * m;
+        for &(cx, cy, sx, sy) in &[(m, m, -1.0, -1.0), (m + w, m, 1.0, -1.0),
+                                   (m, m + h, -1.0, 1.0), (m + w, m + h, 1.0, 1.0)] {
+            draw_line(&mut out, (cx + sx * gap, cy), (cx + sx * (gap + len), cy));
+            draw_line(&mut out, (cx, cy + sy * gap), (cx, cy + sy * (gap + len)));
+        }
+
+        // Each side with a neighbour gets a dashed line where the
+        // neighbour's printable area begins and a registration mark in the
+        // middle of the shared strip.
+        let has_left = self.column > 0;
+        let has_right = self.column + 1 < self.columns;
+        let has_above = self.row > 0;
+        let has_below = self.row + 1 < self.rows;
+
+        append_line(&mut out, "[3 3] 0 d");
+        if has_left {
+            draw_line(&mut out, (m + o, m), (m + o, m + h));
+        }
+        if has_right {
+            draw_line(&mut out, (m + w - o, m), (m + w - o, m + h));
+        }
+        if has_below {
+            draw_line(&mut out, (m, m + o), (m + w, m + o));
+        }
+        if has_above {
+            draw_line(&mut out, (m, m + h - o), (m + w, m + h - o));
+        }
+        append_line(&mut out, "[] 0 d");
+
+        let r = (o / 2.0).min(10.0) * 0.8;
+        let mut marks = vec![];
+        if has_left { marks.push((m + o / 2.0, m + h / 2.0)); }
+        if has_right { marks.push((m + w - o / 2.0, m + h / 2.0)); }
+        if has_below { marks.push((m + w / 2.0, m + o / 2.0)); }
+        if has_above { marks.push((m + w / 2.0, m + h - o / 2.0)); }
+        for &(x, y) in &marks {
+            draw_circle(&mut out, (x, y), r);
+            draw_line(&mut out, (x - 1.5 * r, y), (x + 1.5 * r, y));
+            draw_line(&mut out, (x, y - 1.5 * r), (x, y + 1.5 * r));
+        }
+
+        let label = format!("{}: row {} of {}, column {} of {}",
+                            self.name(), self.row + 1, self.rows, self.column + 1, self.columns);
+        append_line(&mut out, format!("BT /F1 9 Tf {} {} Td ({}) Tj ET", m, 0.35 * m, escape_string(&label)));
+        out
+    }
+}
+
+#[test]
+fn xref_offsets_point_at_their_objects() {
+    let mut pdf = PdfWriter::new("in", 72.0);
+    pdf.set_size(2.0, 2.0);
+    pdf.set_layer(Some("cut"));
+    pdf.start_line();
+    pdf.add_point(Point { x: 0.0, y: 0.0 });
+    pdf.add_point(Point { x: 1.0, y: 1.0 });
+    pdf.end_line();
+    pdf.new_page();
+    pdf.start_line();
+    pdf.add_point(Point { x: 1.0, y: 0.0 });
+    pdf.add_point(Point { x: 0.0, y: 1.0 });
+    pdf.end_line();
+
+    let mut buffer = vec![];
+    pdf.write_to(&mut buffer).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+
+    let startxref = text.rfind("startxref\r\n").unwrap() + "startxref\r\n".len();
+    let xref: usize = text[startxref..].lines().next().unwrap().parse().unwrap();
+    assert!(text[xref..].starts_with("xref"));
+
+    let entries = text[xref..].lines().skip(3).take_while(|l| !l.starts_with("trailer"));
+    let mut count = 0;
+    for (i, entry) in entries.enumerate() {
+        let offset: usize = entry[..10].parse().unwrap();
+        assert!(text[offset..].starts_with(&format!("{} 0 obj", i + 1)));
+        count += 1;
+    }
+    // Catalog, page tree, one layer, font, two drawings and two pages.
+    assert_eq!(count, 8);
+}
+
+#[test]
+fn pages_layers_and_styles_are_written() {
+    use ::Operation;
+
+    let mut pdf = PdfWriter::new("in", 72.0);
+    pdf.set_size(2.0, 2.0);
+
+    let paths = [(Operation::Cut, StrokeStyle::new((1.0, 0.0, 0.0), 0.0)),
+                 (Operation::Score, StrokeStyle::new((0.0, 0.0, 1.0), 0.5)),
+                 (Operation::Engrave, StrokeStyle::new((0.0, 0.0, 0.0), 0.25))];
+    for (i, &(operation, style)) in paths.iter().enumerate() {
+        if i != 0 {
+            pdf.new_page();
+        }
+        let info = PathInfo { closed: false, figure: i, operation: operation, style: style };
+        pdf.start_path(&info);
+        pdf.add_point(Point { x: 0.0, y: 0.0 });
+        pdf.add_point(Point { x: 1.0, y: 1.0 });
+        pdf.end_path(&info);
+    }
+
+    let mut buffer = vec![];
+    pdf.write_to(&mut buffer).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+
+    assert!(text.contains("/Type /Pages /Kids [10 0 R 11 0 R 12 0 R] /Count 3>>"));
+    assert!(text.contains("/OCProperties <</OCGs [3 0 R 4 0 R 5 0 R] /D <</Order [3 0 R 4 0 R 5 0 R] /ON [3 0 R 4 0 R 5 0 R]>>>>"));
+    for (i, name) in ["cut", "score", "engrave"].iter().enumerate() {
+        assert!(text.contains(&format!("{} 0 obj\r\n<</Type /OCG /Name ({})>>", i + 3, name)));
+    }
+
+    // Every stream is exactly as long as it says it is.
+    let mut streams = vec![];
+    let mut rest = &text[..];
+    while let Some(start) = rest.find("<</Length ") {
+        rest = &rest[start + "<</Length ".len()..];
+        let end = rest.find(">>").unwrap();
+        let length: usize = rest[..end].parse().unwrap();
+        rest = &rest[end..];
+        assert!(rest.starts_with(">>\r\nstream\r\n"));
+        rest = &rest[">>\r\nstream\r\n".len()..];
+        streams.push(&rest[..length]);
+        assert!(rest[length..].starts_with("\r\nendstream"));
+    }
+    assert_eq!(streams.len(), 3);
+
+    // Each page is on its own layer and stroked in its own style.
+    let expected = [("/OC /L0 BDC", "0 w\r\n1 0 0 RG"),
+                    ("/OC /L1 BDC", "36 w\r\n0 0 1 RG"),
+                    ("/OC /L2 BDC", "18 w\r\n0 0 0 RG")];
+    for (stream, &(layer, style)) in streams.iter().zip(expected.iter()) {
+        assert!(stream.contains(&format!("{}\r\n{}", layer, style)), "{:?}", stream);
+        assert!(stream.ends_with("S\r\nEMC\r\n"), "{:?}", stream);
+    }
+}
+
+#[test]
+fn tiles_cover_the_page_and_overlap_their_neighbours() {
+    // A 1250 point long collar pattern.
+    let (width, height) = (1250.0, 800.0);
+    for &(paper, columns, rows) in &[(PaperSize::Letter, 3, 2), (PaperSize::A4, 3, 2)] {
+        let tiling = Tiling::new(paper);
+        let (area_w, area_h) = tiling.printable_area();
+        let sheets = tiling.tiles(4, (width, height));
+        assert_eq!(sheets.len(), columns * rows);
+
+        // The part of the drawing that shows through the printable area
+        // of each tile: (left, bottom, right, top).
+        let mut visible = vec![];
+        for (i, sheet) in sheets.iter().enumerate() {
+            let tile = sheet.tile.as_ref().unwrap();
+            assert_eq!(sheet.page, 4);
+            assert_eq!((tile.rows, tile.columns), (rows, columns));
+            assert_eq!((tile.row, tile.column), (i / columns, i % columns));
+            assert_eq!(tile.paper_size, paper.size());
+
+            let (tx, ty) = tile.offset;
+            visible.push((tiling.margin - tx, tiling.margin - ty,
+                          tiling.margin + area_w - tx, tiling.margin + area_h - ty));
+        }
+
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+        for row in 0 .. rows {
+            for column in 0 .. columns {
+                let (left, bottom, right, top) = visible[row * columns + column];
+                if column == 0 {
+                    assert!(close(left, 0.0));
+                }
+                if column + 1 == columns {
+                    assert!(right >= width);
+                } else {
+                    let next_left = visible[row * columns + column + 1].0;
+                    assert!(close(right - next_left, tiling.overlap));
+                }
+                if row == 0 {
+                    assert!(close(top, height));
+                }
+                if row + 1 == rows {
+                    assert!(bottom <= 0.0);
+                } else {
+                    let next_top = visible[(row + 1) * columns + column].3;
+                    assert!(close(next_top - bottom, tiling.overlap));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn fill_rules_pick_the_fill_operator() {
+    use ::Operation;
+
+    for &(rule, operator) in &[(FillRule::NonZero, "h\r\nf\r\n"), (FillRule::EvenOdd, "h\r\nf*\r\n")] {
+        let mut pdf = PdfWriter::new("in", 72.0);
+        pdf.set_size(2.0, 2.0);
+        let info = PathInfo::new(Operation::Engrave, true, 0);
+        pdf.start_fill(rule, &info);
+        for square in &[[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)],
+                        [(0.5, 0.5), (0.5, 1.5), (1.5, 1.5), (1.5, 0.5)]] {
+            pdf.start_path(&info);
+            for &(x, y) in square {
+                pdf.add_point(Point { x: x, y: y });
+            }
+            pdf.end_path(&info);
+        }
+        pdf.end_fill();
+
+        let mut buffer = vec![];
+        pdf.write_to(&mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        // Both contours are part of one fill, which is never stroked.
+        assert_eq!(text.matches(" m\r\n").count(), 2);
+        assert_eq!(text.matches(operator).count(), 1);
+        assert_eq!(text.matches("h\r\n").count(), 2);
+        assert!(!text.contains("\r\nS\r\n"));
     }
 }
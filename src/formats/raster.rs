@@ -0,0 +1,727 @@
+use std::io::{self, Read};
+use std::fs::File;
+use std::path::Path;
+use std::error::Error;
+use std::fmt;
+
+use ::Implicit;
+use ::util::geom::{Point, Rect};
+use super::inflate;
+
+/// An 8 bit grayscale image, stored row by row from the top left, with 0
+/// for black and 255 for white.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl GrayImage {
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    /// The data isn't a PGM or PNG image.
+    UnknownFormat,
+    /// The image is damaged or truncated.
+    Malformed(String),
+    /// The image uses a feature that isn't implemented.
+    Unsupported(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ImageError::Io(ref e) => write!(f, "{}", e),
+            ImageError::UnknownFormat => write!(f, "the image isn't a PGM or PNG file"),
+            ImageError::Malformed(ref message) => write!(f, "malformed image: {}", message),
+            ImageError::Unsupported(ref message) => write!(f, "unsupported image: {}", message),
+        }
+    }
+}
+
+impl Error for ImageError {
+    fn description(&self) -> &str {
+        match *self {
+            ImageError::Io(ref e) => e.description(),
+            ImageError::UnknownFormat => "unknown image format",
+            ImageError::Malformed(_) => "malformed image",
+            ImageError::Unsupported(_) => "unsupported image",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            ImageError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(e: io::Error) -> ImageError {
+        ImageError::Io(e)
+    }
+}
+
+fn malformed<T>(message: &str) -> Result<T, ImageError> {
+    Err(ImageError::Malformed(message.to_string()))
+}
+
+/// The most pixels that an image may have.  Anything bigger is far more
+/// likely to be a damaged header than a real image, and would take
+/// gigabytes to turn into a distance field.
+const MAX_PIXELS: usize = 1 << 26;
+
+/// The number of pixels in an image, if the header's size is believable.
+fn pixel_count(width: usize, height: usize) -> Result<usize, ImageError> {
+    match width.checked_mul(height) {
+        Some(count) if count <= MAX_PIXELS => Ok(count),
+        _ => malformed("image dimensions are too large"),
+    }
+}
+
+/// Reads a PGM or PNG file, converting it to grayscale.
+pub fn read_image_file<P: AsRef<Path>>(path: P) -> Result<GrayImage, ImageError> {
+    let mut data = vec![];
+    try!(try!(File::open(path)).read_to_end(&mut data));
+    read_image(&data)
+}
+
+/// Decodes a PGM or PNG image, depending on what the data starts with.
+pub fn read_image(data: &[u8]) -> Result<GrayImage, ImageError> {
+    if data.starts_with(PNG_SIGNATURE) {
+        read_png(data)
+    } else if data.starts_with(b"P2") || data.starts_with(b"P5") {
+        read_pgm(data)
+    } else {
+        Err(ImageError::UnknownFormat)
+    }
+}
+
+/// Decodes a plain (P2) or binary (P5) PGM image.
+pub fn read_pgm(data: &[u8]) -> Result<GrayImage, ImageError> {
+    let binary = if data.starts_with(b"P2") {
+        false
+    } else if data.starts_with(b"P5") {
+        true
+    } else {
+        return Err(ImageError::UnknownFormat);
+    };
+
+    let mut pos = 2;
+    let mut header = [0usize; 3];
+    for value in header.iter_mut() {
+        *value = match pgm_number(data, &mut pos) {
+            Some(v) => v,
+            None => return malformed("incomplete PGM header"),
+        };
+    }
+    let (width, height, max) = (header[0], header[1], header[2]);
+    if max == 0 || max > 65535 {
+        return malformed("PGM maximum value out of range");
+    }
+
+    let count = try!(pixel_count(width, height));
+    // Exactly one whitespace character separates a binary header from the
+    // data, and every plain sample takes up at least one digit.
+    if binary {
+        pos += 1;
+    }
+    let bytes_per_sample = if binary && max > 255 { 2 } else { 1 };
+    if data.len().saturating_sub(pos) < count * bytes_per_sample {
+        return malformed("truncated PGM data");
+    }
+
+    let scale = |v: usize| (v.min(max) * 255 / max) as u8;
+    let mut pixels = Vec::with_capacity(count);
+    if binary {
+        let data = &data[pos.min(data.len()) ..];
+        for sample in data.chunks(bytes_per_sample).take(count) {
+            let v = if bytes_per_sample == 2 {
+                (sample[0] as usize) << 8 | sample[1] as usize
+            } else {
+                sample[0] as usize
+            };
+            pixels.push(scale(v));
+        }
+    } else {
+        for _ in 0 .. count {
+            match pgm_number(data, &mut pos) {
+                Some(v) => pixels.push(scale(v)),
+                None => return malformed("truncated PGM data"),
+            }
+        }
+    }
+
+    Ok(GrayImage { width: width, height: height, pixels: pixels })
+}
+
+/// Reads the next decimal number, skipping whitespace and comments.
+fn pgm_number(data: &[u8], pos: &mut usize) -> Option<usize> {
+    loop {
+        match data.get(*pos) {
+            Some(&b'#') => {
+                while *pos < data.len() && data[*pos] != b'\n' {
+                    *pos += 1;
+                }
+            }
+            Some(c) if (*c as char).is_whitespace() => *pos += 1,
+            Some(_) => break,
+            None => return None,
+        }
+    }
+
+    let mut value: usize = 0;
+    let start = *pos;
+    while let Some(&c @ b'0' ... b'9') = data.get(*pos) {
+        value = value.saturating_mul(10).saturating_add((c - b'0') as usize);
+        *pos += 1;
+    }
+    if *pos == start { None } else { Some(value) }
+}
+
+const PNG_SIGNATURE: &'static [u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The starting column and row and the spacing of each Adam7 pass.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4),
+    (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2),
+];
+
+fn be_u32(data: &[u8]) -> u32 {
+    (data[0] as u32) << 24 | (data[1] as u32) << 16 | (data[2] as u32) << 8 | data[3] as u32
+}
+
+/// Decodes a PNG image of any color type, bit depth or interlacing.
+///
+/// Color is converted to luminance and transparent pixels are laid over
+/// a white background.
+pub fn read_png(data: &[u8]) -> Result<GrayImage, ImageError> {
+    if !data.starts_with(PNG_SIGNATURE) {
+        return Err(ImageError::UnknownFormat);
+    }
+
+    let mut header = None;
+    let mut palette: Vec<[u8; 4]> = vec![];
+    let mut transparent: Option<Vec<u8>> = None;
+    let mut compressed = vec![];
+    let mut pos = PNG_SIGNATURE.len();
+    loop {
+        if pos + 8 > data.len() {
+            return malformed("truncated PNG chunk");
+        }
+        let len = be_u32(&data[pos ..]) as usize;
+        let kind = &data[pos + 4 .. pos + 8];
+        let start = pos + 8;
+        if start + len + 4 > data.len() {
+            return malformed("truncated PNG chunk");
+        }
+        let chunk = &data[start .. start + len];
+        pos = start + len + 4;
+
+        if kind == b"IHDR" {
+            if len < 13 {
+                return malformed("short IHDR chunk");
+            }
+            header = Some((be_u32(chunk) as usize, be_u32(&chunk[4..]) as usize,
+                           chunk[8], chunk[9], chunk[12]));
+        } else if kind == b"PLTE" {
+            palette = chunk.chunks(3)
+                           .filter(|c| c.len() == 3)
+                           .map(|c| [c[0], c[1], c[2], 255])
+                           .collect();
+        } else if kind == b"tRNS" {
+            transparent = Some(chunk.to_vec());
+        } else if kind == b"IDAT" {
+            compressed.extend_from_slice(chunk);
+        } else if kind == b"IEND" {
+            break;
+        }
+    }
+
+    let (width, height, depth, color_type, interlace) = match header {
+        Some(h) => h,
+        None => return malformed("missing IHDR chunk"),
+    };
+    let channels = match (color_type, depth) {
+        (0, 1) | (0, 2) | (0, 4) | (0, 8) | (0, 16) => 1,
+        (3, 1) | (3, 2) | (3, 4) | (3, 8) => 1,
+        (2, 8) | (2, 16) => 3,
+        (4, 8) | (4, 16) => 2,
+        (6, 8) | (6, 16) => 4,
+        _ => return Err(ImageError::Unsupported(
+            format!("color type {} with bit depth {}", color_type, depth))),
+    };
+    if interlace > 1 {
+        return Err(ImageError::Unsupported(format!("interlace method {}", interlace)));
+    }
+    let count = try!(pixel_count(width, height));
+
+    if let (3, Some(alpha)) = (color_type, transparent.as_ref()) {
+        for (entry, &a) in palette.iter_mut().zip(alpha.iter()) {
+            entry[3] = a;
+        }
+    }
+
+    let bits_per_pixel = channels * depth as usize;
+    let filter_step = ((bits_per_pixel + 7) / 8).max(1);
+    let passes: Vec<(usize, usize, usize, usize)> = if interlace == 1 {
+        ADAM7.to_vec()
+    } else {
+        vec![(0, 0, 1, 1)]
+    };
+
+    // The width, height and bytes per row of each pass.
+    let passes: Vec<_> = passes.into_iter().map(|(x0, y0, dx, dy)| {
+        let pass_width = if width > x0 { (width - x0 + dx - 1) / dx } else { 0 };
+        let pass_height = if height > y0 { (height - y0 + dy - 1) / dy } else { 0 };
+        let stride = (pass_width * bits_per_pixel + 7) / 8;
+        (x0, y0, dx, dy, pass_width, pass_height, stride)
+    }).filter(|p| p.4 > 0 && p.5 > 0).collect();
+
+    // Every row starts with its filter type.
+    let expected = passes.iter().fold(0, |total, p| total + p.5 * (1 + p.6));
+    let raw = match inflate::zlib_decompress(&compressed, expected) {
+        Ok(raw) => raw,
+        Err(message) => return Err(ImageError::Malformed(message)),
+    };
+    if raw.len() < expected {
+        return malformed("truncated image data");
+    }
+
+    let max_sample = ((1u32 << depth.min(8)) - 1) as u32;
+    let mut pixels = vec![255u8; count];
+    let mut offset = 0;
+    for &(x0, y0, dx, dy, pass_width, pass_height, stride) in &passes {
+        let mut previous = vec![0u8; stride];
+        for row in 0 .. pass_height {
+            let filter = raw[offset];
+            let mut line = raw[offset + 1 .. offset + 1 + stride].to_vec();
+            offset += 1 + stride;
+            try!(unfilter(filter, &mut line, &previous, filter_step));
+
+            for column in 0 .. pass_width {
+                // Samples are read as 8 bits, keeping the high byte of 16
+                // bit samples and stretching smaller ones.
+                let sample = |channel: usize| -> u32 {
+                    let index = column * channels + channel;
+                    match depth {
+                        16 => line[index * 2] as u32,
+                        8 => line[index] as u32,
+                        _ => {
+                            let bit = index * depth as usize;
+                            let byte = line[bit / 8] as u32;
+                            (byte >> (8 - depth as usize - bit % 8)) & max_sample
+                        }
+                    }
+                };
+                let raw_sample = |channel: usize| -> u32 {
+                    let index = column * channels + channel;
+                    if depth == 16 {
+                        (line[index * 2] as u32) << 8 | line[index * 2 + 1] as u32
+                    } else {
+                        sample(channel)
+                    }
+                };
+                let stretch = |v: u32| if depth < 8 { v * 255 / max_sample } else { v };
+
+                let (r, g, b, a) = match color_type {
+                    0 => {
+                        let v = stretch(sample(0));
+                        let key = match transparent {
+                            Some(ref t) if t.len() >= 2 => Some((t[0] as u32) << 8 | t[1] as u32),
+                            _ => None,
+                        };
+                        let a = if key == Some(raw_sample(0)) { 0 } else { 255 };
+                        (v, v, v, a)
+                    }
+                    2 => {
+                        let key = match transparent {
+                            Some(ref t) if t.len() >= 6 => Some(((t[0] as u32) << 8 | t[1] as u32,
+                                                                 (t[2] as u32) << 8 | t[3] as u32,
+                                                                 (t[4] as u32) << 8 | t[5] as u32)),
+                            _ => None,
+                        };
+                        let a = if key == Some((raw_sample(0), raw_sample(1), raw_sample(2))) { 0 } else { 255 };
+                        (sample(0), sample(1), sample(2), a)
+                    }
+                    3 => match palette.get(sample(0) as usize) {
+                        Some(c) => (c[0] as u32, c[1] as u32, c[2] as u32, c[3] as u32),
+                        None => return malformed("palette index out of range"),
+                    },
+                    4 => (sample(0), sample(0), sample(0), sample(1)),
+                    _ => (sample(0), sample(1), sample(2), sample(3)),
+                };
+
+                let luma = (299 * r + 587 * g + 114 * b) / 1000;
+                let gray = (luma * a + 255 * (255 - a)) / 255;
+                let x = x0 + column * dx;
+                let y = y0 + row * dy;
+                pixels[y * width + x] = gray as u8;
+            }
+
+            previous = line;
+        }
+    }
+
+    Ok(GrayImage { width: width, height: height, pixels: pixels })
+}
+
+/// Undoes a PNG scanline filter in place.
+fn unfilter(filter: u8, line: &mut [u8], previous: &[u8], step: usize) -> Result<(), ImageError> {
+    for i in 0 .. line.len() {
+        let left = if i >= step { line[i - step] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= step { previous[i - step] } else { 0 };
+        let prediction = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return malformed("unknown scanline filter"),
+        };
+        line[i] = line[i].wrapping_add(prediction);
+    }
+    Ok(())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// The shape drawn in a bitmap, as a signed distance field.
+///
+/// Pixels darker than the threshold are inside of the shape.  The image's
+/// top left corner is at the origin, with every pixel `pixel_size` units
+/// wide.
+#[derive(Clone, Debug)]
+pub struct RasterShape {
+    // Signed distances at the center of every pixel, with a one pixel
+    // border of empty space all around.
+    distances: Vec<f32>,
+    columns: usize,
+    rows: usize,
+    pixel_size: f32,
+    bounds: Option<Rect>,
+}
+
+impl RasterShape {
+    pub fn new(image: &GrayImage, threshold: u8, pixel_size: f32) -> RasterShape {
+        let (columns, rows) = (image.width + 2, image.height + 2);
+        let mut inside = vec![false; columns * rows];
+        let mut bounds: Option<Rect> = None;
+        for y in 0 .. image.height {
+            for x in 0 .. image.width {
+                if image.get(x, y) < threshold {
+                    inside[(y + 1) * columns + x + 1] = true;
+                    let corner = Point { x: x as f32 * pixel_size, y: y as f32 * pixel_size };
+                    let pixel = Rect::from_points(&corner, &Point { x: corner.x + pixel_size, y: corner.y + pixel_size });
+                    bounds = Some(match bounds {
+                        Some(bb) => bb.union_with(&pixel),
+                        None => pixel,
+                    });
+                }
+            }
+        }
+
+        let to_inside = squared_distance_transform(&inside, columns, rows, true);
+        let to_outside = squared_distance_transform(&inside, columns, rows, false);
+
+        // The edge of the shape runs half way between pixel centers.
+        let distances = (0 .. columns * rows).map(|i| {
+            if inside[i] {
+                -(to_outside[i].sqrt() - 0.5) * pixel_size
+            } else {
+                (to_inside[i].sqrt() - 0.5) * pixel_size
+            }
+        }).collect();
+
+        RasterShape {
+            distances: distances,
+            columns: columns,
+            rows: rows,
+            pixel_size: pixel_size,
+            bounds: bounds,
+        }
+    }
+
+    fn at(&self, x: usize, y: usize) -> f32 {
+        self.distances[y * self.columns + x]
+    }
+}
+
+/// The squared distance, in pixels, from every pixel to the nearest pixel
+/// where `mask` equals `target`.
+///
+/// This is the separable algorithm from Felzenszwalb and Huttenlocher's
+/// "Distance Transforms of Sampled Functions".
+fn squared_distance_transform(mask: &[bool], columns: usize, rows: usize, target: bool) -> Vec<f32> {
+    const FAR: f32 = 1.0e20;
+    let mut grid: Vec<f32> = mask.iter().map(|&m| if m == target { 0.0 } else { FAR }).collect();
+
+    let longest = columns.max(rows);
+    let mut f = vec![0.0; longest];
+    let mut d = vec![0.0; longest];
+    let mut v = vec![0; longest];
+    let mut z = vec![0.0; longest + 1];
+
+    for x in 0 .. columns {
+        for y in 0 .. rows {
+            f[y] = grid[y * columns + x];
+        }
+        transform_1d(&f[.. rows], &mut d, &mut v, &mut z);
+        for y in 0 .. rows {
+            grid[y * columns + x] = d[y];
+        }
+    }
+    for y in 0 .. rows {
+        f[.. columns].copy_from_slice(&grid[y * columns .. (y + 1) * columns]);
+        transform_1d(&f[.. columns], &mut d, &mut v, &mut z);
+        grid[y * columns .. (y + 1) * columns].copy_from_slice(&d[.. columns]);
+    }
+    grid
+}
+
+/// The lower envelope of the parabolas rooted at every sample of `f`.
+fn transform_1d(f: &[f32], d: &mut [f32], v: &mut [usize], z: &mut [f32]) {
+    const INF: f32 = ::std::f32::INFINITY;
+    let n = f.len();
+    let mut k = 0;
+    v[0] = 0;
+    z[0] = -INF;
+    z[1] = INF;
+    for q in 1 .. n {
+        let intersection = |k: usize| {
+            let p = v[k];
+            ((f[q] + (q * q) as f32) - (f[p] + (p * p) as f32)) / (2.0 * (q as f32 - p as f32))
+        };
+        let mut s = intersection(k);
+        while s <= z[k] {
+            k -= 1;
+            s = intersection(k);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = INF;
+    }
+
+    k = 0;
+    for q in 0 .. n {
+        while z[k + 1] < q as f32 {
+            k += 1;
+        }
+        let p = v[k];
+        let offset = q as f32 - p as f32;
+        d[q] = offset * offset + f[p];
+    }
+}
+
+impl Implicit for RasterShape {
+    fn sample(&self, pos: Point) -> f32 {
+        // Grid coordinates, where the center of the top left pixel of the
+        // image is at (1, 1).
+        let gx = pos.x / self.pixel_size + 0.5;
+        let gy = pos.y / self.pixel_size + 0.5;
+        let max_x = (self.columns - 1) as f32;
+        let max_y = (self.rows - 1) as f32;
+        let cx = gx.max(0.0).min(max_x);
+        let cy = gy.max(0.0).min(max_y);
+
+        let x0 = (cx.floor() as usize).min(self.columns - 2);
+        let y0 = (cy.floor() as usize).min(self.rows - 2);
+        let (fx, fy) = (cx - x0 as f32, cy - y0 as f32);
+        let top = self.at(x0, y0) * (1.0 - fx) + self.at(x0 + 1, y0) * fx;
+        let bottom = self.at(x0, y0 + 1) * (1.0 - fx) + self.at(x0 + 1, y0 + 1) * fx;
+        let value = top * (1.0 - fy) + bottom * fy;
+
+        // Past the border everything is empty, and the distance to the
+        // shape is at least the hypotenuse of the distance to the grid and
+        // the distance from there to the shape.
+        let outside = ((gx - cx) * (gx - cx) + (gy - cy) * (gy - cy)).sqrt() * self.pixel_size;
+        if outside > 0.0 {
+            (outside * outside + value * value).sqrt()
+        } else {
+            value
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        self.bounds
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+#[test]
+fn dark_pixels_become_a_distance_field() {
+    let pgm = b"P2\n# a 3x3 block in a 7x5 image\n7 5\n255\n\
+                255 255 255 255 255 255 255\n\
+                255   0   0   0 255 255 255\n\
+                255   0   0   0 255 255 255\n\
+                255   0   0   0 255 255 255\n\
+                255 255 255 255 255 255 255\n";
+    let image = read_image(pgm).unwrap();
+    assert_eq!((image.width, image.height), (7, 5));
+
+    let shape = RasterShape::new(&image, 128, 2.0);
+    let bounds = shape.bounding_box().unwrap();
+    assert_eq!((bounds.top_left, bounds.bottom_right), (Point { x: 2.0, y: 2.0 }, Point { x: 8.0, y: 8.0 }));
+
+    // The center pixel is a pixel and a half from the edge.
+    assert_eq!(shape.sample(Point { x: 5.0, y: 5.0 }), -3.0);
+    // Two pixels to the right of the block's edge.
+    assert_eq!(shape.sample(Point { x: 13.0, y: 5.0 }), 5.0);
+    // Far away the field is a lower bound on the real distance of 92.
+    let far = shape.sample(Point { x: 100.0, y: 5.0 });
+    assert!(far > 85.0 && far <= 92.0);
+}
+
+#[test]
+fn damaged_headers_are_rejected() {
+    fn is_malformed(result: Result<GrayImage, ImageError>) -> bool {
+        match result {
+            Err(ImageError::Malformed(_)) => true,
+            _ => false,
+        }
+    }
+
+    // Sizes that overflow, that are absurdly large, or that are bigger
+    // than the data that follows.
+    assert!(is_malformed(read_image(b"P5 18446744073709551615 2 255\n\0\0")));
+    assert!(is_malformed(read_image(b"P5 100000 100000 255\n\0\0")));
+    assert!(is_malformed(read_image(b"P5 40 40 65535\n\0\0\0\0")));
+    assert!(is_malformed(read_image(b"P2 3 3 255\n0 0 0 0")));
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    png.extend_from_slice(b"\0\0\0\x0dIHDR\xff\xff\xff\xff\xff\xff\xff\xff\x08\0\0\0\0\0\0\0\0");
+    png.extend_from_slice(b"\0\0\0\0IEND\0\0\0\0");
+    assert!(is_malformed(read_image(&png)));
+
+    // An image without any dark pixels has no shape in it.
+    let blank = read_image(b"P2 2 2 255\n255 255 255 255").unwrap();
+    assert!(RasterShape::new(&blank, 128, 1.0).bounding_box().is_none());
+}
+
+/// A PNG file with the given header fields and image data, which is
+/// stored in the zlib stream without compression.  Checksums are left
+/// as zeros, since they aren't checked.
+#[cfg(test)]
+fn png_file(size: (u32, u32), depth: u8, color_type: u8, interlace: u8,
+            chunks: &[(&[u8], &[u8])], raw: &[u8]) -> Vec<u8> {
+    fn push_u32(out: &mut Vec<u8>, v: u32) {
+        out.extend_from_slice(&[(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8]);
+    }
+    fn push_chunk(out: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+        push_u32(out, data.len() as u32);
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        push_u32(out, 0);
+    }
+
+    let mut header = vec![];
+    push_u32(&mut header, size.0);
+    push_u32(&mut header, size.1);
+    header.extend_from_slice(&[depth, color_type, 0, 0, interlace]);
+
+    assert!(raw.len() < 0x10000);
+    let len = raw.len() as u16;
+    let mut zlib = vec![0x78, 0x01, 0x01, len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8];
+    zlib.extend_from_slice(raw);
+    zlib.extend_from_slice(&[0, 0, 0, 0]);
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    push_chunk(&mut png, b"IHDR", &header);
+    for &(kind, data) in chunks {
+        push_chunk(&mut png, kind, data);
+    }
+    push_chunk(&mut png, b"IDAT", &zlib);
+    push_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+#[cfg(test)]
+fn pgm_file(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut pgm = format!("P5 {} {} 255\n", width, height).into_bytes();
+    pgm.extend_from_slice(pixels);
+    pgm
+}
+
+#[test]
+fn png_filters_match_the_pgm_image() {
+    let pixels: Vec<u8> = (0 .. 25).map(|i| (i * 97 % 256) as u8).collect();
+
+    // Row `y` uses filter `y`, from none through Paeth.
+    let mut raw = vec![];
+    for y in 0 .. 5 {
+        raw.push(y as u8);
+        for x in 0 .. 5 {
+            let at = |x: isize, y: isize| if x < 0 || y < 0 { 0 } else { pixels[y as usize * 5 + x as usize] };
+            let (x, y) = (x as isize, y as isize);
+            let (left, up, up_left) = (at(x - 1, y), at(x, y - 1), at(x - 1, y - 1));
+            let prediction = match y {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                _ => paeth(left, up, up_left),
+            };
+            raw.push(at(x, y).wrapping_sub(prediction));
+        }
+    }
+
+    let png = read_image(&png_file((5, 5), 8, 0, 0, &[], &raw)).unwrap();
+    assert_eq!(png, read_image(&pgm_file(5, 5, &pixels)).unwrap());
+
+    // Image data that inflates to more than the image holds.
+    raw.push(0);
+    match read_image(&png_file((5, 5), 8, 0, 0, &[], &raw)) {
+        Err(ImageError::Malformed(_)) => {}
+        other => panic!("expected a malformed image, got {:?}", other),
+    }
+}
+
+#[test]
+fn png_palettes_are_laid_over_white() {
+    // Black, white, red, half transparent black and invisible black.
+    let palette = [0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 0, 0, 0, 0, 0];
+    let alpha = [255, 255, 255, 128, 0];
+    let raw = [0, 0, 1, 2, 0, 3, 4, 0];
+    let png = read_image(&png_file((3, 2), 8, 3, 0, &[(b"PLTE", &palette), (b"tRNS", &alpha)], &raw)).unwrap();
+    assert_eq!(png, read_image(&pgm_file(3, 2, &[0, 255, 76, 127, 255, 0])).unwrap());
+}
+
+#[test]
+fn interlaced_pngs_match_the_pgm_image() {
+    let pixels: Vec<u8> = (0 .. 25).map(|i| (i * 10) as u8).collect();
+
+    let mut raw = vec![];
+    for &(x0, y0, dx, dy) in &ADAM7 {
+        for y in (y0 .. 5).filter(|y| (y - y0) % dy == 0) {
+            raw.push(0);
+            for x in (x0 .. 5).filter(|x| (x - x0) % dx == 0) {
+                raw.push(pixels[y * 5 + x]);
+            }
+        }
+    }
+
+    let png = read_image(&png_file((5, 5), 8, 0, 1, &[], &raw)).unwrap();
+    assert_eq!(png, read_image(&pgm_file(5, 5, &pixels)).unwrap());
+}
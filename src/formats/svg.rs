@@ -0,0 +1,229 @@
+use std::io::{self, Write, BufWriter};
+use std::fs::File;
+
+use ::{OutputDevice, FillRule, StrokeStyle, PathInfo};
+use ::util::geom::Point;
+
+const EPSILON: f32 = 0.001;
+
+/// All of the subpaths that share a style, written out as one `<path>`.
+struct PathGroup {
+    style: StrokeStyle,
+    class: String,
+    closed: bool,
+    data: String,
+}
+
+pub struct SvgWriter {
+    size: (f32, f32),
+    units: String,
+    conversion: f32,
+    current: Vec<Point>,
+    // Set by `start_path`; plain lines are guessed to be closed if they
+    // end where they started.
+    current_info: Option<PathInfo>,
+    groups: Vec<PathGroup>,
+    // One finished `<path>` element per filled region.
+    fills: Vec<String>,
+    fill_buffer: String,
+    filling: Option<(FillRule, StrokeStyle)>,
+}
+
+fn append_subpath(buffer: &mut String, points: &[Point], close: bool) {
+    for (i, &Point{x, y}) in points.iter().enumerate() {
+        let cmd = if i == 0 { "M" } else { "L" };
+        buffer.push_str(&format!("{}{} {} ", cmd, x, y));
+    }
+    if close {
+        buffer.push_str("Z ");
+    }
+}
+
+fn color(style: &StrokeStyle) -> String {
+    let (r, g, b) = style.color;
+    let c = |v: f32| (v.max(0.0).min(1.0) * 255.0).round() as u8;
+    format!("rgb({},{},{})", c(r), c(g), c(b))
+}
+
+impl SvgWriter {
+    /// Creates a writer whose coordinates are multiplied by
+    /// `conversion_factor` and interpreted as `units` (any SVG length
+    /// unit, such as "in", "mm" or "px").
+    pub fn new(units: &str, conversion_factor: f32) -> SvgWriter {
+        SvgWriter {
+            size: (800.0, 800.0),
+            units: units.to_string(),
+            conversion: conversion_factor,
+            current: vec![],
+            current_info: None,
+            groups: vec![],
+            fills: vec![],
+            fill_buffer: String::new(),
+            filling: None,
+        }
+    }
+
+    fn document(&self) -> String {
+        let (width, height) = self.size;
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n");
+        out.push_str(&format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" width=\"{w}{u}\" height=\"{h}{u}\" viewBox=\"0 0 {w} {h}\">\n",
+            w = width, h = height, u = self.units));
+
+        for fill in &self.fills {
+            out.push_str(fill);
+        }
+
+        for group in &self.groups {
+            // A zero width asks for a hairline, which SVG can only
+            // approximate with a one pixel non-scaling stroke.
+            let width = if group.style.width == 0.0 {
+                "stroke-width=\"1\" vector-effect=\"non-scaling-stroke\"".to_string()
+            } else {
+                format!("stroke-width=\"{}\"", group.style.width * self.conversion)
+            };
+            out.push_str(&format!(
+                "<path class=\"{} {}\" fill=\"none\" stroke=\"{}\" {} d=\"{}\"/>\n",
+                group.class, if group.closed { "closed" } else { "open" },
+                color(&group.style), width, group.data.trim()));
+        }
+
+        out.push_str("</svg>\n");
+        out
+    }
+
+    /// Writes the document to a file at `path`.
+    pub fn write_out(&self, path: &str) -> io::Result<()> {
+        let file = try!(File::create(path));
+        let mut out = BufWriter::new(file);
+        try!(self.write_to(&mut out));
+        out.flush()
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(self.document().as_bytes())
+    }
+
+    fn group_for(&mut self, style: StrokeStyle, class: &str, closed: bool) -> &mut String {
+        let position = self.groups.iter().position(|g| {
+            g.style == style && g.class == class && g.closed == closed
+        });
+        let index = match position {
+            Some(i) => i,
+            None => {
+                self.groups.push(PathGroup {
+                    style: style,
+                    class: class.to_string(),
+                    closed: closed,
+                    data: String::new(),
+                });
+                self.groups.len() - 1
+            }
+        };
+        &mut self.groups[index].data
+    }
+}
+
+impl OutputDevice for SvgWriter {
+    fn start_line(&mut self) {
+        self.current.clear();
+    }
+
+    fn add_point(&mut self, Point{x, y}: Point) {
+        self.current.push(Point {
+            x: x * self.conversion,
+            y: y * self.conversion,
+        });
+    }
+
+    fn end_line(&mut self) {
+        let mut points = ::std::mem::replace(&mut self.current, vec![]);
+        let info = self.current_info.take();
+        if points.is_empty() {
+            return;
+        }
+
+        let ends_at_start = points.len() > 2 &&
+                            points[0].close_to(points.last().unwrap(), EPSILON);
+        let (style, class, closed) = match info {
+            Some(info) => (info.style, info.operation.name(), info.closed),
+            None => (StrokeStyle::hairline(), "line", ends_at_start),
+        };
+        let closed = closed || self.filling.is_some();
+        if closed && ends_at_start {
+            // The closing point is implied by the "Z"
+            points.pop();
+        }
+
+        if self.filling.is_some() {
+            append_subpath(&mut self.fill_buffer, &points, true);
+        } else {
+            append_subpath(self.group_for(style, class, closed), &points, closed);
+        }
+    }
+
+    fn start_path(&mut self, info: &PathInfo) {
+        self.start_line();
+        self.current_info = Some(*info);
+    }
+
+    fn start_fill(&mut self, rule: FillRule, info: &PathInfo) {
+        self.fill_buffer.clear();
+        self.filling = Some((rule, info.style));
+    }
+
+    fn end_fill(&mut self) {
+        let (rule, style) = match self.filling.take() {
+            Some((FillRule::EvenOdd, style)) => ("evenodd", style),
+            Some((FillRule::NonZero, style)) => ("nonzero", style),
+            None => return,
+        };
+        if !self.fill_buffer.is_empty() {
+            self.fills.push(format!("<path fill=\"{}\" stroke=\"none\" fill-rule=\"{}\" d=\"{}\"/>\n",
+                                    color(&style), rule, self.fill_buffer.trim()));
+        }
+        self.fill_buffer.clear();
+    }
+
+    fn set_size(&mut self, w: f32, h: f32) {
+        self.size = (w * self.conversion, h * self.conversion);
+    }
+}
+
+#[test]
+fn closed_and_open_contours_are_separate_paths() {
+    use ::Operation;
+
+    let mut svg = SvgWriter::new("mm", 2.0);
+    svg.set_size(10.0, 5.0);
+
+    let info = PathInfo::new(Operation::Cut, true, 0);
+    svg.start_path(&info);
+    for &(x, y) in &[(0.0, 0.0), (4.0, 0.0), (4.0, 3.0), (0.0, 0.0)] {
+        svg.add_point(Point { x: x, y: y });
+    }
+    svg.end_path(&info);
+
+    let info = PathInfo::new(Operation::Score, false, 1);
+    svg.start_path(&info);
+    svg.add_point(Point { x: 1.0, y: 1.0 });
+    svg.add_point(Point { x: 2.5, y: 1.0 });
+    svg.end_path(&info);
+
+    let mut buffer = vec![];
+    svg.write_to(&mut buffer).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+
+    assert!(text.contains("width=\"20mm\" height=\"10mm\" viewBox=\"0 0 20 10\""));
+
+    let paths: Vec<&str> = text.lines().filter(|l| l.starts_with("<path")).collect();
+    assert_eq!(paths.len(), 2);
+    let data = |line: &str| line.split(" d=\"").nth(1).unwrap().trim_right_matches("\"/>").to_string();
+
+    let closed = paths.iter().find(|l| l.contains("class=\"cut closed\"")).unwrap();
+    assert_eq!(data(closed), "M0 0 L8 0 L8 6 Z");
+
+    let open = paths.iter().find(|l| l.contains("class=\"score open\"")).unwrap();
+    assert_eq!(data(open), "M2 2 L5 2");
+}
@@ -0,0 +1,979 @@
+use std::io::{self, Read};
+use std::fs::File;
+use std::path::Path;
+use std::error::Error;
+use std::fmt;
+use std::f32::consts::PI;
+
+use ::{OrThese, PolyGroup, Circle, Rectangle};
+use ::util::geom::{Point, Rect, Polygon, Matrix};
+use super::import::{self, ImportedShape};
+
+/// Everything that can go wrong while reading an SVG document.
+#[derive(Debug)]
+pub enum SvgError {
+    Io(io::Error),
+    /// The document isn't well formed enough to be read.
+    Syntax { line: usize, message: String },
+    /// An element that draws something without an outline that could be
+    /// imported, like `<text>` or `<image>`.
+    UnsupportedElement { name: String, line: usize },
+    /// A required attribute is missing or couldn't be parsed.
+    InvalidAttribute { element: String, attribute: String, line: usize },
+    /// The `d` attribute of a `<path>` couldn't be parsed.
+    InvalidPath { line: usize, message: String },
+    /// The document doesn't contain any shapes.
+    Empty,
+}
+
+impl fmt::Display for SvgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SvgError::Io(ref e) => write!(f, "{}", e),
+            SvgError::Syntax { line, ref message } =>
+                write!(f, "line {}: {}", line, message),
+            SvgError::UnsupportedElement { ref name, line } =>
+                write!(f, "line {}: <{}> elements can't be imported", line, name),
+            SvgError::InvalidAttribute { ref element, ref attribute, line } =>
+                write!(f, "line {}: invalid or missing \"{}\" on <{}>", line, attribute, element),
+            SvgError::InvalidPath { line, ref message } =>
+                write!(f, "line {}: invalid path data: {}", line, message),
+            SvgError::Empty => write!(f, "the document doesn't contain any shapes"),
+        }
+    }
+}
+
+impl Error for SvgError {
+    fn description(&self) -> &str {
+        match *self {
+            SvgError::Io(ref e) => e.description(),
+            SvgError::Syntax { .. } => "malformed svg document",
+            SvgError::UnsupportedElement { .. } => "unsupported svg element",
+            SvgError::InvalidAttribute { .. } => "invalid svg attribute",
+            SvgError::InvalidPath { .. } => "invalid svg path data",
+            SvgError::Empty => "svg document without shapes",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            SvgError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SvgError {
+    fn from(e: io::Error) -> SvgError {
+        SvgError::Io(e)
+    }
+}
+
+/// Reads the shapes out of the SVG file at `path`.
+///
+/// See `read_svg`.
+pub fn read_svg_file<P: AsRef<Path>>(path: P, tolerance: f32) -> Result<OrThese<ImportedShape>, SvgError> {
+    let mut source = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut source));
+    read_svg(&source, tolerance)
+}
+
+/// Reads every `<path>`, `<polygon>`, `<polyline>`, `<rect>`, `<circle>`
+/// and `<ellipse>` out of an SVG document, in user units.
+///
+/// Curves are flattened so that they stay within `tolerance` of the real
+/// outline.  Circles and rectangles that survive their transforms intact
+/// are kept as exact primitives.  Paths are filled with the even-odd rule
+/// and fill and stroke styles are ignored.  Definitions, metadata and
+/// elements from other namespaces are skipped, but anything else that
+/// draws (text, images, `<use>`, lone lines) is reported as an error.
+pub fn read_svg(source: &str, tolerance: f32) -> Result<OrThese<ImportedShape>, SvgError> {
+    let tolerance = tolerance.max(0.0001);
+    let mut tokenizer = Tokenizer::new(source);
+    // The transform of every open element, with the document's on the bottom.
+    let mut transforms = vec![Matrix::new()];
+    // How deep we are inside of an element whose contents are ignored.
+    let mut skipping = 0;
+    let mut shapes = vec![];
+
+    while let Some(tag) = try!(tokenizer.next_tag()) {
+        if skipping > 0 {
+            match tag.kind {
+                TagKind::Open => skipping += 1,
+                TagKind::Close => skipping -= 1,
+                TagKind::Empty => {}
+            }
+            continue;
+        }
+
+        if tag.kind == TagKind::Close {
+            if transforms.len() <= 1 {
+                return Err(SvgError::Syntax {
+                    line: tag.line,
+                    message: format!("unexpected </{}>", tag.name),
+                });
+            }
+            transforms.pop();
+            continue;
+        }
+
+        let name = match local_name(&tag.name) {
+            Some(name) => name,
+            None => {
+                if tag.kind == TagKind::Open {
+                    skipping = 1;
+                }
+                continue;
+            }
+        };
+
+        if IGNORED.iter().any(|&ignored| ignored == name) {
+            if tag.kind == TagKind::Open {
+                skipping = 1;
+            }
+            continue;
+        }
+
+        let parent = *transforms.last().unwrap();
+        let matrix = match tag.attribute("transform") {
+            Some(t) => try!(parse_transform(parent, t).ok_or_else(|| tag.invalid("transform"))),
+            None => parent,
+        };
+
+        match name {
+            "svg" | "g" | "a" | "switch" => {}
+            "path" | "polygon" | "polyline" | "rect" | "circle" | "ellipse" => {
+                if let Some(shape) = try!(read_shape(name, &tag, &matrix, tolerance)) {
+                    shapes.push(shape);
+                }
+            }
+            _ => return Err(SvgError::UnsupportedElement {
+                name: name.to_string(),
+                line: tag.line,
+            }),
+        }
+
+        if tag.kind == TagKind::Open {
+            transforms.push(matrix);
+        }
+    }
+
+    if shapes.is_empty() {
+        Err(SvgError::Empty)
+    } else {
+        Ok(OrThese::new(shapes))
+    }
+}
+
+/// Elements that never draw anything directly.
+const IGNORED: &'static [&'static str] = &[
+    "defs", "title", "desc", "metadata", "style", "script", "clipPath", "mask",
+    "pattern", "marker", "symbol", "linearGradient", "radialGradient", "filter",
+];
+
+/// The name of an element in the SVG namespace, or None for elements
+/// from other namespaces (usually editor metadata).
+fn local_name(name: &str) -> Option<&str> {
+    if name.starts_with("svg:") {
+        Some(&name[4..])
+    } else if name.contains(':') {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn read_shape(name: &str, tag: &Tag, matrix: &Matrix, tolerance: f32) -> Result<Option<ImportedShape>, SvgError> {
+    let mut outline = Outline::new(*matrix, tolerance);
+
+    match name {
+        "path" => {
+            let data = try!(tag.attribute("d").ok_or_else(|| tag.invalid("d")));
+            if let Err(message) = parse_path(data, &mut outline) {
+                return Err(SvgError::InvalidPath { line: tag.line, message: message });
+            }
+        }
+        "polygon" | "polyline" => {
+            let points = try!(tag.attribute("points").ok_or_else(|| tag.invalid("points")));
+            let numbers = try!(parse_numbers(points).ok_or_else(|| tag.invalid("points")));
+            for (i, pair) in numbers.chunks(2).enumerate() {
+                if pair.len() != 2 {
+                    return Err(tag.invalid("points"));
+                }
+                let p = Point { x: pair[0], y: pair[1] };
+                if i == 0 { outline.move_to(p) } else { outline.line_to(p) }
+            }
+        }
+        "rect" => {
+            let x = try!(tag.length("x", 0.0));
+            let y = try!(tag.length("y", 0.0));
+            let w = try!(tag.required_length("width"));
+            let h = try!(tag.required_length("height"));
+            if w <= 0.0 || h <= 0.0 {
+                return Ok(None);
+            }
+
+            let (rx, ry) = match (tag.attribute("rx"), tag.attribute("ry")) {
+                (None, None) => (0.0, 0.0),
+                (Some(_), None) => { let rx = try!(tag.length("rx", 0.0)); (rx, rx) }
+                (None, Some(_)) => { let ry = try!(tag.length("ry", 0.0)); (ry, ry) }
+                (Some(_), Some(_)) => (try!(tag.length("rx", 0.0)), try!(tag.length("ry", 0.0))),
+            };
+            let rx = rx.max(0.0).min(w / 2.0);
+            let ry = ry.max(0.0).min(h / 2.0);
+
+            if (rx == 0.0 || ry == 0.0) && import::is_axis_aligned(matrix) {
+                let a = matrix.transform_point(&Point { x: x, y: y });
+                let b = matrix.transform_point(&Point { x: x + w, y: y + h });
+                return Ok(Some(ImportedShape::Rectangle(Rectangle::new(Rect::from_points(&a, &b)))));
+            }
+            outline.rounded_rect(x, y, w, h, rx, ry);
+        }
+        "circle" => {
+            let center = Point { x: try!(tag.length("cx", 0.0)), y: try!(tag.length("cy", 0.0)) };
+            let r = try!(tag.required_length("r"));
+            if r <= 0.0 {
+                return Ok(None);
+            }
+            if let Some(scale) = import::similarity_scale(matrix) {
+                return Ok(Some(ImportedShape::Circle(Circle {
+                    center: matrix.transform_point(&center),
+                    radius: r * scale,
+                })));
+            }
+            outline.ellipse(center, r, r);
+        }
+        "ellipse" => {
+            let center = Point { x: try!(tag.length("cx", 0.0)), y: try!(tag.length("cy", 0.0)) };
+            let rx = try!(tag.required_length("rx"));
+            let ry = try!(tag.required_length("ry"));
+            if rx <= 0.0 || ry <= 0.0 {
+                return Ok(None);
+            }
+            if let (true, Some(scale)) = (rx == ry, import::similarity_scale(matrix)) {
+                return Ok(Some(ImportedShape::Circle(Circle {
+                    center: matrix.transform_point(&center),
+                    radius: rx * scale,
+                })));
+            }
+            outline.ellipse(center, rx, ry);
+        }
+        _ => unreachable!(),
+    }
+
+    let polys: Vec<_> = outline.finish().into_iter()
+                               .map(|points| Polygon::new(points.into_iter()))
+                               .collect();
+    if polys.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(ImportedShape::Polygons(PolyGroup { polys: polys })))
+    }
+}
+
+/// Collects the flattened and transformed subpaths of a single element.
+struct Outline {
+    matrix: Matrix,
+    tolerance: f32,
+    subpaths: Vec<Vec<Point>>,
+    current: Vec<Point>,
+}
+
+impl Outline {
+    fn new(matrix: Matrix, tolerance: f32) -> Outline {
+        Outline {
+            matrix: matrix,
+            tolerance: tolerance,
+            subpaths: vec![],
+            current: vec![],
+        }
+    }
+
+    fn move_to(&mut self, p: Point) {
+        self.close();
+        self.current.push(self.matrix.transform_point(&p));
+    }
+
+    fn line_to(&mut self, p: Point) {
+        let p = self.matrix.transform_point(&p);
+        self.current.push(p);
+    }
+
+    // Beziers are affine invariant, so it's enough to transform the
+    // control points and flatten in the output space.
+    fn quad_to(&mut self, p0: Point, p1: Point, p2: Point) {
+        let m = self.matrix;
+        let points = import::flatten_quad(m.transform_point(&p0), m.transform_point(&p1),
+                                          m.transform_point(&p2), self.tolerance);
+        self.current.extend(points);
+    }
+
+    fn cubic_to(&mut self, p0: Point, p1: Point, p2: Point, p3: Point) {
+        let m = self.matrix;
+        let points = import::flatten_cubic(m.transform_point(&p0), m.transform_point(&p1),
+                                           m.transform_point(&p2), m.transform_point(&p3),
+                                           self.tolerance);
+        self.current.extend(points);
+    }
+
+    /// Adds an elliptical arc from the angle `start` to `start + sweep`,
+    /// not including its first point.
+    fn ellipse_arc(&mut self, center: Point, rx: f32, ry: f32, phi: f32, start: f32, sweep: f32) {
+        let radius = rx.max(ry) * import::matrix_scale(&self.matrix);
+        let n = import::arc_segments(radius, sweep, self.tolerance);
+        for i in 1 .. n + 1 {
+            let p = ellipse_point(center, rx, ry, phi, start + sweep * (i as f32 / n as f32));
+            self.line_to(p);
+        }
+    }
+
+    /// Adds an SVG style arc, given by its endpoints.
+    fn arc_to(&mut self, from: Point, rx: f32, ry: f32, phi: f32, large: bool, sweep: bool, to: Point) {
+        if from == to {
+            return;
+        }
+        let (mut rx, mut ry) = (rx.abs(), ry.abs());
+        if rx == 0.0 || ry == 0.0 {
+            return self.line_to(to);
+        }
+
+        // Conversion from endpoint to center parameterization, as
+        // described in the implementation notes of the SVG spec.
+        let (c, s) = (phi.cos(), phi.sin());
+        let (dx, dy) = ((from.x - to.x) / 2.0, (from.y - to.y) / 2.0);
+        let x1 = c * dx + s * dy;
+        let y1 = -s * dx + c * dy;
+
+        let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if lambda > 1.0 {
+            rx *= lambda.sqrt();
+            ry *= lambda.sqrt();
+        }
+
+        let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+        let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+        let sign = if large == sweep { -1.0 } else { 1.0 };
+        let coef = sign * (num / den).max(0.0).sqrt();
+        let cx1 = coef * rx * y1 / ry;
+        let cy1 = -coef * ry * x1 / rx;
+        let center = Point {
+            x: c * cx1 - s * cy1 + (from.x + to.x) / 2.0,
+            y: s * cx1 + c * cy1 + (from.y + to.y) / 2.0,
+        };
+
+        let (ux, uy) = ((x1 - cx1) / rx, (y1 - cy1) / ry);
+        let (vx, vy) = ((-x1 - cx1) / rx, (-y1 - cy1) / ry);
+        let start = uy.atan2(ux);
+        let mut delta = (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+        if !sweep && delta > 0.0 {
+            delta -= 2.0 * PI;
+        } else if sweep && delta < 0.0 {
+            delta += 2.0 * PI;
+        }
+
+        self.ellipse_arc(center, rx, ry, phi, start, delta);
+        // Land exactly on the endpoint so that following segments line up.
+        if let Some(last) = self.current.last_mut() {
+            *last = self.matrix.transform_point(&to);
+        }
+    }
+
+    fn ellipse(&mut self, center: Point, rx: f32, ry: f32) {
+        self.move_to(ellipse_point(center, rx, ry, 0.0, 0.0));
+        self.ellipse_arc(center, rx, ry, 0.0, 0.0, 2.0 * PI);
+        self.close();
+    }
+
+    fn rounded_rect(&mut self, x: f32, y: f32, w: f32, h: f32, rx: f32, ry: f32) {
+        let corners = [
+            (Point { x: x + w - rx, y: y + ry }, -PI / 2.0),
+            (Point { x: x + w - rx, y: y + h - ry }, 0.0),
+            (Point { x: x + rx, y: y + h - ry }, PI / 2.0),
+            (Point { x: x + rx, y: y + ry }, PI),
+        ];
+        for (i, &(center, start)) in corners.iter().enumerate() {
+            let first = ellipse_point(center, rx, ry, 0.0, start);
+            if i == 0 { self.move_to(first) } else { self.line_to(first) }
+            if rx > 0.0 && ry > 0.0 {
+                self.ellipse_arc(center, rx, ry, 0.0, start, PI / 2.0);
+            }
+        }
+        self.close();
+    }
+
+    fn close(&mut self) {
+        let mut points = ::std::mem::replace(&mut self.current, vec![]);
+        if points.len() > 1 && points[0].close_to(points.last().unwrap(), 0.00001) {
+            points.pop();
+        }
+        if points.len() >= 3 {
+            self.subpaths.push(points);
+        }
+    }
+
+    fn finish(mut self) -> Vec<Vec<Point>> {
+        self.close();
+        self.subpaths
+    }
+}
+
+fn ellipse_point(center: Point, rx: f32, ry: f32, phi: f32, t: f32) -> Point {
+    let (c, s) = (phi.cos(), phi.sin());
+    let (x, y) = (rx * t.cos(), ry * t.sin());
+    Point {
+        x: center.x + c * x - s * y,
+        y: center.y + s * x + c * y,
+    }
+}
+
+/// Feeds the commands in a path's `d` attribute into `outline`.
+fn parse_path(data: &str, outline: &mut Outline) -> Result<(), String> {
+    let mut scanner = Scanner::new(data);
+    let origin = Point { x: 0.0, y: 0.0 };
+    let mut current = origin;
+    let mut start = origin;
+    // The second control point of the previous curve, for S and T.
+    let mut last_cubic: Option<Point> = None;
+    let mut last_quad: Option<Point> = None;
+    let mut previous: Option<u8> = None;
+
+    loop {
+        scanner.skip_separators();
+        if scanner.at_end() {
+            return Ok(());
+        }
+
+        let command = match scanner.command() {
+            Some(c) => c,
+            // Coordinates without a command repeat the previous one, and
+            // extra pairs after a moveto are linetos.
+            None => match previous {
+                Some(b'M') => b'L',
+                Some(b'm') => b'l',
+                Some(b'Z') | Some(b'z') | None =>
+                    return Err(format!("expected a command at offset {}", scanner.pos)),
+                Some(c) => c,
+            },
+        };
+        if previous.is_none() && command != b'M' && command != b'm' {
+            return Err("path data must start with a moveto".to_string());
+        }
+        previous = Some(command);
+
+        let relative = command >= b'a' && command <= b'z';
+        let base = if relative { current } else { origin };
+        let mut cubic = None;
+        let mut quad = None;
+
+        let upper = if relative { command - (b'a' - b'A') } else { command };
+        match upper {
+            b'M' => {
+                current = try!(scanner.point(base));
+                start = current;
+                outline.move_to(current);
+            }
+            b'L' => {
+                current = try!(scanner.point(base));
+                outline.line_to(current);
+            }
+            b'H' => {
+                current.x = try!(scanner.number()) + base.x;
+                outline.line_to(current);
+            }
+            b'V' => {
+                current.y = try!(scanner.number()) + base.y;
+                outline.line_to(current);
+            }
+            b'C' => {
+                let c1 = try!(scanner.point(base));
+                let c2 = try!(scanner.point(base));
+                let end = try!(scanner.point(base));
+                outline.cubic_to(current, c1, c2, end);
+                cubic = Some(c2);
+                current = end;
+            }
+            b'S' => {
+                let c1 = reflect(last_cubic, current);
+                let c2 = try!(scanner.point(base));
+                let end = try!(scanner.point(base));
+                outline.cubic_to(current, c1, c2, end);
+                cubic = Some(c2);
+                current = end;
+            }
+            b'Q' => {
+                let c = try!(scanner.point(base));
+                let end = try!(scanner.point(base));
+                outline.quad_to(current, c, end);
+                quad = Some(c);
+                current = end;
+            }
+            b'T' => {
+                let c = reflect(last_quad, current);
+                let end = try!(scanner.point(base));
+                outline.quad_to(current, c, end);
+                quad = Some(c);
+                current = end;
+            }
+            b'A' => {
+                let rx = try!(scanner.number());
+                let ry = try!(scanner.number());
+                let phi = try!(scanner.number()).to_radians();
+                let large = try!(scanner.flag());
+                let sweep = try!(scanner.flag());
+                let end = try!(scanner.point(base));
+                outline.arc_to(current, rx, ry, phi, large, sweep, end);
+                current = end;
+            }
+            b'Z' => {
+                outline.close();
+                current = start;
+                // Drawing on without a moveto starts a new subpath from here.
+                outline.move_to(current);
+            }
+            _ => return Err(format!("unknown command '{}' at offset {}", command as char, scanner.pos - 1)),
+        }
+
+        last_cubic = cubic;
+        last_quad = quad;
+    }
+}
+
+/// The reflection of a curve's last control point about `current`, or
+/// `current` itself if the previous segment wasn't the same kind of curve.
+fn reflect(control: Option<Point>, current: Point) -> Point {
+    match control {
+        Some(c) => Point { x: 2.0 * current.x - c.x, y: 2.0 * current.y - c.y },
+        None => current,
+    }
+}
+
+/// Applies an SVG transform list to `matrix`.
+fn parse_transform(mut matrix: Matrix, text: &str) -> Option<Matrix> {
+    let mut rest = text.trim_left_matches(|c: char| c.is_whitespace() || c == ',');
+    while !rest.is_empty() {
+        let open = match rest.find('(') { Some(i) => i, None => return None };
+        let close = match rest.find(')') { Some(i) if i > open => i, _ => return None };
+        let name = rest[..open].trim();
+        let args = match parse_numbers(&rest[open + 1 .. close]) { Some(a) => a, None => return None };
+
+        matrix = match (name, args.len()) {
+            ("matrix", 6) => matrix.apply_matrix([[args[0], args[1], 0.0],
+                                                  [args[2], args[3], 0.0],
+                                                  [args[4], args[5], 1.0]]),
+            ("translate", 1) => matrix.translate(args[0], 0.0),
+            ("translate", 2) => matrix.translate(args[0], args[1]),
+            ("scale", 1) => matrix.scale(args[0], args[0]),
+            ("scale", 2) => matrix.scale(args[0], args[1]),
+            ("rotate", 1) => matrix.rotate(args[0].to_radians()),
+            ("rotate", 3) => matrix.translate(args[1], args[2])
+                                   .rotate(args[0].to_radians())
+                                   .translate(-args[1], -args[2]),
+            ("skewX", 1) => matrix.shear(args[0].to_radians().tan(), 0.0),
+            ("skewY", 1) => matrix.shear(0.0, args[0].to_radians().tan()),
+            _ => return None,
+        };
+
+        rest = rest[close + 1 ..].trim_left_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+    Some(matrix)
+}
+
+/// Parses a whitespace or comma separated list of numbers.
+fn parse_numbers(text: &str) -> Option<Vec<f32>> {
+    let mut scanner = Scanner::new(text);
+    let mut numbers = vec![];
+    loop {
+        scanner.skip_separators();
+        if scanner.at_end() {
+            return Some(numbers);
+        }
+        match scanner.number() {
+            Ok(n) => numbers.push(n),
+            Err(_) => return None,
+        }
+    }
+}
+
+/// Parses a length, converting absolute units into user units.
+fn parse_length(text: &str) -> Option<f32> {
+    let text = text.trim();
+    let split = text.find(|c: char| c.is_alphabetic() && c != 'e' && c != 'E' || c == '%')
+                    .unwrap_or(text.len());
+    // Exponents are allowed in the number, but "em" and "ex" are units.
+    let split = if text[..split].ends_with('e') || text[..split].ends_with('E') { split - 1 } else { split };
+    let value: f32 = match text[..split].trim().parse() { Ok(v) => v, Err(_) => return None };
+    let scale = match text[split..].trim() {
+        "" | "px" => 1.0,
+        "in" => 96.0,
+        "mm" => 96.0 / 25.4,
+        "cm" => 96.0 / 2.54,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        _ => return None,
+    };
+    Some(value * scale)
+}
+
+/// Reads numbers, flags and commands out of path data.
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    source: &'a str,
+    pos: usize,
+}
+
+impl <'a> Scanner<'a> {
+    fn new(source: &'a str) -> Scanner<'a> {
+        Scanner { bytes: source.as_bytes(), source: source, pos: 0 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).cloned()
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                b' ' | b'\t' | b'\n' | b'\r' | b',' => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn skip_digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some(b'0' ... b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        match self.peek() {
+            Some(c) if (c >= b'a' && c <= b'z' || c >= b'A' && c <= b'Z') && c != b'e' && c != b'E' => {
+                self.pos += 1;
+                Some(c)
+            }
+            _ => None,
+        }
+    }
+
+    fn number(&mut self) -> Result<f32, String> {
+        self.skip_separators();
+        let start = self.pos;
+        if let Some(b'+') | Some(b'-') = self.peek() {
+            self.pos += 1;
+        }
+        let mut digits = self.skip_digits();
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            digits += self.skip_digits();
+        }
+        if digits == 0 {
+            self.pos = start;
+            return Err(format!("expected a number at offset {}", start));
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            let mark = self.pos;
+            self.pos += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if self.skip_digits() == 0 {
+                self.pos = mark;
+            }
+        }
+        self.source[start .. self.pos].parse()
+            .map_err(|_| format!("invalid number at offset {}", start))
+    }
+
+    fn point(&mut self, base: Point) -> Result<Point, String> {
+        let x = try!(self.number());
+        let y = try!(self.number());
+        Ok(Point { x: base.x + x, y: base.y + y })
+    }
+
+    // Flags can be written without separators, as in "a1 1 0 00 1 1".
+    fn flag(&mut self) -> Result<bool, String> {
+        self.skip_separators();
+        match self.peek() {
+            Some(b'0') => { self.pos += 1; Ok(false) }
+            Some(b'1') => { self.pos += 1; Ok(true) }
+            _ => Err(format!("expected a flag at offset {}", self.pos)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum TagKind {
+    Open,
+    Close,
+    /// A self-closing tag, like `<path/>`.
+    Empty,
+}
+
+struct Tag {
+    name: String,
+    attributes: Vec<(String, String)>,
+    kind: TagKind,
+    line: usize,
+}
+
+impl Tag {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter()
+            .find(|&&(ref n, _)| n == name)
+            .map(|&(_, ref v)| &v[..])
+    }
+
+    fn invalid(&self, attribute: &str) -> SvgError {
+        SvgError::InvalidAttribute {
+            element: self.name.clone(),
+            attribute: attribute.to_string(),
+            line: self.line,
+        }
+    }
+
+    fn length(&self, name: &str, default: f32) -> Result<f32, SvgError> {
+        match self.attribute(name) {
+            Some(v) => parse_length(v).ok_or_else(|| self.invalid(name)),
+            None => Ok(default),
+        }
+    }
+
+    fn required_length(&self, name: &str) -> Result<f32, SvgError> {
+        match self.attribute(name) {
+            Some(v) => parse_length(v).ok_or_else(|| self.invalid(name)),
+            None => Err(self.invalid(name)),
+        }
+    }
+}
+
+/// Splits a document into tags, skipping text, comments, processing
+/// instructions and doctype declarations.
+struct Tokenizer<'a> {
+    source: &'a str,
+    pos: usize,
+    // A position whose line is already known, so that finding the line of
+    // each tag only has to count the newlines since the previous one.
+    counted: (usize, usize),
+}
+
+impl <'a> Tokenizer<'a> {
+    fn new(source: &'a str) -> Tokenizer<'a> {
+        Tokenizer { source: source, pos: 0, counted: (0, 1) }
+    }
+
+    fn line_at(&self, pos: usize) -> usize {
+        let (from, line) = if pos >= self.counted.0 { self.counted } else { (0, 1) };
+        self.source.as_bytes()[from..pos].iter().filter(|&&b| b == b'\n').count() + line
+    }
+
+    fn error(&self, pos: usize, message: &str) -> SvgError {
+        SvgError::Syntax { line: self.line_at(pos), message: message.to_string() }
+    }
+
+    /// Moves past the next occurrence of `end`.
+    fn skip_past(&mut self, start: usize, end: &str) -> Result<(), SvgError> {
+        match self.source[start..].find(end) {
+            Some(i) => {
+                self.pos = start + i + end.len();
+                Ok(())
+            }
+            None => Err(self.error(start, &format!("missing \"{}\"", end))),
+        }
+    }
+
+    fn next_tag(&mut self) -> Result<Option<Tag>, SvgError> {
+        loop {
+            let start = match self.source[self.pos..].find('<') {
+                Some(i) => self.pos + i,
+                None => return Ok(None),
+            };
+            let source = self.source;
+            let rest = &source[start..];
+
+            if rest.starts_with("<!--") {
+                try!(self.skip_past(start, "-->"));
+            } else if rest.starts_with("<![CDATA[") {
+                try!(self.skip_past(start, "]]>"));
+            } else if rest.starts_with("<?") {
+                try!(self.skip_past(start, "?>"));
+            } else if rest.starts_with("<!") {
+                try!(self.skip_declaration(start));
+            } else {
+                return self.read_tag(start).map(Some);
+            }
+        }
+    }
+
+    // Doctypes can carry an internal subset in square brackets.
+    fn skip_declaration(&mut self, start: usize) -> Result<(), SvgError> {
+        let mut depth = 0;
+        for (i, &b) in self.source.as_bytes()[start..].iter().enumerate() {
+            match b {
+                b'[' => depth += 1,
+                b']' => depth -= 1,
+                b'>' if depth <= 0 => {
+                    self.pos = start + i + 1;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        Err(self.error(start, "unterminated declaration"))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.source.len() && (self.source.as_bytes()[self.pos] as char).is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn read_name(&mut self) -> &'a str {
+        let source = self.source;
+        let start = self.pos;
+        while let Some(&b) = source.as_bytes().get(self.pos) {
+            match b {
+                b' ' | b'\t' | b'\n' | b'\r' | b'/' | b'>' | b'=' => break,
+                _ => self.pos += 1,
+            }
+        }
+        &source[start .. self.pos]
+    }
+
+    fn read_tag(&mut self, start: usize) -> Result<Tag, SvgError> {
+        let line = self.line_at(start);
+        self.counted = (start, line);
+        self.pos = start + 1;
+        let closing = self.source[self.pos..].starts_with('/');
+        if closing {
+            self.pos += 1;
+        }
+
+        let name = self.read_name().to_string();
+        if name.is_empty() {
+            return Err(self.error(start, "expected an element name"));
+        }
+
+        let mut attributes = vec![];
+        loop {
+            self.skip_whitespace();
+            let source = self.source;
+            let rest = &source[self.pos..];
+            if rest.starts_with('>') {
+                self.pos += 1;
+                let kind = if closing { TagKind::Close } else { TagKind::Open };
+                return Ok(Tag { name: name, attributes: attributes, kind: kind, line: line });
+            } else if rest.starts_with("/>") && !closing {
+                self.pos += 2;
+                return Ok(Tag { name: name, attributes: attributes, kind: TagKind::Empty, line: line });
+            } else if rest.is_empty() {
+                return Err(self.error(start, &format!("unterminated <{}>", name)));
+            } else if closing {
+                return Err(self.error(self.pos, &format!("unexpected text in </{}>", name)));
+            }
+
+            let attribute = self.read_name().to_string();
+            if attribute.is_empty() {
+                return Err(self.error(self.pos, &format!("malformed attribute in <{}>", name)));
+            }
+            self.skip_whitespace();
+            if !self.source[self.pos..].starts_with('=') {
+                return Err(self.error(self.pos, &format!("expected '=' after \"{}\"", attribute)));
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+
+            let quote = match self.source.as_bytes().get(self.pos) {
+                Some(&b'"') => '"',
+                Some(&b'\'') => '\'',
+                _ => return Err(self.error(self.pos, &format!("expected a quoted value for \"{}\"", attribute))),
+            };
+            let value_start = self.pos + 1;
+            let value_end = match self.source[value_start..].find(quote) {
+                Some(i) => value_start + i,
+                None => return Err(self.error(self.pos, "unterminated attribute value")),
+            };
+            self.pos = value_end + 1;
+            attributes.push((attribute, decode_entities(&self.source[value_start .. value_end])));
+        }
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').and_then(|end| {
+            let entity = &rest[1 .. end];
+            let c = match entity {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ if entity.starts_with("#x") =>
+                    u32::from_str_radix(&entity[2..], 16).ok().and_then(::std::char::from_u32),
+                _ if entity.starts_with('#') =>
+                    entity[1..].parse().ok().and_then(::std::char::from_u32),
+                _ => None,
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1 ..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[test]
+fn paths_and_circles_are_imported() {
+    use ::Implicit;
+
+    let document = r#"<?xml version="1.0"?>
+<!-- A square with a hole, and a circle -->
+<svg xmlns="http://www.w3.org/2000/svg" width="100mm" height="100mm">
+  <defs><text>ignored</text></defs>
+  <g transform="translate(10, 10)">
+    <path d="M0,0 h40 v40 h-40 z m10,10 h20 v20 h-20 z"/>
+    <circle cx="70" cy="20" r="10" transform="scale(2) translate(-35 -10)"/>
+  </g>
+</svg>"#;
+    let shape = read_svg(document, 0.01).unwrap();
+    assert_eq!(shape.targets.len(), 2);
+    assert!(shape.sample(Point { x: 15.0, y: 15.0 }) < 0.0);
+    assert!(shape.sample(Point { x: 30.0, y: 30.0 }) > 0.0);
+    match shape.targets[1] {
+        ImportedShape::Circle(c) => {
+            assert!(c.center.close_to(&Point { x: 80.0, y: 30.0 }, 0.001));
+            assert!((c.radius - 20.0).abs() < 0.001);
+        }
+        ref other => panic!("expected a circle, got {:?}", other),
+    }
+
+    match read_svg("<svg>\n<image href=\"logo.png\"/></svg>", 0.01) {
+        Err(SvgError::UnsupportedElement { ref name, line: 2 }) if name == "image" => {}
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+
+    // Lines are counted on from earlier tags, including inside a tag.
+    match read_svg("<svg>\n<g>\n<g></g>\n\n<path\n d=\"M0,0\"\n fill></g></svg>", 0.01) {
+        Err(SvgError::Syntax { line: 7, .. }) => {}
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,37 @@
+/// A physical unit that an output device can describe its coordinates in.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Unit {
+    Inches,
+    Feet,
+    Millimeters,
+    Centimeters,
+    Meters,
+    Points,
+}
+
+impl Unit {
+    /// Looks up a unit by its usual abbreviation ("in", "mm", "pt", ...).
+    pub fn from_name(name: &str) -> Option<Unit> {
+        match name {
+            "in" | "inch" | "inches" => Some(Unit::Inches),
+            "ft" | "foot" | "feet" => Some(Unit::Feet),
+            "mm" => Some(Unit::Millimeters),
+            "cm" => Some(Unit::Centimeters),
+            "m" => Some(Unit::Meters),
+            "pt" => Some(Unit::Points),
+            _ => None,
+        }
+    }
+
+    /// How many of this unit make up one inch.
+    pub fn per_inch(&self) -> f32 {
+        match *self {
+            Unit::Inches => 1.0,
+            Unit::Feet => 1.0 / 12.0,
+            Unit::Millimeters => 25.4,
+            Unit::Centimeters => 2.54,
+            Unit::Meters => 0.0254,
+            Unit::Points => 72.0,
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use super::*;
-use util::geom::{Line, Point, Rect, Polygon, Matrix, Vector, Ray};
+use util::geom::{Line, Point, Rect, Polygon, Matrix, Vector, Ray, Segment, CircularArc, QuadBezier, CubicBezier};
 use std::rc::Rc;
 
 #[derive(Clone, Debug)]
@@ -13,10 +13,174 @@ pub struct Circle {
     pub radius: f32
 }
 
+/// An ellipse with radii along its own x and y axes, rotated by
+/// `rotation` radians around its center.
+#[derive(Copy, Clone, Debug)]
+pub struct Ellipse {
+    pub center: Point,
+    pub radius_x: f32,
+    pub radius_y: f32,
+    pub rotation: f32,
+}
+
+/// A box of size `2 * half_size`, rotated by `rotation` radians around
+/// its center, with its corners rounded off.
+///
+/// The corner radii go clockwise from the top left: top left, top right,
+/// bottom right, bottom left.  Each one is capped at half of the box's
+/// shorter side.
+#[derive(Copy, Clone, Debug)]
+pub struct RoundedBox {
+    pub center: Point,
+    pub half_size: Vector,
+    pub radii: [f32; 4],
+    pub rotation: f32,
+}
+
+/// A line segment with a thickness of `2 * radius` and round ends.
+#[derive(Copy, Clone, Debug)]
+pub struct Capsule {
+    pub start: Point,
+    pub end: Point,
+    pub radius: f32,
+}
+
+/// An outline made of lines, circular arcs and bezier curves, which are
+/// sampled directly instead of being flattened into polygons.
+///
+/// Every subpath is treated as closed, and the inside is decided by the
+/// fill rule (nonzero unless set otherwise).  A path without any
+/// segments has no bounding box.
+#[derive(Clone, Debug)]
+pub struct Path {
+    subpaths: Vec<Subpath>,
+    fill_rule: FillRule,
+    bounds: Option<Rect>,
+}
+
+#[derive(Clone, Debug)]
+struct Subpath {
+    start: Point,
+    segments: Vec<Segment>,
+}
+
+/// A ring between two circles.
+#[derive(Copy, Clone, Debug)]
+pub struct Annulus {
+    pub center: Point,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+}
+
+/// How the ends of an `ArcBand` are finished.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ArcCap {
+    /// Half circles centered on the ends of the arc.
+    Round,
+    /// Cut off square along the radius at the arc's start and end angles.
+    Square,
+}
+
+/// An arc drawn with a thickness, centered on `arc`.
+#[derive(Copy, Clone, Debug)]
+pub struct ArcBand {
+    pub arc: CircularArc,
+    pub thickness: f32,
+    pub cap: ArcCap,
+}
+
+/// A slice of a circle between two angles.
+#[derive(Copy, Clone, Debug)]
+pub struct PieSector {
+    pub center: Point,
+    pub radius: f32,
+    pub start_angle: f32,
+    pub sweep: f32,
+}
+
+/// A polygon with `sides` equal sides, its corners on a circle of
+/// `radius`.  With a `rotation` of 0 the first corner points along +x.
+#[derive(Copy, Clone, Debug)]
+pub struct RegularPolygon {
+    pub center: Point,
+    pub sides: u32,
+    pub radius: f32,
+    pub rotation: f32,
+}
+
+/// A star with `points` tips on a circle of `outer_radius`, and the
+/// corners between them on a circle of `inner_radius`.  With a
+/// `rotation` of 0 the first tip points along +x.
+#[derive(Copy, Clone, Debug)]
+pub struct Star {
+    pub center: Point,
+    pub points: u32,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    pub rotation: f32,
+}
+
+/// A spur gear with involute teeth, centred on `center`.  With a
+/// `rotation` of 0 the first tooth points along +x.
+///
+/// The teeth are sized by `module` (the pitch diameter divided by the
+/// number of teeth), and reach `addendum` past the pitch circle and
+/// `dedendum` inside of it.  `backlash` thins each tooth by that much
+/// along the pitch circle, so that meshing gears have clearance.  Below
+/// the base circle the flanks go straight down to the root circle.
+#[derive(Copy, Clone, Debug)]
+pub struct InvoluteGear {
+    pub center: Point,
+    pub module: f32,
+    pub teeth: u32,
+    pub pressure_angle: f32,
+    pub addendum: f32,
+    pub dedendum: f32,
+    pub backlash: f32,
+    pub rotation: f32,
+}
+
+/// A stroke of `thickness` along the curve traced by `function` as its
+/// parameter goes from `start` to `end`.
+///
+/// The curve is split up into nearly straight pieces when it's built,
+/// and distances are found by refining the closest points on those
+/// pieces with Newton's method.
+#[derive(Clone)]
+pub struct ParametricCurve<F: Fn(f32) -> Point + Sync> {
+    function: F,
+    thickness: f32,
+    closed: bool,
+    pieces: Vec<CurvePiece>,
+    bounds: Rect,
+}
+
+/// A part of a `ParametricCurve` that strays no further than `error`
+/// from the line between its ends.
+#[derive(Copy, Clone, Debug)]
+struct CurvePiece {
+    start_t: f32,
+    end_t: f32,
+    chord: Line,
+    error: f32,
+}
+
+/// A field given by a closure, for shapes that don't have a primitive.
+///
+/// The field doesn't need to be a distance field, but the most that it
+/// can change over a unit of distance has to be declared with
+/// `with_lipschitz_constant` so that rendering doesn't skip over it.
+#[derive(Clone)]
+pub struct FnImplicit<F: Fn(Point) -> f32 + Sync> {
+    function: F,
+    bounds: Option<Rect>,
+    lipschitz: f32,
+}
+
 #[derive(Clone, Debug)]
 pub struct Rectangle {
     rect: Rect,
-    poly: Polygon
+    shape: RoundedBox,
 }
 
 #[derive(Copy, Clone)]
@@ -31,6 +195,39 @@ pub struct Or<A: Implicit, B: Implicit> {
     pub right: B,
 }
 
+/// How the edges of two shapes are joined where they meet, and how far
+/// the join reaches from the corner it replaces.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Blend {
+    /// A round fillet, made with a polynomial smooth minimum.
+    Round(f32),
+    /// A round fillet made with an exponential smooth minimum, which is
+    /// smoother but never quite stops bending the edges away from the
+    /// corner.
+    Exponential(f32),
+    /// A straight cut across the corner.
+    Chamfer(f32),
+    /// A corner filled with the given number of stairs.
+    Stairs(f32, u32),
+}
+
+/// The union of two shapes with the corners where they meet blended.
+#[derive(Copy, Clone)]
+pub struct BlendedOr<A: Implicit, B: Implicit> {
+    pub left: A,
+    pub right: B,
+    pub blend: Blend,
+}
+
+/// The intersection of two shapes with the corners where they meet
+/// blended.
+#[derive(Copy, Clone)]
+pub struct BlendedAnd<A: Implicit, B: Implicit> {
+    pub left: A,
+    pub right: B,
+    pub blend: Blend,
+}
+
 #[derive(Clone)]
 pub struct OrThese<A: Implicit> {
     pub targets: Vec<A>
@@ -158,6 +355,10 @@ impl <A: Implicit> Implicit for OrThese<A> {
     fn follows_rules(&self) -> bool {
         self.targets.iter().all(|a| a.follows_rules())
     }
+
+    fn lipschitz_constant(&self) -> f32 {
+        self.targets.iter().fold(1.0, |l, a| l.max(a.lipschitz_constant()))
+    }
 }
 
 impl <A: Implicit> Implicit for AndThese<A> {
@@ -191,6 +392,10 @@ impl <A: Implicit> Implicit for AndThese<A> {
     fn follows_rules(&self) -> bool {
         self.targets.iter().all(|a| a.follows_rules())
     }
+
+    fn lipschitz_constant(&self) -> f32 {
+        self.targets.iter().fold(1.0, |l, a| l.max(a.lipschitz_constant()))
+    }
 }
 
 impl <A: Implicit> BoxCache<A> {
@@ -203,6 +408,19 @@ impl <A: Implicit> BoxCache<A> {
     }
 }
 
+/// The most that the inverse of `matrix` can stretch a vector by, which
+/// is how much faster a transformed field can change than the original.
+fn inverse_stretch(matrix: &Matrix) -> f32 {
+    let origin = matrix.transform_point_inv(&Point { x: 0.0, y: 0.0 });
+    let a = matrix.transform_point_inv(&Point { x: 1.0, y: 0.0 }) - origin;
+    let b = matrix.transform_point_inv(&Point { x: 0.0, y: 1.0 }) - origin;
+    // The square root of the largest eigenvalue of the transpose times
+    // the matrix.
+    let (p, q, r) = (a.dot(&a), b.dot(&b), a.dot(&b));
+    let half_difference = (p - q) / 2.0;
+    ((p + q) / 2.0 + (half_difference * half_difference + r * r).sqrt()).sqrt()
+}
+
 impl <A: Implicit> Implicit for Transformation<A> {
     fn sample(&self, pos: Point) -> f32 {
         self.target.sample(self.matrix.transform_point_inv(&pos))
@@ -230,6 +448,10 @@ impl <A: Implicit> Implicit for Transformation<A> {
     fn follows_rules(&self) -> bool {
         self.target.follows_rules()
     }
+
+    fn lipschitz_constant(&self) -> f32 {
+        self.target.lipschitz_constant() * inverse_stretch(&self.matrix)
+    }
 }
 
 impl <A: Implicit> Implicit for Scale<A> {
@@ -256,6 +478,10 @@ impl <A: Implicit> Implicit for Scale<A> {
     fn follows_rules(&self) -> bool {
         self.target.follows_rules()
     }
+
+    fn lipschitz_constant(&self) -> f32 {
+        self.target.lipschitz_constant()
+    }
 }
 
 impl <'a> Implicit for &'a Implicit {
@@ -270,6 +496,10 @@ impl <'a> Implicit for &'a Implicit {
     fn follows_rules(&self) -> bool {
         (**self).follows_rules()
     }
+
+    fn lipschitz_constant(&self) -> f32 {
+        (**self).lipschitz_constant()
+    }
 }
 
 impl <'a, A> Implicit for &'a A where A: Implicit + Sized {
@@ -284,6 +514,10 @@ impl <'a, A> Implicit for &'a A where A: Implicit + Sized {
     fn follows_rules(&self) -> bool {
         (**self).follows_rules()
     }
+
+    fn lipschitz_constant(&self) -> f32 {
+        (**self).lipschitz_constant()
+    }
 }
 
 #[derive(Clone)]
@@ -309,6 +543,10 @@ impl Implicit for SyncBox {
     fn follows_rules(&self) -> bool {
         self.inner.follows_rules()
     }
+
+    fn lipschitz_constant(&self) -> f32 {
+        self.inner.lipschitz_constant()
+    }
 }
 
 unsafe impl Sync for SyncBox { }
@@ -338,6 +576,9 @@ impl <I: Implicit> Implicit for BoxCache<I> {
     fn follows_rules(&self) -> bool {
         self.target.follows_rules()
     }
+    fn lipschitz_constant(&self) -> f32 {
+        self.target.lipschitz_constant()
+    }
 }
 
 impl <I: Implicit> Implicit for Not<I> {
@@ -350,6 +591,9 @@ impl <I: Implicit> Implicit for Not<I> {
     fn follows_rules(&self) -> bool {
         self.target.follows_rules()
     }
+    fn lipschitz_constant(&self) -> f32 {
+        self.target.lipschitz_constant()
+    }
 }
 
 #[repr(simd)]
@@ -506,6 +750,110 @@ impl Implicit for Circle {
     fn follows_rules(&self) -> bool { true }
 }
 
+impl Ellipse {
+    pub fn new(center: Point, radius_x: f32, radius_y: f32) -> Ellipse {
+        Ellipse {
+            center: center,
+            radius_x: radius_x.abs(),
+            radius_y: radius_y.abs(),
+            rotation: 0.0,
+        }
+    }
+
+    pub fn with_rotation(self, rotation: f32) -> Ellipse {
+        Ellipse { rotation: rotation, .. self }
+    }
+}
+
+/// The distance from `(y0, y1)` to the ellipse with radii `e0 >= e1 > 0`,
+/// for a point in the first quadrant.
+///
+/// This is David Eberly's "Distance from a Point to an Ellipse", which
+/// bisects for the root of a function that is monotonic in the region of
+/// interest, so it can't diverge like Newton's method does near the axes.
+fn first_quadrant_ellipse_distance(e0: f64, e1: f64, y0: f64, y1: f64) -> f64 {
+    if y1 > 0.0 {
+        if y0 > 0.0 {
+            let z0 = y0 / e0;
+            let z1 = y1 / e1;
+            let g = z0 * z0 + z1 * z1 - 1.0;
+            if g == 0.0 {
+                return 0.0;
+            }
+            let r0 = (e0 / e1) * (e0 / e1);
+            let n0 = r0 * z0;
+            let mut s0 = z1 - 1.0;
+            let mut s1 = if g < 0.0 { 0.0 } else { (n0 * n0 + z1 * z1).sqrt() - 1.0 };
+            let mut s = 0.0;
+            for _ in 0 .. 160 {
+                s = (s0 + s1) / 2.0;
+                if s == s0 || s == s1 {
+                    break;
+                }
+                let ratio0 = n0 / (s + r0);
+                let ratio1 = z1 / (s + 1.0);
+                let g = ratio0 * ratio0 + ratio1 * ratio1 - 1.0;
+                if g > 0.0 {
+                    s0 = s;
+                } else if g < 0.0 {
+                    s1 = s;
+                } else {
+                    break;
+                }
+            }
+            let x0 = r0 * y0 / (s + r0);
+            let x1 = y1 / (s + 1.0);
+            ((x0 - y0) * (x0 - y0) + (x1 - y1) * (x1 - y1)).sqrt()
+        } else {
+            (y1 - e1).abs()
+        }
+    } else {
+        let numer0 = e0 * y0;
+        let denom0 = e0 * e0 - e1 * e1;
+        if numer0 < denom0 {
+            let xde0 = numer0 / denom0;
+            let x0 = e0 * xde0;
+            let x1 = e1 * (1.0 - xde0 * xde0).sqrt();
+            ((x0 - y0) * (x0 - y0) + x1 * x1).sqrt()
+        } else {
+            (y0 - e0).abs()
+        }
+    }
+}
+
+impl Implicit for Ellipse {
+    fn sample(&self, pos: Point) -> f32 {
+        // Move into the ellipse's own frame and fold into the first
+        // quadrant, with the longer radius along x.
+        let (c, s) = (self.rotation.cos(), self.rotation.sin());
+        let (dx, dy) = (pos.x - self.center.x, pos.y - self.center.y);
+        let x = (c * dx + s * dy).abs() as f64;
+        let y = (-s * dx + c * dy).abs() as f64;
+        let (a, b) = (self.radius_x as f64, self.radius_y as f64);
+        let (e0, e1, y0, y1) = if a >= b { (a, b, x, y) } else { (b, a, y, x) };
+
+        if e1 <= 0.0 {
+            // A flat ellipse is just a line segment.
+            let along = (y0 - e0).max(0.0);
+            return (along * along + y1 * y1).sqrt() as f32;
+        }
+
+        let distance = first_quadrant_ellipse_distance(e0, e1, y0, y1);
+        let inside = (y0 / e0) * (y0 / e0) + (y1 / e1) * (y1 / e1) < 1.0;
+        (if inside { -distance } else { distance }) as f32
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        let (c, s) = (self.rotation.cos(), self.rotation.sin());
+        let (a, b) = (self.radius_x, self.radius_y);
+        let half_width = (a * a * c * c + b * b * s * s).sqrt();
+        let half_height = (a * a * s * s + b * b * c * c).sqrt();
+        Some(Rect::null_at(&self.center).expand(half_width, half_height, half_width, half_height))
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
 impl <A: Implicit, B: Implicit> Implicit for And<A, B> {
     fn sample(&self, pos: Point) -> f32 {
         self.left.sample(pos).max(self.right.sample(pos))
@@ -527,6 +875,10 @@ impl <A: Implicit, B: Implicit> Implicit for And<A, B> {
         self.left.follows_rules() &&
         self.right.follows_rules()
     }
+
+    fn lipschitz_constant(&self) -> f32 {
+        self.left.lipschitz_constant().max(self.right.lipschitz_constant())
+    }
 }
 
 impl <A: Implicit, B: Implicit> Implicit for Or<A, B> {
@@ -553,6 +905,10 @@ impl <A: Implicit, B: Implicit> Implicit for Or<A, B> {
         self.left.follows_rules() &&
         self.right.follows_rules()
     }
+
+    fn lipschitz_constant(&self) -> f32 {
+        self.left.lipschitz_constant().max(self.right.lipschitz_constant())
+    }
 }
 
 impl <A: Implicit, B: Implicit> Implicit for Xor<A, B> {
@@ -597,6 +953,113 @@ impl <A: Implicit, B: Implicit> Implicit for Xor<A, B> {
         self.left.follows_rules() &&
         self.right.follows_rules()
     }
+
+    fn lipschitz_constant(&self) -> f32 {
+        self.left.lipschitz_constant().max(self.right.lipschitz_constant())
+    }
+}
+
+impl Blend {
+    /// How far from a corner the blend reaches.
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Blend::Round(r) | Blend::Exponential(r) | Blend::Chamfer(r) | Blend::Stairs(r, _) => r,
+        }
+    }
+
+    /// Joins two samples like `min`.
+    pub fn union(&self, a: f32, b: f32) -> f32 {
+        let closest = a.min(b);
+        match *self {
+            Blend::Round(r) => {
+                if r <= 0.0 {
+                    return closest;
+                }
+                let h = (r - (a - b).abs()).max(0.0) / r;
+                closest - h * h * r / 4.0
+            }
+            Blend::Exponential(r) => {
+                if r <= 0.0 || closest.is_infinite() {
+                    return closest;
+                }
+                // Scaled to dip as far as `Round` where the edges cross,
+                // and taken relative to the closest sample so that the
+                // powers can't overflow.
+                let k = r / 4.0;
+                closest - k * (((closest - a) / k).exp2() + ((closest - b) / k).exp2()).log2()
+            }
+            Blend::Chamfer(r) => closest.min((a + b - r) * ::std::f32::consts::FRAC_1_SQRT_2),
+            Blend::Stairs(r, steps) => {
+                let step = r / steps.max(1) as f32;
+                let u = b - r;
+                let x = u - a + step;
+                let wrapped = x - 2.0 * step * (x / (2.0 * step)).floor();
+                closest.min(0.5 * (u + a + (wrapped - step).abs()))
+            }
+        }
+    }
+
+    /// Joins two samples like `max`.
+    pub fn intersection(&self, a: f32, b: f32) -> f32 {
+        -self.union(-a, -b)
+    }
+
+    /// How much faster than its inputs a blended field can change.
+    fn stretch(&self) -> f32 {
+        match *self {
+            Blend::Chamfer(_) => ::std::f32::consts::SQRT_2,
+            _ => 1.0,
+        }
+    }
+}
+
+impl <A: Implicit, B: Implicit> Implicit for BlendedOr<A, B> {
+    fn sample(&self, pos: Point) -> f32 {
+        self.blend.union(self.left.sample(pos), self.right.sample(pos))
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        // Blending only adds material within the radius of both shapes.
+        let r = self.blend.radius().max(0.0);
+        match (self.left.bounding_box(), self.right.bounding_box()) {
+            (Some(left_bb), Some(right_bb)) => Some(left_bb.union_with(&right_bb).expand(r, r, r, r)),
+            (_, _) => None
+        }
+    }
+
+    fn follows_rules(&self) -> bool {
+        self.left.follows_rules() &&
+        self.right.follows_rules()
+    }
+
+    fn lipschitz_constant(&self) -> f32 {
+        self.left.lipschitz_constant().max(self.right.lipschitz_constant()) * self.blend.stretch()
+    }
+}
+
+impl <A: Implicit, B: Implicit> Implicit for BlendedAnd<A, B> {
+    fn sample(&self, pos: Point) -> f32 {
+        self.blend.intersection(self.left.sample(pos), self.right.sample(pos))
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        // Blending only takes material away, so this is the same as `And`.
+        match (self.left.bounding_box(), self.right.bounding_box()) {
+            (Some(left_bb), Some(right_bb)) => Some(left_bb.intersect_with(&right_bb)),
+            (Some(left_bb), None) => Some(left_bb),
+            (None, Some(right_bb)) => Some(right_bb),
+            (None, None) => None
+        }
+    }
+
+    fn follows_rules(&self) -> bool {
+        self.left.follows_rules() &&
+        self.right.follows_rules()
+    }
+
+    fn lipschitz_constant(&self) -> f32 {
+        self.left.lipschitz_constant().max(self.right.lipschitz_constant()) * self.blend.stretch()
+    }
 }
 
 impl <A: Implicit> Implicit for Boundary<A> {
@@ -611,41 +1074,1137 @@ impl <A: Implicit> Implicit for Boundary<A> {
     fn follows_rules(&self) -> bool {
         self.target.follows_rules()
     }
-}
 
-impl Rectangle {
-    fn recompute_poly(&mut self) {
-        let v = vec![self.rect.top_left(),
-                     self.rect.top_right(),
-                     self.rect.bottom_right(),
-                     self.rect.bottom_left()];
-        self.poly = Polygon::new(v.into_iter());
+    fn lipschitz_constant(&self) -> f32 {
+        self.target.lipschitz_constant()
     }
+}
 
+impl Rectangle {
     pub fn new(rect: Rect) -> Rectangle {
-        let mut r = Rectangle {
+        Rectangle {
             rect: rect,
-            poly: Polygon::new(vec![].into_iter())
-        };
-        r.recompute_poly();
-        r
+            shape: RoundedBox::new(rect),
+        }
     }
 
     pub fn with_underlying<R, F: FnOnce(&mut Rect) -> R>(&mut self, f: F) -> R {
         let r = f(&mut self.rect);
-        self.recompute_poly();
+        self.shape = RoundedBox::new(self.rect);
         r
     }
 }
 
 impl Implicit for Rectangle {
     fn sample(&self, pos: Point) -> f32 {
-        self.poly.sample(pos)
+        self.shape.sample(pos)
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        self.shape.bounding_box()
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+impl RoundedBox {
+    /// An axis aligned box with square corners covering `rect`.
+    pub fn new(rect: Rect) -> RoundedBox {
+        RoundedBox::centered(rect.midpoint(), rect.width(), rect.height())
+    }
+
+    pub fn centered(center: Point, width: f32, height: f32) -> RoundedBox {
+        RoundedBox {
+            center: center,
+            half_size: Vector { x: width.abs() / 2.0, y: height.abs() / 2.0 },
+            radii: [0.0; 4],
+            rotation: 0.0,
+        }
+    }
+
+    /// A line segment with a thickness, with square ends that stop at the
+    /// segment's endpoints.
+    pub fn from_segment(start: Point, end: Point, thickness: f32) -> RoundedBox {
+        let along = end - start;
+        RoundedBox {
+            center: start + along / 2.0,
+            half_size: Vector { x: along.magnitude() / 2.0, y: thickness.abs() / 2.0 },
+            radii: [0.0; 4],
+            rotation: along.y.atan2(along.x),
+        }
+    }
+
+    pub fn with_radius(self, radius: f32) -> RoundedBox {
+        self.with_corner_radii([radius; 4])
+    }
+
+    pub fn with_corner_radii(self, radii: [f32; 4]) -> RoundedBox {
+        RoundedBox { radii: radii, .. self }
+    }
+
+    pub fn with_rotation(self, rotation: f32) -> RoundedBox {
+        RoundedBox { rotation: rotation, .. self }
+    }
+
+    fn radius(&self, corner: usize) -> f32 {
+        self.radii[corner].max(0.0).min(self.half_size.x.min(self.half_size.y))
+    }
+
+    /// Converts a point in the box's frame back into the world.
+    fn to_world(&self, x: f32, y: f32) -> Point {
+        let (c, s) = (self.rotation.cos(), self.rotation.sin());
+        Point {
+            x: self.center.x + c * x - s * y,
+            y: self.center.y + s * x + c * y,
+        }
+    }
+}
+
+impl Implicit for RoundedBox {
+    fn sample(&self, pos: Point) -> f32 {
+        let (c, s) = (self.rotation.cos(), self.rotation.sin());
+        let (dx, dy) = (pos.x - self.center.x, pos.y - self.center.y);
+        let x = c * dx + s * dy;
+        let y = -s * dx + c * dy;
+
+        // y points down, so negative y is the top.
+        let corner = match (x >= 0.0, y >= 0.0) {
+            (false, false) => 0,
+            (true, false) => 1,
+            (true, true) => 2,
+            (false, true) => 3,
+        };
+        let r = self.radius(corner);
+
+        // Distance to a box shrunk by the corner radius, grown back out.
+        let qx = x.abs() - self.half_size.x + r;
+        let qy = y.abs() - self.half_size.y + r;
+        let outside = Vector { x: qx.max(0.0), y: qy.max(0.0) }.magnitude();
+        let inside = qx.max(qy).min(0.0);
+        outside + inside - r
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        // Each rounded corner is a circle around a point inset by its radius.
+        let signs = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+        let mut bb = Rect::null();
+        for (corner, &(sx, sy)) in signs.iter().enumerate() {
+            let r = self.radius(corner);
+            let p = self.to_world(sx * (self.half_size.x - r), sy * (self.half_size.y - r));
+            let circle = Rect::centered_with_radius(&p, r);
+            bb = if bb.is_null() { circle } else { bb.union_with(&circle) };
+        }
+        Some(bb)
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+impl Implicit for Capsule {
+    fn sample(&self, pos: Point) -> f32 {
+        Line(self.start, self.end).dist_to_point(pos) - self.radius
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        let r = self.radius;
+        Some(Rect::from_points(&self.start, &self.end).expand(r, r, r, r))
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+impl Path {
+    pub fn new() -> Path {
+        Path {
+            subpaths: vec![],
+            fill_rule: FillRule::NonZero,
+            bounds: None,
+        }
+    }
+
+    pub fn set_fill_rule(&mut self, rule: FillRule) -> &mut Path {
+        self.fill_rule = rule;
+        self
+    }
+
+    /// Starts a new subpath at `p`.
+    pub fn move_to(&mut self, p: Point) -> &mut Path {
+        self.subpaths.push(Subpath { start: p, segments: vec![] });
+        self
+    }
+
+    /// Where the next segment starts.  Drawing without a subpath starts
+    /// one at `fallback`.
+    fn current(&mut self, fallback: Point) -> Point {
+        match self.subpaths.last() {
+            Some(sub) => return sub.segments.last().map(|s| s.end()).unwrap_or(sub.start),
+            None => {}
+        }
+        self.move_to(fallback);
+        fallback
+    }
+
+    /// Adds a segment, starting a new subpath if it doesn't continue the
+    /// current one.
+    pub fn push(&mut self, segment: Segment) -> &mut Path {
+        let start = segment.start();
+        if !self.current(start).close_to(&start, 0.00001) {
+            self.move_to(start);
+        }
+        let bb = segment.bounding_box();
+        self.bounds = Some(match self.bounds {
+            Some(bounds) => bounds.union_with(&bb),
+            None => bb,
+        });
+        self.subpaths.last_mut().unwrap().segments.push(segment);
+        self
+    }
+
+    pub fn line_to(&mut self, p: Point) -> &mut Path {
+        let from = self.current(p);
+        self.push(Segment::Line(Line(from, p)))
+    }
+
+    pub fn quad_to(&mut self, control: Point, p: Point) -> &mut Path {
+        let from = self.current(control);
+        self.push(Segment::Quad(QuadBezier(from, control, p)))
+    }
+
+    pub fn cubic_to(&mut self, control_1: Point, control_2: Point, p: Point) -> &mut Path {
+        let from = self.current(control_1);
+        self.push(Segment::Cubic(CubicBezier(from, control_1, control_2, p)))
+    }
+
+    /// Continues around a circle centered at `center` by `sweep` radians.
+    pub fn arc_to(&mut self, center: Point, sweep: f32) -> &mut Path {
+        let from = self.current(center);
+        let offset = from - center;
+        self.push(Segment::Arc(CircularArc {
+            center: center,
+            radius: offset.magnitude(),
+            start_angle: offset.y.atan2(offset.x),
+            sweep: sweep,
+        }))
+    }
+
+    /// Draws a line back to the start of the current subpath.  Drawing on
+    /// afterwards starts a new subpath from the same point.
+    pub fn close(&mut self) -> &mut Path {
+        let start = match self.subpaths.last() {
+            Some(sub) => sub.start,
+            None => return self,
+        };
+        if !self.current(start).close_to(&start, 0.00001) {
+            self.line_to(start);
+        }
+        self.move_to(start)
+    }
+}
+
+/// A lower bound on the distance from `p` to anything inside of `rect`.
+fn distance_to_rect(rect: &Rect, p: Point) -> f32 {
+    let dx = (rect.left() - p.x).max(p.x - rect.right()).max(0.0);
+    let dy = (rect.top() - p.y).max(p.y - rect.bottom()).max(0.0);
+    (dx * dx + dy * dy).sqrt()
+}
+
+impl Implicit for Path {
+    fn sample(&self, pos: Point) -> f32 {
+        let mut distance = ::std::f32::INFINITY;
+        let mut winding = 0;
+        for sub in &self.subpaths {
+            let last = match sub.segments.last() {
+                Some(s) => s.end(),
+                None => continue,
+            };
+            let closing = if last.close_to(&sub.start, 0.00001) {
+                None
+            } else {
+                Some(Segment::Line(Line(last, sub.start)))
+            };
+
+            for segment in sub.segments.iter().chain(closing.iter()) {
+                winding += segment.winding(pos);
+                // Curves are expensive, so skip the ones that can't be closer.
+                if let Segment::Line(_) = *segment {
+                } else if distance_to_rect(&segment.bounding_box(), pos) >= distance {
+                    continue;
+                }
+                distance = distance.min(segment.dist_to_point(pos));
+            }
+        }
+
+        let inside = match self.fill_rule {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        };
+        if inside { -distance } else { distance }
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        self.bounds
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+impl Implicit for Annulus {
+    fn sample(&self, pos: Point) -> f32 {
+        let middle = (self.inner_radius + self.outer_radius) / 2.0;
+        let half_width = (self.outer_radius - self.inner_radius) / 2.0;
+        (pos.distance(&self.center) - middle).abs() - half_width
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        Some(Rect::centered_with_radius(&self.center, self.outer_radius))
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+impl ArcBand {
+    /// A band with round ends.
+    pub fn new(center: Point, radius: f32, start_angle: f32, sweep: f32, thickness: f32) -> ArcBand {
+        ArcBand {
+            arc: CircularArc {
+                center: center,
+                radius: radius,
+                start_angle: start_angle,
+                sweep: sweep,
+            },
+            thickness: thickness,
+            cap: ArcCap::Round,
+        }
+    }
+
+    pub fn with_cap(self, cap: ArcCap) -> ArcBand {
+        ArcBand { cap: cap, .. self }
+    }
+
+    /// The flat end of a square capped band at `angle`.
+    fn end_line(&self, angle: f32) -> Line {
+        let half_width = self.thickness / 2.0;
+        let (c, s) = (angle.cos(), angle.sin());
+        let at = |r: f32| Point { x: self.arc.center.x + r * c, y: self.arc.center.y + r * s };
+        Line(at(self.arc.radius - half_width), at(self.arc.radius + half_width))
+    }
+}
+
+/// The signed distance to a shape bounded by two radial lines and some
+/// arcs, given the unsigned distance to its arcs when `pos` is within
+/// the shape's angles.
+///
+/// Outside of the angles, the closest point is always on one of the
+/// lines.  Inside of them, the lines only matter for points that are
+/// already inside of the shape.
+fn radially_bounded(within_angles: bool, to_arcs: f32, ends: Option<(Line, Line)>, pos: Point) -> f32 {
+    let to_ends = match ends {
+        Some((a, b)) => a.dist_to_point(pos).min(b.dist_to_point(pos)),
+        None => ::std::f32::INFINITY,
+    };
+    if !within_angles {
+        to_ends
+    } else if to_arcs > 0.0 {
+        to_arcs
+    } else {
+        -(-to_arcs).min(to_ends)
+    }
+}
+
+impl Implicit for ArcBand {
+    fn sample(&self, pos: Point) -> f32 {
+        let half_width = self.thickness / 2.0;
+        match self.cap {
+            ArcCap::Round => self.arc.dist_to_point(pos) - half_width,
+            ArcCap::Square => {
+                let offset = pos - self.arc.center;
+                let within = self.arc.contains_angle(offset.y.atan2(offset.x));
+                let to_arcs = (offset.magnitude() - self.arc.radius).abs() - half_width;
+                let full_circle = self.arc.sweep.abs() >= 2.0 * ::std::f32::consts::PI;
+                let ends = if full_circle {
+                    None
+                } else {
+                    let end_angle = self.arc.start_angle + self.arc.sweep;
+                    Some((self.end_line(self.arc.start_angle), self.end_line(end_angle)))
+                };
+                radially_bounded(within, to_arcs, ends, pos)
+            }
+        }
     }
 
     fn bounding_box(&self) -> Option<Rect> {
-        self.poly.bounding_box()
+        let half_width = self.thickness / 2.0;
+        match self.cap {
+            ArcCap::Round => {
+                let w = half_width;
+                Some(self.arc.bounding_box().expand(w, w, w, w))
+            }
+            ArcCap::Square => {
+                let outer = CircularArc { radius: self.arc.radius + half_width, .. self.arc };
+                let inner = CircularArc { radius: (self.arc.radius - half_width).max(0.0), .. self.arc };
+                Some(outer.bounding_box().union_with(&inner.bounding_box()))
+            }
+        }
     }
 
     fn follows_rules(&self) -> bool { true }
 }
+
+impl Implicit for PieSector {
+    fn sample(&self, pos: Point) -> f32 {
+        let arc = CircularArc {
+            center: self.center,
+            radius: self.radius,
+            start_angle: self.start_angle,
+            sweep: self.sweep,
+        };
+        let offset = pos - self.center;
+        let within = arc.contains_angle(offset.y.atan2(offset.x));
+        let to_arc = offset.magnitude() - self.radius;
+        let ends = if self.sweep.abs() >= 2.0 * ::std::f32::consts::PI {
+            None
+        } else {
+            Some((Line(self.center, arc.start()), Line(self.center, arc.end())))
+        };
+        radially_bounded(within, to_arc, ends, pos)
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        let arc = CircularArc {
+            center: self.center,
+            radius: self.radius,
+            start_angle: self.start_angle,
+            sweep: self.sweep,
+        };
+        let mut bb = arc.bounding_box();
+        bb.expand_to_include(&self.center);
+        Some(bb)
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+/// The signed distance to a star shape with `n` tips at `outer` and
+/// corners between them at `inner`, for a point relative to its center
+/// with the first tip along +x.
+///
+/// The shape is symmetric under a dihedral group, so `pos` can be folded
+/// into the wedge between a tip and the next corner.  The closest point
+/// on the outline is then always on the edge between those two.
+fn folded_star_distance(pos: Vector, n: u32, outer: f32, inner: f32) -> f32 {
+    use std::f32::consts::PI;
+    let wedge = PI / n as f32;
+    let radius = pos.magnitude();
+    let mut angle = pos.y.atan2(pos.x) % (2.0 * wedge);
+    if angle < 0.0 {
+        angle += 2.0 * wedge;
+    }
+    if angle > wedge {
+        angle = 2.0 * wedge - angle;
+    }
+    let folded = Point { x: radius * angle.cos(), y: radius * angle.sin() };
+
+    let tip = Point { x: outer, y: 0.0 };
+    let corner = Point { x: inner * wedge.cos(), y: inner * wedge.sin() };
+    let distance = Line(tip, corner).dist_to_point(folded);
+    // The center is to the left of the edge going from tip to corner.
+    if (corner - tip).cross(&(folded - tip)) > 0.0 {
+        -distance
+    } else {
+        distance
+    }
+}
+
+/// The bounding box of the corners at `radius` and `angle + k * step`.
+fn corners_bounding_box(center: Point, radius: f32, angle: f32, step: f32, count: u32) -> Rect {
+    let mut bb = Rect::null_at(&Point {
+        x: center.x + radius * angle.cos(),
+        y: center.y + radius * angle.sin(),
+    });
+    for i in 1 .. count {
+        let a = angle + step * i as f32;
+        bb.expand_to_include(&Point { x: center.x + radius * a.cos(), y: center.y + radius * a.sin() });
+    }
+    bb
+}
+
+/// Rotates `pos - center` by `-rotation`.
+fn to_local(pos: Point, center: Point, rotation: f32) -> Vector {
+    let offset = pos - center;
+    let (c, s) = (rotation.cos(), rotation.sin());
+    Vector { x: c * offset.x + s * offset.y, y: c * offset.y - s * offset.x }
+}
+
+impl RegularPolygon {
+    pub fn new(center: Point, sides: u32, radius: f32) -> RegularPolygon {
+        assert!(sides >= 3, "a regular polygon needs at least 3 sides");
+        RegularPolygon { center: center, sides: sides, radius: radius, rotation: 0.0 }
+    }
+
+    pub fn with_rotation(self, rotation: f32) -> RegularPolygon {
+        RegularPolygon { rotation: rotation, .. self }
+    }
+
+    /// The distance from the center to the middle of each side.
+    pub fn inradius(&self) -> f32 {
+        self.radius * (::std::f32::consts::PI / self.sides as f32).cos()
+    }
+}
+
+impl Implicit for RegularPolygon {
+    fn sample(&self, pos: Point) -> f32 {
+        // A regular polygon is a star with its corners on the sides.
+        let local = to_local(pos, self.center, self.rotation);
+        folded_star_distance(local, self.sides, self.radius, self.inradius())
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        let step = 2.0 * ::std::f32::consts::PI / self.sides as f32;
+        Some(corners_bounding_box(self.center, self.radius, self.rotation, step, self.sides))
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+impl Star {
+    pub fn new(center: Point, points: u32, inner_radius: f32, outer_radius: f32) -> Star {
+        assert!(points >= 2, "a star needs at least 2 points");
+        Star {
+            center: center,
+            points: points,
+            inner_radius: inner_radius,
+            outer_radius: outer_radius,
+            rotation: 0.0,
+        }
+    }
+
+    pub fn with_rotation(self, rotation: f32) -> Star {
+        Star { rotation: rotation, .. self }
+    }
+}
+
+impl Implicit for Star {
+    fn sample(&self, pos: Point) -> f32 {
+        let local = to_local(pos, self.center, self.rotation);
+        folded_star_distance(local, self.points, self.outer_radius, self.inner_radius)
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        let step = 2.0 * ::std::f32::consts::PI / self.points as f32;
+        let tips = corners_bounding_box(self.center, self.outer_radius, self.rotation, step, self.points);
+        let corners = corners_bounding_box(self.center, self.inner_radius, self.rotation + step / 2.0, step, self.points);
+        Some(tips.union_with(&corners))
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+/// How far around the base circle of `base` radius an involute has
+/// turned by the time it reaches `radius`.
+fn involute_angle(base: f32, radius: f32) -> f32 {
+    let roll = (radius * radius / (base * base) - 1.0).max(0.0).sqrt();
+    roll - roll.atan()
+}
+
+fn polar(radius: f32, angle: f32) -> Point {
+    Point { x: radius * angle.cos(), y: radius * angle.sin() }
+}
+
+/// The distance from `pos` (at `radius` and `angle`) to the arc of
+/// `arc_radius` between angles `from` and `to`.
+fn arc_distance(pos: Point, radius: f32, angle: f32, arc_radius: f32, from: f32, to: f32) -> f32 {
+    if from > to {
+        ::std::f32::INFINITY
+    } else if angle >= from && angle <= to {
+        (radius - arc_radius).abs()
+    } else {
+        pos.distance(&polar(arc_radius, from)).min(pos.distance(&polar(arc_radius, to)))
+    }
+}
+
+impl InvoluteGear {
+    /// A gear with a 20 degree pressure angle, an addendum of one module,
+    /// a dedendum of 1.25 modules and no backlash.
+    pub fn new(center: Point, module: f32, teeth: u32) -> InvoluteGear {
+        assert!(teeth >= 3, "a gear needs at least 3 teeth");
+        assert!(module > 0.0, "a gear's module must be positive");
+        InvoluteGear {
+            center: center,
+            module: module,
+            teeth: teeth,
+            pressure_angle: 20.0f32.to_radians(),
+            addendum: module,
+            dedendum: 1.25 * module,
+            backlash: 0.0,
+            rotation: 0.0,
+        }
+    }
+
+    pub fn with_pressure_angle(self, pressure_angle: f32) -> InvoluteGear {
+        InvoluteGear { pressure_angle: pressure_angle, .. self }
+    }
+
+    pub fn with_addendum(self, addendum: f32) -> InvoluteGear {
+        InvoluteGear { addendum: addendum, .. self }
+    }
+
+    pub fn with_dedendum(self, dedendum: f32) -> InvoluteGear {
+        InvoluteGear { dedendum: dedendum, .. self }
+    }
+
+    pub fn with_backlash(self, backlash: f32) -> InvoluteGear {
+        InvoluteGear { backlash: backlash, .. self }
+    }
+
+    pub fn with_rotation(self, rotation: f32) -> InvoluteGear {
+        InvoluteGear { rotation: rotation, .. self }
+    }
+
+    /// The radius of the circle that rolls against a meshing gear's.
+    pub fn pitch_radius(&self) -> f32 {
+        self.module * self.teeth as f32 / 2.0
+    }
+
+    /// The radius of the circle that the flanks unwind from.
+    pub fn base_radius(&self) -> f32 {
+        self.pitch_radius() * self.pressure_angle.cos()
+    }
+
+    /// The radius of the tips of the teeth, unless they come to a point
+    /// before reaching it.
+    pub fn outer_radius(&self) -> f32 {
+        self.pitch_radius() + self.addendum
+    }
+
+    /// The radius of the bottoms of the gaps between the teeth.
+    pub fn root_radius(&self) -> f32 {
+        self.pitch_radius() - self.dedendum
+    }
+
+    /// The angle from the middle of a tooth to where its flank leaves the
+    /// base circle.
+    fn flank_start(&self) -> f32 {
+        let thickness = ::std::f32::consts::PI * self.module / 2.0 - self.backlash;
+        thickness / (2.0 * self.pitch_radius()) + involute_angle(self.base_radius(), self.pitch_radius())
+    }
+
+    /// The angle from the middle of a tooth to its flank at `radius`.
+    fn flank_angle(&self, radius: f32) -> f32 {
+        self.flank_start() - involute_angle(self.base_radius(), radius)
+    }
+
+    /// Where the teeth end: at the outer radius, or closer in where the
+    /// two flanks of a tooth meet.
+    fn tip_radius(&self) -> f32 {
+        let outer = self.outer_radius();
+        if self.flank_angle(outer) >= 0.0 {
+            return outer;
+        }
+        // Solve tan(a) - a = flank_start for the roll tan(a) by Newton's
+        // method, starting from the small angle approximation.
+        let start = self.flank_start();
+        let mut roll = (3.0 * start).cbrt();
+        for _ in 0 .. 8 {
+            roll -= (roll - roll.atan() - start) * (1.0 + roll * roll) / (roll * roll);
+        }
+        self.base_radius() * (1.0 + roll * roll).sqrt()
+    }
+
+    /// The distance from `pos` to the flank between `low` and `high`
+    /// radii, on the side of the tooth toward +y.
+    ///
+    /// The normals of an involute are the tangents to its base circle, so
+    /// the closest points are found by following the two tangents from
+    /// `pos` back to the base circle.
+    fn flank_distance(&self, pos: Point, low: f32, high: f32) -> f32 {
+        let base = self.base_radius();
+        let start = self.flank_start();
+        let mut distance = pos.distance(&polar(low, self.flank_angle(low)))
+            .min(pos.distance(&polar(high, self.flank_angle(high))));
+
+        let radius = (pos.x * pos.x + pos.y * pos.y).sqrt();
+        if radius <= base {
+            return distance;
+        }
+        let tangent = (radius * radius - base * base).sqrt();
+        let angle = pos.y.atan2(pos.x);
+        let (low_roll, high_roll) = ((low * low / (base * base) - 1.0).max(0.0).sqrt(),
+                                     (high * high / (base * base) - 1.0).max(0.0).sqrt());
+        for &turn in &[-(tangent / base).atan(), (tangent / base).atan()] {
+            let touch = angle + turn;
+            let roll = start - touch;
+            if roll >= low_roll && roll <= high_roll {
+                let on_base = polar(base, touch);
+                let flank = Point {
+                    x: on_base.x - base * roll * touch.sin(),
+                    y: on_base.y + base * roll * touch.cos(),
+                };
+                distance = distance.min(pos.distance(&flank));
+            }
+        }
+        distance
+    }
+}
+
+impl Implicit for InvoluteGear {
+    fn sample(&self, pos: Point) -> f32 {
+        use std::f32::consts::PI;
+
+        // Fold the point into the wedge between the middle of a tooth and
+        // the middle of the next gap, where the outline is a tip arc, a
+        // flank, possibly a straight piece, and a root arc.
+        let local = to_local(pos, self.center, self.rotation);
+        let wedge = PI / self.teeth as f32;
+        let radius = local.magnitude();
+        let mut angle = local.y.atan2(local.x) % (2.0 * wedge);
+        if angle < 0.0 {
+            angle += 2.0 * wedge;
+        }
+        if angle > wedge {
+            angle = 2.0 * wedge - angle;
+        }
+        let folded = polar(radius, angle);
+
+        let base = self.base_radius();
+        let root = self.root_radius();
+        let tip = self.tip_radius();
+        let low = root.max(base);
+
+        let mut distance = arc_distance(folded, radius, angle, tip, 0.0, self.flank_angle(tip).max(0.0))
+            .min(arc_distance(folded, radius, angle, root, self.flank_angle(low).min(wedge), wedge))
+            .min(self.flank_distance(folded, low, tip));
+        if root < base {
+            let start = self.flank_start();
+            distance = distance.min(Line(polar(root, start), polar(base, start)).dist_to_point(folded));
+        }
+
+        let inside = radius <= root || (radius <= tip && angle <= self.flank_angle(radius));
+        if inside { -distance } else { distance }
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        Some(Rect::centered_with_radius(&self.center, self.tip_radius()))
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+/// The number of even steps that a `ParametricCurve` is split into
+/// before any adaptive subdivision, so that small wiggles aren't missed.
+const CURVE_INITIAL_PIECES: usize = 64;
+const CURVE_MAX_DEPTH: u32 = 12;
+
+impl <F: Fn(f32) -> Point + Sync> ParametricCurve<F> {
+    pub fn new(function: F, start: f32, end: f32, thickness: f32) -> ParametricCurve<F> {
+        let n = CURVE_INITIAL_PIECES;
+        let ts: Vec<f32> = (0 .. n + 1).map(|i| start + (end - start) * (i as f32 / n as f32)).collect();
+        let points: Vec<Point> = ts.iter().map(|&t| function(t)).collect();
+
+        let mut bounds = Rect::null_at(&points[0]);
+        for p in &points {
+            bounds.expand_to_include(p);
+        }
+        // Pieces only need to be flat compared to the size of the curve;
+        // Newton's method takes care of the rest.
+        let tolerance = (bounds.width().max(bounds.height()) * 0.001).max(0.000001);
+
+        let mut pieces = vec![];
+        for i in 0 .. n {
+            subdivide(&function, ts[i], points[i], ts[i + 1], points[i + 1], tolerance, 0, &mut pieces);
+        }
+
+        let mut error: f32 = 0.0;
+        for piece in &pieces {
+            bounds.expand_to_include(&piece.chord.1);
+            error = error.max(piece.error);
+        }
+        let grow = thickness / 2.0 + error;
+
+        ParametricCurve {
+            function: function,
+            thickness: thickness,
+            closed: false,
+            pieces: pieces,
+            bounds: bounds.expand(grow, grow, grow, grow),
+        }
+    }
+
+    /// Joins the end of the curve back to its start with a straight line.
+    pub fn closed(self) -> ParametricCurve<F> {
+        ParametricCurve { closed: true, .. self }
+    }
+
+    /// The distance from `pos` to the closest point on `piece`, starting
+    /// Newton's method from the closest point on its chord.
+    fn refine(&self, piece: &CurvePiece, pos: Point) -> f32 {
+        let span = piece.end_t - piece.start_t;
+        let chord = piece.chord.1 - piece.chord.0;
+        let length_2 = chord.dot(&chord);
+        let along = if length_2 > 0.0 {
+            ((pos - piece.chord.0).dot(&chord) / length_2).max(0.0).min(1.0)
+        } else {
+            0.0
+        };
+
+        // Small steps lose everything to rounding in the second difference.
+        let h = (span.abs() * 0.01).max((piece.start_t.abs() + piece.end_t.abs()) * 0.0001);
+        let mut t = piece.start_t + span * along;
+        let mut best = ::std::f32::INFINITY;
+        for _ in 0 .. 16 {
+            let here = (self.function)(t);
+            let before = (self.function)(t - h);
+            let after = (self.function)(t + h);
+            let offset = here - pos;
+            best = best.min(offset.magnitude());
+
+            let velocity = (after - before) / (2.0 * h);
+            let acceleration = Vector {
+                x: (after.x - 2.0 * here.x + before.x) / (h * h),
+                y: (after.y - 2.0 * here.y + before.y) / (h * h),
+            };
+            // Looking for a zero of the derivative of the squared distance.
+            let slope = offset.dot(&velocity);
+            let speed_2 = velocity.dot(&velocity);
+            let curvature = speed_2 + offset.dot(&acceleration);
+            let denominator = if curvature > 0.0 { curvature } else { speed_2 };
+            if denominator <= 0.0 {
+                break;
+            }
+            let next = (t - slope / denominator).max(piece.start_t.min(piece.end_t)).min(piece.start_t.max(piece.end_t));
+            if (next - t).abs() <= span.abs() * 0.00001 {
+                break;
+            }
+            t = next;
+        }
+        best.min((self.function)(t).distance(&pos))
+    }
+}
+
+/// Splits the curve between `t0` and `t1` until each piece is within
+/// `tolerance` of its chord.
+fn subdivide<F: Fn(f32) -> Point>(function: &F, t0: f32, p0: Point, t1: f32, p1: Point,
+                                  tolerance: f32, depth: u32, out: &mut Vec<CurvePiece>) {
+    let chord = Line(p0, p1);
+    let tm = (t0 + t1) / 2.0;
+    let pm = function(tm);
+    let error = chord.dist_to_point(pm)
+        .max(chord.dist_to_point(function((t0 + tm) / 2.0)))
+        .max(chord.dist_to_point(function((tm + t1) / 2.0)));
+    if error > tolerance && depth < CURVE_MAX_DEPTH {
+        subdivide(function, t0, p0, tm, pm, tolerance, depth + 1, out);
+        subdivide(function, tm, pm, t1, p1, tolerance, depth + 1, out);
+    } else {
+        // The deviation is only measured at a few points, so leave room
+        // for the parts in between.
+        out.push(CurvePiece { start_t: t0, end_t: t1, chord: chord, error: error * 1.5 });
+    }
+}
+
+impl <F: Fn(f32) -> Point + Sync> Implicit for ParametricCurve<F> {
+    fn sample(&self, pos: Point) -> f32 {
+        let chord_distances: Vec<f32> = self.pieces.iter().map(|piece| piece.chord.dist_to_point(pos)).collect();
+        let mut upper = ::std::f32::INFINITY;
+        for (piece, &d) in self.pieces.iter().zip(chord_distances.iter()) {
+            upper = upper.min(d + piece.error);
+        }
+
+        let mut distance = ::std::f32::INFINITY;
+        if self.closed {
+            let first = self.pieces[0].chord.0;
+            let last = self.pieces[self.pieces.len() - 1].chord.1;
+            distance = Line(last, first).dist_to_point(pos);
+        }
+        for (piece, &d) in self.pieces.iter().zip(chord_distances.iter()) {
+            if d - piece.error > upper.min(distance) {
+                continue;
+            }
+            distance = distance.min(self.refine(piece, pos));
+        }
+        distance - self.thickness / 2.0
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        Some(self.bounds)
+    }
+
+    fn follows_rules(&self) -> bool { true }
+}
+
+impl <F: Fn(Point) -> f32 + Sync> FnImplicit<F> {
+    /// An unbounded field with a Lipschitz constant of 1.
+    pub fn new(function: F) -> FnImplicit<F> {
+        FnImplicit {
+            function: function,
+            bounds: None,
+            lipschitz: 1.0,
+        }
+    }
+
+    pub fn with_bounding_box(self, bounds: Rect) -> FnImplicit<F> {
+        FnImplicit { bounds: Some(bounds), .. self }
+    }
+
+    pub fn with_lipschitz_constant(self, lipschitz: f32) -> FnImplicit<F> {
+        assert!(lipschitz > 0.0, "the lipschitz constant must be positive");
+        FnImplicit { lipschitz: lipschitz, .. self }
+    }
+}
+
+impl <F: Fn(Point) -> f32 + Sync> Implicit for FnImplicit<F> {
+    fn sample(&self, pos: Point) -> f32 {
+        (self.function)(pos)
+    }
+
+    fn bounding_box(&self) -> Option<Rect> {
+        self.bounds
+    }
+
+    /// Fields that change faster than distance overestimate how far away
+    /// their edges are.
+    fn follows_rules(&self) -> bool {
+        self.lipschitz <= 1.0
+    }
+
+    fn lipschitz_constant(&self) -> f32 {
+        self.lipschitz
+    }
+}
+
+#[test]
+fn rings_bands_and_sectors() {
+    use std::f32::consts::{PI, FRAC_PI_2};
+    let origin = Point { x: 0.0, y: 0.0 };
+    let close = |a: f32, b: f32| (a - b).abs() < 0.0001;
+
+    let ring = Annulus { center: origin, inner_radius: 2.0, outer_radius: 4.0 };
+    assert!(close(ring.sample(origin), 2.0));
+    assert!(close(ring.sample(Point { x: 3.0, y: 0.0 }), -1.0));
+
+    // The top right quarter of a ring of radius 10, 2 thick.
+    let band = ArcBand::new(origin, 10.0, -FRAC_PI_2, FRAC_PI_2, 2.0);
+    assert!(close(band.sample(Point { x: 0.0, y: 12.0 }), (12.0f32 * 12.0 + 10.0 * 10.0).sqrt() - 1.0));
+    let square = band.with_cap(ArcCap::Square);
+    assert!(close(square.sample(Point { x: -3.0, y: -10.0 }), 3.0));
+    assert!(close(square.sample(Point { x: 0.5, y: -10.0 }), -0.5));
+    let bb = square.bounding_box().unwrap();
+    assert!(bb.close_to(&Rect::from_points(&Point { x: 0.0, y: -11.0 }, &Point { x: 11.0, y: 0.0 }), 0.0001));
+
+    // A three quarter pie, missing the quarter with positive x and negative y.
+    let pie = PieSector { center: origin, radius: 5.0, start_angle: 0.0, sweep: 1.5 * PI };
+    assert!(close(pie.sample(Point { x: 2.0, y: -1.0 }), 1.0));
+    assert!(close(pie.sample(Point { x: 1.0, y: 2.0 }), -2.0));
+    assert!(close(pie.sample(Point { x: 0.0, y: 7.0 }), 2.0));
+    let bb = pie.bounding_box().unwrap();
+    assert!(bb.close_to(&Rect::from_points(&Point { x: -5.0, y: -5.0 }, &Point { x: 5.0, y: 5.0 }), 0.0001));
+}
+
+#[test]
+fn regular_polygons_and_stars_are_folded() {
+    use std::f32::consts::FRAC_PI_4;
+    let origin = Point { x: 0.0, y: 0.0 };
+    let close = |a: f32, b: f32| (a - b).abs() < 0.0001;
+
+    // A square with sides of 2 * sqrt(2), turned to be axis aligned.
+    let square = RegularPolygon::new(origin, 4, 2.0).with_rotation(FRAC_PI_4);
+    let half = 2.0f32.sqrt();
+    assert!(close(square.sample(origin), -half));
+    assert!(close(square.sample(Point { x: 0.0, y: -3.0 }), 3.0 - half));
+    assert!(close(square.sample(Point { x: -3.0, y: 3.0 }), half * (3.0 - half)));
+    let bb = square.bounding_box().unwrap();
+    assert!(bb.close_to(&Rect::from_points(&Point { x: -half, y: -half }, &Point { x: half, y: half }), 0.0001));
+
+    let star = Star::new(Point { x: 1.0, y: 1.0 }, 5, 1.0, 3.0);
+    assert!(close(star.sample(Point { x: 5.0, y: 1.0 }), 1.0));
+    // The inner corners are the closest points to the center.
+    assert!(close(star.sample(Point { x: 1.0, y: 1.0 }), -1.0));
+    let bb = star.bounding_box().unwrap();
+    assert!(close(bb.right(), 4.0));
+}
+
+#[test]
+fn parametric_curves_are_refined() {
+    let close = |a: f32, b: f32| (a - b).abs() < 0.001;
+
+    let circle = ParametricCurve::new(|t: f32| Point { x: 5.0 * t.cos(), y: 5.0 * t.sin() },
+                                      0.0, 2.0 * ::std::f32::consts::PI, 1.0);
+    assert!(close(circle.sample(Point { x: 0.0, y: 0.0 }), 4.5));
+    assert!(close(circle.sample(Point { x: 6.0, y: 8.0 }), 4.5));
+    assert!(close(circle.sample(Point { x: 0.0, y: -5.2 }), -0.3));
+    let bb = circle.bounding_box().unwrap();
+    assert!(bb.close_to(&Rect::from_points(&Point { x: -5.5, y: -5.5 }, &Point { x: 5.5, y: 5.5 }), 0.05));
+
+    // Half of a parabola, which is only closed when asked.
+    let parabola = |t: f32| Point { x: t, y: t * t };
+    let open = ParametricCurve::new(parabola, 0.0, 2.0, 0.0);
+    assert!(close(open.sample(Point { x: -1.0, y: 0.0 }), 1.0));
+    assert!(close(open.sample(Point { x: 0.0, y: 1.0 }), (0.75f32).sqrt()));
+    let closed = ParametricCurve::new(parabola, 0.0, 2.0, 0.0).closed();
+    assert!(close(closed.sample(Point { x: 0.0, y: 1.0 }), 1.0 / 5.0f32.sqrt()));
+}
+
+#[test]
+fn lipschitz_constants_are_carried_through() {
+    let field = FnImplicit::new(|p: Point| 3.0 * (p.x * p.x + p.y * p.y).sqrt() - 3.0)
+        .with_lipschitz_constant(3.0);
+    assert!(!field.follows_rules());
+    assert_eq!(field.sample(Point { x: 2.0, y: 0.0 }), 3.0);
+    assert_eq!(field.bounding_box(), None);
+
+    let circle = Circle { center: Point { x: 0.0, y: 0.0 }, radius: 1.0 };
+    assert_eq!(circle.lipschitz_constant(), 1.0);
+    let combined = (&field).or(circle).grow(1.0).not();
+    assert_eq!(combined.lipschitz_constant(), 3.0);
+
+    let mut squashed = (&circle).transform();
+    squashed.matrix = squashed.matrix.scale(0.5, 1.0);
+    assert!((squashed.lipschitz_constant() - 2.0).abs() < 0.0001);
+    assert!((squashed.translate(3.0, 1.0).lipschitz_constant() - 2.0).abs() < 0.0001);
+}
+
+#[test]
+fn paths_sample_their_curves_exactly() {
+    use std::f32::consts::PI;
+    let origin = Point { x: 0.0, y: 0.0 };
+
+    // A full circle made out of one arc.
+    let mut disc = Path::new();
+    disc.move_to(Point { x: 3.0, y: 0.0 }).arc_to(origin, 2.0 * PI);
+    let circle = Circle { center: origin, radius: 3.0 };
+    for &(x, y) in &[(0.0, 0.0), (1.0, 2.0), (-5.0, 0.5), (0.1, -8.0)] {
+        let p = Point { x: x, y: y };
+        assert!((disc.sample(p) - circle.sample(p)).abs() < 0.0001);
+    }
+    let bb = disc.bounding_box().unwrap();
+    assert!(bb.close_to(&circle.bounding_box().unwrap(), 0.0001));
+
+    // Paths without any segments are empty, even if they were moved.
+    let mut empty = Path::new();
+    assert!(empty.bounding_box().is_none());
+    empty.move_to(Point { x: 1.0, y: 1.0 });
+    assert!(empty.bounding_box().is_none());
+
+    // A lens bounded by a quadratic and a cubic, closed by the builder.
+    let mut lens = Path::new();
+    lens.move_to(Point { x: 0.0, y: 0.0 })
+        .quad_to(Point { x: 5.0, y: -5.0 }, Point { x: 10.0, y: 0.0 })
+        .cubic_to(Point { x: 7.0, y: 4.0 }, Point { x: 3.0, y: 4.0 }, Point { x: 0.0, y: 0.0 })
+        .close();
+    assert!(lens.sample(Point { x: 5.0, y: 0.0 }) < 0.0);
+    assert!(lens.sample(Point { x: 5.0, y: -3.0 }) > 0.0);
+    assert!(lens.sample(Point { x: -1.0, y: 0.0 }) > 0.0);
+
+    let quad = QuadBezier(origin, Point { x: 5.0, y: -5.0 }, Point { x: 10.0, y: 0.0 });
+    let cubic = CubicBezier(Point { x: 10.0, y: 0.0 }, Point { x: 7.0, y: 4.0 }, Point { x: 3.0, y: 4.0 }, origin);
+    for &(x, y) in &[(5.0, 0.0), (5.0, -6.0), (12.0, 1.0), (4.0, 5.0)] {
+        let p = Point { x: x, y: y };
+        let brute = (0 .. 10001).map(|i| i as f32 / 10000.0)
+            .flat_map(|t| vec![quad.point_at(t), cubic.point_at(t)])
+            .map(|q| q.distance(&p))
+            .fold(::std::f32::INFINITY, f32::min);
+        assert!((lens.sample(p).abs() - brute).abs() < 0.001, "{:?}", p);
+    }
+}
+
+#[test]
+fn boxes_and_capsules_have_exact_distances() {
+    let square = RoundedBox::new(Rect::from_points(&Point { x: 0.0, y: 0.0 }, &Point { x: 4.0, y: 2.0 }));
+    assert_eq!(square.sample(Point { x: 2.0, y: 1.0 }), -1.0);
+    assert_eq!(square.sample(Point { x: 7.0, y: 6.0 }), 5.0);
+
+    // Only the top right corner is rounded.
+    let rounded = square.with_corner_radii([0.0, 1.0, 0.0, 0.0]);
+    assert!((rounded.sample(Point { x: 6.0, y: -2.0 }) - (18.0f32.sqrt() - 1.0)).abs() < 0.0001);
+    assert!((rounded.sample(Point { x: -2.0, y: -2.0 }) - 8.0f32.sqrt()).abs() < 0.0001);
+
+    let tilted = RoundedBox::centered(Point { x: 0.0, y: 0.0 }, 4.0, 2.0)
+        .with_rotation(::std::f32::consts::FRAC_PI_2);
+    assert!((tilted.sample(Point { x: 0.0, y: 3.0 }) - 1.0).abs() < 0.0001);
+    let bb = tilted.bounding_box().unwrap();
+    assert!(bb.close_to(&Rect::from_points(&Point { x: -1.0, y: -2.0 }, &Point { x: 1.0, y: 2.0 }), 0.0001));
+
+    let segment = RoundedBox::from_segment(Point { x: 0.0, y: 0.0 }, Point { x: 3.0, y: 4.0 }, 2.0);
+    assert!((segment.sample(Point { x: 6.0, y: 8.0 }) - 5.0).abs() < 0.0001);
+    let capsule = Capsule { start: Point { x: 0.0, y: 0.0 }, end: Point { x: 3.0, y: 4.0 }, radius: 1.0 };
+    assert!((capsule.sample(Point { x: 6.0, y: 8.0 }) - 4.0).abs() < 0.0001);
+}
+
+#[test]
+fn ellipse_distances_match_brute_force() {
+    let ellipse = Ellipse::new(Point { x: 1.0, y: -2.0 }, 5.0, 2.0).with_rotation(0.7);
+    let outline: Vec<Point> = (0 .. 20000).map(|i| {
+        let t = i as f32 / 20000.0 * 2.0 * ::std::f32::consts::PI;
+        let (x, y) = (5.0 * t.cos(), 2.0 * t.sin());
+        let (c, s) = (0.7f32.cos(), 0.7f32.sin());
+        Point { x: 1.0 + c * x - s * y, y: -2.0 + s * x + c * y }
+    }).collect();
+
+    for &(x, y) in &[(0.0, 0.0), (1.0, -2.0), (7.0, 1.0), (-3.0, -5.0), (2.0, -1.5), (20.0, 20.0)] {
+        let p = Point { x: x, y: y };
+        let closest = outline.iter().map(|o| o.distance(&p)).fold(::std::f32::INFINITY, f32::min);
+        assert!((ellipse.sample(p).abs() - closest).abs() < 0.01, "{:?}", p);
+    }
+    assert!(ellipse.sample(Point { x: 1.0, y: -2.0 }) < 0.0);
+    assert!((ellipse.sample(Point { x: 1.0, y: -2.0 }) + 2.0).abs() < 0.0001);
+}
+
+#[test]
+fn involute_gears_are_exact() {
+    use std::f32::consts::PI;
+    let origin = Point { x: 0.0, y: 0.0 };
+    let close = |a: f32, b: f32| (a - b).abs() < 0.0001;
+
+    // Pitch radius 20, tips at 22 and roots at 17.5.
+    let gear = InvoluteGear::new(origin, 2.0, 20);
+    assert!(close(gear.sample(Point { x: 23.0, y: 0.0 }), 1.0));
+    assert!(close(gear.sample(polar(17.0, PI / 20.0)), -0.5));
+    assert!(close(gear.sample(polar(18.0, PI / 20.0)), 0.5));
+    let bb = gear.bounding_box().unwrap();
+    assert!(close(bb.right(), 22.0));
+
+    // Teeth are half of the circular pitch wide at the pitch circle,
+    // less the backlash.
+    let flank = polar(20.0, PI / 40.0);
+    assert!(gear.sample(flank).abs() < 0.001);
+    assert!(gear.with_backlash(0.5).sample(flank) > 0.2);
+
+    // Turning by half a tooth puts a gap along +x.
+    assert!(gear.with_rotation(PI / 20.0).sample(Point { x: 21.0, y: 0.0 }) > 1.0);
+
+    // Teeth that are too tall come to a point before the outer radius.
+    let pointed = InvoluteGear::new(origin, 2.0, 10).with_addendum(4.0);
+    assert!(pointed.tip_radius() < pointed.outer_radius());
+    assert!(close(pointed.sample(Point { x: pointed.tip_radius(), y: 0.0 }), 0.0));
+}
+
+#[test]
+fn blended_booleans() {
+    let close = |a: f32, b: f32| (a - b).abs() < 0.0001;
+    let circle = |x: f32, radius: f32| Circle { center: Point { x: x, y: 0.0 }, radius: radius };
+    let origin = Point { x: 0.0, y: 0.0 };
+
+    // Two circles that miss each other by a gap of 1.
+    let (a, b) = (circle(-1.5, 1.0), circle(1.5, 1.0));
+    assert!(close(a.or(b).sample(origin), 0.5));
+    assert!(close(a.smooth_or(b, 2.0).sample(origin), 0.0));
+    assert!(close(a.blend_or(b, Blend::Exponential(2.0)).sample(origin), 0.0));
+    assert!(close(a.chamfer_or(b, 2.0).sample(origin), -0.5f32.sqrt()));
+    assert!(close(a.blend_or(b, Blend::Stairs(2.0, 4)).sample(origin), -0.5));
+    // Blends only reach as far as their radius.
+    assert!(close(a.smooth_or(b, 2.0).sample(Point { x: 5.0, y: 0.0 }), 2.5));
+    assert!(close(a.chamfer_or(b, 2.0).sample(Point { x: 5.0, y: 0.0 }), 2.5));
+    let bb = a.smooth_or(b, 2.0).bounding_box().unwrap();
+    assert!(close(bb.width(), 9.0));
+
+    // Overlapping circles, which meet at a corner above and below x = 1.
+    let (c, d) = (circle(0.0, 2.0), circle(2.0, 2.0));
+    let middle = Point { x: 1.0, y: 0.0 };
+    assert!(close(c.and(d).sample(middle), -1.0));
+    assert!(close(c.smooth_and(d, 1.0).sample(middle), -0.75));
+    let left = Point { x: -1.0, y: 0.0 };
+    assert!(close(c.and_not(d).sample(left), -1.0));
+    assert!(close(c.smooth_and_not(d, 1.0).sample(left), -0.75));
+    assert!(c.chamfer_and_not(d, 1.0).sample(left) > -1.0);
+
+    assert_eq!(c.smooth_or(d, 1.0).lipschitz_constant(), 1.0);
+    assert!(close(c.chamfer_or(d, 1.0).lipschitz_constant(), 2.0f32.sqrt()));
+}
@@ -17,8 +17,8 @@ pub mod formats;
 mod scene;
 pub(crate) mod util;
 
-pub use vectorize::render::{render, RenderMode, OutputMode};
-pub use formats::output_device::{OutputDevice, NullDevice};
+pub use vectorize::render::{render, RenderMode, OutputMode, FillRule, FilledRegion};
+pub use formats::output_device::{OutputDevice, NullDevice, StrokeStyle, PathInfo, Operation};
 pub use scene::*;
 pub use vectorize::gather_lines;
 pub use vectorize::line_gather;
@@ -48,6 +48,15 @@ pub trait Implicit {
     /// True if the shape follows all the rules about implicit shapes.
     fn follows_rules(&self) -> bool;
 
+    /// The most that `sample` can change over a unit of distance.
+    ///
+    /// A true distance field has a Lipschitz constant of 1.  Fields that
+    /// change faster can still be rendered, because dividing their samples
+    /// by this gives a safe lower bound on the distance to their edge.
+    fn lipschitz_constant(&self) -> f32 {
+        1.0
+    }
+
     fn boxed(self) -> SyncBox where Self: Sized + 'static + Sync {
         SyncBox::new(self)
     }
@@ -80,6 +89,62 @@ pub trait Implicit {
         }
     }
 
+    /// Joins two shapes with `blend` filling the corners where they meet.
+    fn blend_or<B: Implicit>(self, other: B, blend: Blend) -> BlendedOr<Self, B> where Self: Sized {
+        BlendedOr {
+            left: self,
+            right: other,
+            blend: blend,
+        }
+    }
+
+    /// Keeps the overlap of two shapes, with `blend` cutting off the
+    /// corners where their edges meet.
+    fn blend_and<B: Implicit>(self, other: B, blend: Blend) -> BlendedAnd<Self, B> where Self: Sized {
+        BlendedAnd {
+            left: self,
+            right: other,
+            blend: blend,
+        }
+    }
+
+    /// Cuts `other` out of this shape, with `blend` on the corners left
+    /// behind.
+    fn blend_and_not<B: Implicit>(self, other: B, blend: Blend) -> BlendedAnd<Self, Not<B>> where Self: Sized {
+        self.blend_and(other.not(), blend)
+    }
+
+    /// Like `or`, with round fillets `radius` across.
+    fn smooth_or<B: Implicit>(self, other: B, radius: f32) -> BlendedOr<Self, B> where Self: Sized {
+        self.blend_or(other, Blend::Round(radius))
+    }
+
+    /// Like `and`, with round fillets `radius` across.
+    fn smooth_and<B: Implicit>(self, other: B, radius: f32) -> BlendedAnd<Self, B> where Self: Sized {
+        self.blend_and(other, Blend::Round(radius))
+    }
+
+    /// Like `and_not`, with round fillets `radius` across.
+    fn smooth_and_not<B: Implicit>(self, other: B, radius: f32) -> BlendedAnd<Self, Not<B>> where Self: Sized {
+        self.blend_and_not(other, Blend::Round(radius))
+    }
+
+    /// Like `or`, with the corners cut off `radius` from where they were.
+    fn chamfer_or<B: Implicit>(self, other: B, radius: f32) -> BlendedOr<Self, B> where Self: Sized {
+        self.blend_or(other, Blend::Chamfer(radius))
+    }
+
+    /// Like `and`, with the corners cut off `radius` from where they were.
+    fn chamfer_and<B: Implicit>(self, other: B, radius: f32) -> BlendedAnd<Self, B> where Self: Sized {
+        self.blend_and(other, Blend::Chamfer(radius))
+    }
+
+    /// Like `and_not`, with the corners cut off `radius` from where they
+    /// were.
+    fn chamfer_and_not<B: Implicit>(self, other: B, radius: f32) -> BlendedAnd<Self, Not<B>> where Self: Sized {
+        self.blend_and_not(other, Blend::Chamfer(radius))
+    }
+
     fn shrink(self, by: f32) -> Boundary<Self> where Self: Sized {
         let by = by.max(0.0);
         Boundary {
@@ -1,31 +1,41 @@
 mod drawing_quadtree_producer;
 
-use ::{OutputMode, RenderMode, Implicit, render, OutputDevice};
+use ::{OutputMode, RenderMode, Implicit, render, OutputDevice, PathInfo, Operation, StrokeStyle};
 use ::util::geom::{Point, Rect, Matrix};
 use self::drawing_quadtree_producer::DrawWrapper;
 
 #[macro_export]
 macro_rules! figure {
     () => { () };
-    (($shape:expr, $mode:expr, $matrix:expr, $mask:expr)) => {
+    (($shape:expr, $mode:expr, $matrix:expr, $mask:expr, $operation:expr, $style:expr)) => {
         FigureLink {
             shape: $shape,
             mode: $mode,
             matrix: $matrix,
             mask: $mask,
+            operation: $operation,
+            style: $style,
             next: (),
         }
     };
-    (($shape:expr, $mode:expr, $matrix:expr, $mask:expr), $($rest:tt),+) => {
+    (($shape:expr, $mode:expr, $matrix:expr, $mask:expr, $operation:expr, $style:expr), $($rest:tt),+) => {
         FigureLink {
             shape: $shape,
             mode: $mode,
             matrix: $matrix,
             mask: $mask,
+            operation: $operation,
+            style: $style,
             next: figure!($($rest),+),
         }
     };
 
+    (($shape:expr, $mode:expr, $matrix:expr, $mask:expr)) => {
+        figure![($shape, $mode, $matrix, $mask, None, None)]
+    };
+    (($shape:expr, $mode:expr, $matrix:expr, $mask:expr), $($rest:tt),+) => {
+        figure![($shape, $mode, $matrix, $mask, None, None), $($rest),+]
+    };
     (($shape:expr, $mode:expr, $matrix:expr)) => {
         figure![($shape, $mode, $matrix, None)]
     };
@@ -69,17 +79,41 @@ pub struct FigureState {
     low_y: f32,
     figure_bb: Rect,
     adjusted_bb: Rect,
-    shapes: Vec<OutputMode>,
+    shapes: Vec<RenderedShape>,
 
     current_y: f32,
     recursion_depth: u32,
 }
 
+/// The output of one link in a figure, along with how it should be drawn.
+struct RenderedShape {
+    output: OutputMode,
+    operation: Option<Operation>,
+    style: Option<StrokeStyle>,
+}
+
+/// A figure made of open lines, like single stroke lettering, that are
+/// drawn as they are instead of being traced around the edge of a shape.
+///
+/// Unless told otherwise, the lines are scored rather than cut.
+pub struct Strokes {
+    pub lines: Vec<Vec<Point>>,
+    pub operation: Option<Operation>,
+    pub style: Option<StrokeStyle>,
+}
+
 pub struct FigureLink<S: Implicit, N: ApplyFigure> {
     pub shape: S,
     pub mask: Option<Box<Implicit>>,
     pub matrix: Option<Matrix>,
     pub mode: RenderMode,
+    /// What the machine should do along the shape's paths.  By default
+    /// solid shapes are engraved, outlines are cut and dashed lines are
+    /// scored.
+    pub operation: Option<Operation>,
+    /// How the paths should look.  By default they are hairlines in the
+    /// colour of their operation.
+    pub style: Option<StrokeStyle>,
 
     pub next: N
 }
@@ -111,7 +145,11 @@ impl <S: Implicit + Sync, N: ApplyFigure> ApplyFigure for FigureLink<S, N> {
         state.adjusted_bb = state.adjusted_bb.union_with(&bb);
 
         let out = render(shape, &self.mode, state.recursion_depth, true);
-        state.shapes.push(out);
+        state.shapes.push(RenderedShape {
+            output: out,
+            operation: self.operation,
+            style: self.style,
+        });
 
         // Continue
         self.next.render(state);
@@ -126,6 +164,49 @@ impl <S: Implicit + Sync, N: ApplyFigure> ApplyFigure for FigureLink<S, N> {
     }
 }
 
+impl Strokes {
+    pub fn new(lines: Vec<Vec<Point>>) -> Strokes {
+        Strokes {
+            lines: lines,
+            operation: None,
+            style: None,
+        }
+    }
+
+    /// The smallest rectangle around every point, if there are any.
+    pub fn bounding_box(&self) -> Option<Rect> {
+        lines_bounding_box(&self.lines)
+    }
+}
+
+impl ApplyFigure for Strokes {
+    fn analyze(&self, state: &mut FigureState) {
+        if let Some(bb) = self.bounding_box() {
+            let Point{x, y} = bb.top_left();
+            state.figure_bb = state.figure_bb.union_with(&bb);
+            state.low_x = state.low_x.min(x);
+            state.low_y = state.low_y.min(y);
+        }
+    }
+
+    fn render(&self, state: &mut FigureState) {
+        let (dx, dy) = (-state.low_x, state.current_y - state.low_y);
+        let lines: Vec<Vec<Point>> = self.lines.iter().map(|line| {
+            line.iter().map(|p| Point { x: p.x + dx, y: p.y + dy }).collect()
+        }).collect();
+        if let Some(bb) = lines_bounding_box(&lines) {
+            state.adjusted_bb = state.adjusted_bb.union_with(&bb);
+        }
+        state.shapes.push(RenderedShape {
+            output: OutputMode::Strokes(lines),
+            operation: self.operation,
+            style: self.style,
+        });
+    }
+
+    fn draw_into(&self, _: &FigureState, _: &mut FnMut(Rect, SampleValue)) { }
+}
+
 impl Scene {
     pub fn new() -> Scene {
         Scene {
@@ -136,6 +217,13 @@ impl Scene {
     }
 
     pub fn render_shapes(&self) -> (Vec<OutputMode>, Rect) {
+        let (figures, total_bounding_box) = self.render_figures();
+        let out = figures.into_iter().flat_map(|f| f.into_iter()).map(|r| r.output).collect();
+        (out, total_bounding_box)
+    }
+
+    /// Like `render_shapes`, but keeps the shapes of each figure together.
+    fn render_figures(&self) -> (Vec<Vec<RenderedShape>>, Rect) {
         let mut total_bounding_box = Rect::null();
         let mut out = vec![];
 
@@ -157,7 +245,7 @@ impl Scene {
             list.analyze(&mut state);
             list.render(&mut state);
 
-            out.append(&mut state.shapes);
+            out.push(state.shapes);
             current_y += state.figure_bb.height();
             current_y += self.margin;
             total_bounding_box = total_bounding_box.union_with(&state.adjusted_bb);
@@ -194,40 +282,41 @@ impl Scene {
     }
 
     pub fn render_all<O: OutputDevice>(&self, out: &mut O) {
-        let (shapes, total_bounding_box) = self.render_shapes();
-        let Point { x: top_x, y: top_y } = total_bounding_box.top_left();
+        let (figures, total_bounding_box) = self.render_figures();
+        let top = total_bounding_box.top_left();
         out.set_size(total_bounding_box.width(), total_bounding_box.height());
-        for rendered in &shapes {
-            match rendered {
-                &OutputMode::Solid(_) => unimplemented!(),
-                &OutputMode::Outline(ref lines) => {
-                    for line in lines {
-                        out.start_line();
-                        let Point { x: start_x, y:start_y } = line[0];
-                        for &Point{x, y} in line {
-                            out.add_point(Point {
-                                x: x - top_x,
-                                y: y - top_y
-                            });
+        for (figure, shapes) in figures.iter().enumerate() {
+            for rendered in shapes {
+                match &rendered.output {
+                    &OutputMode::Solid(ref regions, rule) => {
+                        let info = rendered.path_info(Operation::Engrave, true, figure);
+                        for region in regions {
+                            out.start_fill(rule, &info);
+                            draw_closed(out, &region.outer, top, &info);
+                            for hole in &region.holes {
+                                draw_closed(out, hole, top, &info);
+                            }
+                            out.end_fill();
                         }
-                        out.add_point(Point {
-                            x: start_x - top_x,
-                            y: start_y - top_y
-                        });
-                        out.end_line();
                     }
-                },
-                &OutputMode::DashedLine(ref dashed) => {
-                    for dashed_line in dashed {
-                        for segment in dashed_line.segments() {
-                            out.start_line();
-                            for &Point{x, y} in segment {
-                                out.add_point(Point {
-                                    x: x + top_x,
-                                    y: y + top_y,
-                                });
+                    &OutputMode::Outline(ref lines) => {
+                        let info = rendered.path_info(Operation::Cut, true, figure);
+                        for line in lines {
+                            draw_closed(out, line, top, &info);
+                        }
+                    },
+                    &OutputMode::DashedLine(ref dashed) => {
+                        let info = rendered.path_info(Operation::Score, false, figure);
+                        for dashed_line in dashed {
+                            for segment in dashed_line.segments() {
+                                draw_open(out, segment, top, &info);
                             }
-                            out.end_line();
+                        }
+                    }
+                    &OutputMode::Strokes(ref lines) => {
+                        let info = rendered.path_info(Operation::Score, false, figure);
+                        for line in lines {
+                            draw_open(out, line, top, &info);
                         }
                     }
                 }
@@ -236,6 +325,60 @@ impl Scene {
     }
 }
 
+impl RenderedShape {
+    fn path_info(&self, operation: Operation, closed: bool, figure: usize) -> PathInfo {
+        let mut info = PathInfo::new(self.operation.unwrap_or(operation), closed, figure);
+        if let Some(style) = self.style {
+            info.style = style;
+        }
+        info
+    }
+}
+
+fn draw_open<O: OutputDevice>(out: &mut O, line: &[Point], top: Point, info: &PathInfo) {
+    let Point { x: top_x, y: top_y } = top;
+    out.start_path(info);
+    for &Point{x, y} in line {
+        out.add_point(Point {
+            x: x - top_x,
+            y: y - top_y
+        });
+    }
+    out.end_path(info);
+}
+
+fn draw_closed<O: OutputDevice>(out: &mut O, line: &[Point], top: Point, info: &PathInfo) {
+    if line.is_empty() {
+        return;
+    }
+    let Point { x: top_x, y: top_y } = top;
+    out.start_path(info);
+    let Point { x: start_x, y:start_y } = line[0];
+    for &Point{x, y} in line {
+        out.add_point(Point {
+            x: x - top_x,
+            y: y - top_y
+        });
+    }
+    out.add_point(Point {
+        x: start_x - top_x,
+        y: start_y - top_y
+    });
+    out.end_path(info);
+}
+
+fn lines_bounding_box(lines: &[Vec<Point>]) -> Option<Rect> {
+    let mut points = lines.iter().flat_map(|line| line.iter());
+    let mut bb = match points.next() {
+        Some(p) => Rect::null_at(p),
+        None => return None,
+    };
+    for p in points {
+        bb.expand_to_include(p);
+    }
+    Some(bb)
+}
+
 fn transform_bounding_box(bb: Rect, matrix: Matrix) -> Rect {
     let a = matrix.transform_point(&bb.top_left());
     let b = matrix.transform_point(&bb.top_right());
@@ -247,3 +390,168 @@ fn transform_bounding_box(bb: Rect, matrix: Matrix) -> Rect {
     new_bb.expand_to_include(&d);
     new_bb
 }
+
+#[test]
+fn dashed_lines_are_drawn_inside_the_page() {
+    use ::{Circle, RenderMode};
+
+    struct Recorder {
+        size: (f32, f32),
+        points: Vec<Point>,
+    }
+
+    impl OutputDevice for Recorder {
+        fn start_line(&mut self) { }
+        fn add_point(&mut self, p: Point) { self.points.push(p); }
+        fn end_line(&mut self) { }
+        fn set_size(&mut self, w: f32, h: f32) { self.size = (w, h); }
+    }
+
+    let circle = Circle { center: Point { x: 30.0, y: 40.0 }, radius: 10.0 };
+    let mut scene = Scene::new();
+    scene.add(figure![(circle, RenderMode::BasicDashed(vec![2.0, 1.0]))]);
+
+    let mut recorder = Recorder { size: (0.0, 0.0), points: vec![] };
+    scene.render_all(&mut recorder);
+
+    let (w, h) = recorder.size;
+    assert!(recorder.points.len() > 10);
+    let (mut low, mut high) = (Point { x: w, y: h }, Point { x: 0.0, y: 0.0 });
+    for p in &recorder.points {
+        low = Point { x: low.x.min(p.x), y: low.y.min(p.y) };
+        high = Point { x: high.x.max(p.x), y: high.y.max(p.y) };
+    }
+    assert!(low.x.abs() < 0.5 && low.y.abs() < 0.5, "{:?}", low);
+    assert!((high.x - w).abs() < 0.5 && (high.y - h).abs() < 0.5, "{:?} {:?}", high, (w, h));
+}
+
+#[test]
+fn figures_choose_their_operation_and_style() {
+    use ::{Circle, RenderMode};
+    use ::formats::svg::SvgWriter;
+    use ::formats::dxf::DxfWriter;
+
+    struct Recorder(Vec<PathInfo>);
+    impl OutputDevice for Recorder {
+        fn start_line(&mut self) { }
+        fn add_point(&mut self, _: Point) { }
+        fn end_line(&mut self) { }
+        fn start_path(&mut self, info: &PathInfo) { self.0.push(*info); }
+    }
+
+    let circle = |x, y, radius| Circle { center: Point { x: x, y: y }, radius: radius };
+    let green = StrokeStyle::new((0.0, 1.0, 0.0), 0.0);
+
+    let mut scene = Scene::new();
+    scene.add(figure![
+        (circle(10.0, 10.0, 10.0)),
+        (circle(10.0, 10.0, 6.0), RenderMode::BasicDashed(vec![2.0, 1.0]))
+    ]);
+    scene.add(figure![(circle(10.0, 10.0, 10.0), RenderMode::Outline, None, None, Some(Operation::Score), Some(green))]);
+    scene.add(Strokes::new(vec![vec![Point { x: 0.0, y: 0.0 }, Point { x: 5.0, y: 5.0 }]]));
+
+    let mut recorder = Recorder(vec![]);
+    scene.render_all(&mut recorder);
+    let mut infos = recorder.0;
+    infos.dedup();
+    let red = Operation::Cut.default_style();
+    let blue = Operation::Score.default_style();
+    assert_eq!(infos, vec![
+        PathInfo { closed: true, figure: 0, operation: Operation::Cut, style: red },
+        PathInfo { closed: false, figure: 0, operation: Operation::Score, style: blue },
+        PathInfo { closed: true, figure: 1, operation: Operation::Score, style: green },
+        PathInfo { closed: false, figure: 2, operation: Operation::Score, style: blue },
+    ]);
+
+    let mut svg = SvgWriter::new("mm", 1.0);
+    scene.render_all(&mut svg);
+    let mut buffer = vec![];
+    svg.write_to(&mut buffer).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+    let mut paths: Vec<String> = text.lines()
+        .filter(|l| l.starts_with("<path"))
+        .map(|l| l.split(" d=").next().unwrap().to_string())
+        .collect();
+    paths.sort();
+    assert_eq!(paths, vec![
+        "<path class=\"cut closed\" fill=\"none\" stroke=\"rgb(255,0,0)\" stroke-width=\"1\" vector-effect=\"non-scaling-stroke\"",
+        "<path class=\"score closed\" fill=\"none\" stroke=\"rgb(0,255,0)\" stroke-width=\"1\" vector-effect=\"non-scaling-stroke\"",
+        "<path class=\"score open\" fill=\"none\" stroke=\"rgb(0,0,255)\" stroke-width=\"1\" vector-effect=\"non-scaling-stroke\"",
+    ]);
+
+    let mut dxf = DxfWriter::new("mm", 1.0);
+    scene.render_all(&mut dxf);
+    let mut buffer = vec![];
+    dxf.write_to(&mut buffer).unwrap();
+    let text = String::from_utf8(buffer).unwrap();
+    let lines: Vec<&str> = text.lines().map(|l| l.trim()).collect();
+    // (layer, closed flag) of every polyline.
+    let mut polylines: Vec<(String, String)> = vec![];
+    let mut in_polyline = false;
+    for pair in lines.chunks(2) {
+        match (pair[0], pair[1]) {
+            ("0", entity) => in_polyline = entity == "LWPOLYLINE",
+            ("8", layer) if in_polyline => polylines.push((layer.to_string(), String::new())),
+            ("70", flag) if in_polyline => polylines.last_mut().unwrap().1 = flag.to_string(),
+            _ => {}
+        }
+    }
+    polylines.dedup();
+    let expected: Vec<(String, String)> = [("cut", "1"), ("score", "0"), ("score", "1"), ("score", "0")]
+        .iter().map(|&(a, b)| (a.to_string(), b.to_string())).collect();
+    assert_eq!(polylines, expected);
+}
+
+#[test]
+fn solid_shapes_are_filled_with_their_holes() {
+    use ::{Circle, RenderMode, FillRule};
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        StartFill(FillRule),
+        Path(bool, Operation),
+        EndFill,
+    }
+    struct Recorder {
+        events: Vec<Event>,
+        contours: Vec<Vec<Point>>,
+    }
+    impl OutputDevice for Recorder {
+        fn start_line(&mut self) { }
+        fn add_point(&mut self, p: Point) { self.contours.last_mut().unwrap().push(p); }
+        fn end_line(&mut self) { }
+        fn start_path(&mut self, info: &PathInfo) {
+            self.events.push(Event::Path(info.closed, info.operation));
+            self.contours.push(vec![]);
+        }
+        fn start_fill(&mut self, rule: FillRule, _: &PathInfo) { self.events.push(Event::StartFill(rule)); }
+        fn end_fill(&mut self) { self.events.push(Event::EndFill); }
+    }
+
+    let origin = Point { x: 0.0, y: 0.0 };
+    let ring = Circle { center: origin, radius: 10.0 }.and_not(Circle { center: origin, radius: 5.0 });
+    let mut scene = Scene::new();
+    scene.add(figure![(ring, RenderMode::Solid(FillRule::EvenOdd))]);
+
+    let mut recorder = Recorder { events: vec![], contours: vec![] };
+    scene.render_all(&mut recorder);
+    assert_eq!(recorder.events, vec![
+        Event::StartFill(FillRule::EvenOdd),
+        Event::Path(true, Operation::Engrave),
+        Event::Path(true, Operation::Engrave),
+        Event::EndFill,
+    ]);
+
+    // The hole winds the other way around from the outside.
+    let winding = |poly: &[Point]| {
+        poly.windows(2).fold(0.0, |total, w| total + w[0].x * w[1].y - w[1].x * w[0].y)
+    };
+    let (outer, hole) = (winding(&recorder.contours[0]), winding(&recorder.contours[1]));
+    assert!(outer * hole < 0.0);
+    assert!(outer.abs() > hole.abs());
+
+    // Empty contours are skipped instead of drawn.
+    let info = PathInfo::new(Operation::Engrave, true, 0);
+    draw_closed(&mut recorder, &[], origin, &info);
+    assert_eq!(recorder.events.len(), 4);
+}
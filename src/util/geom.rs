@@ -647,6 +647,412 @@ impl Point {
     }
 }
 
+/// A circular arc, starting at `start_angle` and turning by `sweep`
+/// radians (positive sweeps go from +x towards +y).
+#[derive(PartialOrd, PartialEq, Copy, Clone, Debug)]
+pub struct CircularArc {
+    pub center: Point,
+    pub radius: f32,
+    pub start_angle: f32,
+    pub sweep: f32,
+}
+
+/// A quadratic bezier curve with one control point.
+#[derive(PartialOrd, PartialEq, Copy, Clone, Debug)]
+pub struct QuadBezier(pub Point, pub Point, pub Point);
+
+/// A cubic bezier curve with two control points.
+#[derive(PartialOrd, PartialEq, Copy, Clone, Debug)]
+pub struct CubicBezier(pub Point, pub Point, pub Point, pub Point);
+
+/// Any one of the pieces that an outline can be made out of.
+#[derive(PartialOrd, PartialEq, Copy, Clone, Debug)]
+pub enum Segment {
+    Line(Line),
+    Arc(CircularArc),
+    Quad(QuadBezier),
+    Cubic(CubicBezier),
+}
+
+/// The real roots of `a t^3 + b t^2 + c t + d`, falling back to lower
+/// degrees when the leading coefficients vanish.
+fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    const EPSILON: f64 = 1.0e-12;
+    let scale = a.abs().max(b.abs()).max(c.abs()).max(d.abs());
+    if scale == 0.0 {
+        return vec![];
+    }
+    if a.abs() <= EPSILON * scale {
+        return solve_quadratic(b, c, d);
+    }
+
+    // Depressed cubic t = u - b / 3a, u^3 + p u + q = 0.
+    let (b, c, d) = (b / a, c / a, d / a);
+    let shift = b / 3.0;
+    let p = c - b * b / 3.0;
+    let q = 2.0 * b * b * b / 27.0 - b * c / 3.0 + d;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+
+    if discriminant > 0.0 {
+        let s = discriminant.sqrt();
+        vec![(-q / 2.0 + s).cbrt() + (-q / 2.0 - s).cbrt() - shift]
+    } else if p == 0.0 {
+        vec![-shift]
+    } else {
+        // Three real roots, from the trigonometric solution.
+        let m = 2.0 * (-p / 3.0).sqrt();
+        let theta = (3.0 * q / (p * m)).max(-1.0).min(1.0).acos() / 3.0;
+        (0 .. 3).map(|k| {
+            m * (theta - 2.0 * ::std::f64::consts::PI * k as f64 / 3.0).cos() - shift
+        }).collect()
+    }
+}
+
+/// The real roots of `a t^2 + b t + c`.
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    const EPSILON: f64 = 1.0e-12;
+    let scale = a.abs().max(b.abs()).max(c.abs());
+    if scale == 0.0 {
+        return vec![];
+    }
+    if a.abs() <= EPSILON * scale {
+        return if b == 0.0 { vec![] } else { vec![-c / b] };
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return vec![];
+    }
+    // Avoids cancellation between -b and the square root.
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        vec![0.0]
+    } else {
+        vec![q / a, c / q]
+    }
+}
+
+/// Parameters strictly inside of (0, 1).
+fn interior(roots: Vec<f64>) -> Vec<f32> {
+    roots.into_iter().filter(|&t| t > 0.0 && t < 1.0).map(|t| t as f32).collect()
+}
+
+/// Angles in `[0, 2pi)` relative to `start`, in the direction of `sweep`.
+fn arc_offset(angle: f32, start: f32, sweep: f32) -> f32 {
+    const TAU: f32 = 2.0 * ::std::f32::consts::PI;
+    let delta = if sweep >= 0.0 { angle - start } else { start - angle };
+    let delta = delta % TAU;
+    if delta < 0.0 { delta + TAU } else { delta }
+}
+
+impl CircularArc {
+    pub fn point_at(&self, t: f32) -> Point {
+        let angle = self.start_angle + self.sweep * t;
+        Point {
+            x: self.center.x + self.radius * angle.cos(),
+            y: self.center.y + self.radius * angle.sin(),
+        }
+    }
+
+    pub fn start(&self) -> Point {
+        self.point_at(0.0)
+    }
+
+    pub fn end(&self) -> Point {
+        self.point_at(1.0)
+    }
+
+    /// The parameters at which the arc passes through `angle`.
+    fn parameters_at_angle(&self, angle: f32) -> Vec<f32> {
+        const TAU: f32 = 2.0 * ::std::f32::consts::PI;
+        if self.sweep == 0.0 {
+            return vec![];
+        }
+        let mut offset = arc_offset(angle, self.start_angle, self.sweep);
+        let mut out = vec![];
+        while offset < self.sweep.abs() {
+            out.push(offset / self.sweep.abs());
+            offset += TAU;
+        }
+        out
+    }
+
+    /// True if the arc passes through `angle`, or would if its radius
+    /// were different.
+    pub fn contains_angle(&self, angle: f32) -> bool {
+        self.sweep.abs() >= 2.0 * ::std::f32::consts::PI ||
+        arc_offset(angle, self.start_angle, self.sweep) <= self.sweep.abs()
+    }
+
+    pub fn dist_to_point(&self, p: Point) -> f32 {
+        let (dx, dy) = (p.x - self.center.x, p.y - self.center.y);
+        let to_circle = ((dx * dx + dy * dy).sqrt() - self.radius).abs();
+        if self.contains_angle(dy.atan2(dx)) {
+            to_circle
+        } else {
+            p.distance(&self.start()).min(p.distance(&self.end()))
+        }
+    }
+
+    pub fn bounding_box(&self) -> Rect {
+        use std::f32::consts::{PI, FRAC_PI_2};
+        let mut bb = Rect::from_points(&self.start(), &self.end());
+        for &angle in &[0.0, FRAC_PI_2, PI, -FRAC_PI_2] {
+            for t in self.parameters_at_angle(angle) {
+                bb.expand_to_include(&self.point_at(t));
+            }
+        }
+        bb
+    }
+
+    /// Parameters where y stops increasing or decreasing.
+    fn y_extrema(&self) -> Vec<f32> {
+        let mut out = self.parameters_at_angle(::std::f32::consts::FRAC_PI_2);
+        out.extend(self.parameters_at_angle(-::std::f32::consts::FRAC_PI_2));
+        out
+    }
+}
+
+impl QuadBezier {
+    pub fn point_at(&self, t: f32) -> Point {
+        let mt = 1.0 - t;
+        Point {
+            x: mt * mt * self.0.x + 2.0 * mt * t * self.1.x + t * t * self.2.x,
+            y: mt * mt * self.0.y + 2.0 * mt * t * self.1.y + t * t * self.2.y,
+        }
+    }
+
+    /// Solves for the closest point exactly; the derivative of the squared
+    /// distance along a quadratic is a cubic.
+    pub fn dist_to_point(&self, p: Point) -> f32 {
+        let (p0, p1, p2) = (self.0, self.1, self.2);
+        let (ax, ay) = ((p1.x - p0.x) as f64, (p1.y - p0.y) as f64);
+        let (bx, by) = ((p0.x - 2.0 * p1.x + p2.x) as f64, (p0.y - 2.0 * p1.y + p2.y) as f64);
+        let (dx, dy) = ((p0.x - p.x) as f64, (p0.y - p.y) as f64);
+
+        let k3 = bx * bx + by * by;
+        let k2 = 3.0 * (ax * bx + ay * by);
+        let k1 = 2.0 * (ax * ax + ay * ay) + dx * bx + dy * by;
+        let k0 = dx * ax + dy * ay;
+
+        let mut best = p.distance_2(&p0).min(p.distance_2(&p2));
+        for t in interior(solve_cubic(k3, k2, k1, k0)) {
+            best = best.min(p.distance_2(&self.point_at(t)));
+        }
+        best.sqrt()
+    }
+
+    pub fn bounding_box(&self) -> Rect {
+        let mut bb = Rect::from_points(&self.0, &self.2);
+        let extrema = |a: f32, b: f32, c: f32| solve_quadratic(0.0, 2.0 * (a - 2.0 * b + c) as f64, 2.0 * (b - a) as f64);
+        let mut ts = interior(extrema(self.0.x, self.1.x, self.2.x));
+        ts.extend(interior(extrema(self.0.y, self.1.y, self.2.y)));
+        for t in ts {
+            bb.expand_to_include(&self.point_at(t));
+        }
+        bb
+    }
+
+    fn y_extrema(&self) -> Vec<f32> {
+        let (a, b, c) = (self.0.y as f64, self.1.y as f64, self.2.y as f64);
+        interior(solve_quadratic(0.0, 2.0 * (a - 2.0 * b + c), 2.0 * (b - a)))
+    }
+}
+
+impl CubicBezier {
+    pub fn point_at(&self, t: f32) -> Point {
+        let mt = 1.0 - t;
+        let (a, b, c, d) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+        Point {
+            x: a * self.0.x + b * self.1.x + c * self.2.x + d * self.3.x,
+            y: a * self.0.y + b * self.1.y + c * self.2.y + d * self.3.y,
+        }
+    }
+
+    fn derivative_at(&self, t: f32) -> Vector {
+        let mt = 1.0 - t;
+        let (a, b, c) = (3.0 * mt * mt, 6.0 * mt * t, 3.0 * t * t);
+        Vector {
+            x: a * (self.1.x - self.0.x) + b * (self.2.x - self.1.x) + c * (self.3.x - self.2.x),
+            y: a * (self.1.y - self.0.y) + b * (self.2.y - self.1.y) + c * (self.3.y - self.2.y),
+        }
+    }
+
+    fn second_derivative_at(&self, t: f32) -> Vector {
+        let mt = 1.0 - t;
+        Vector {
+            x: 6.0 * (mt * (self.2.x - 2.0 * self.1.x + self.0.x) + t * (self.3.x - 2.0 * self.2.x + self.1.x)),
+            y: 6.0 * (mt * (self.2.y - 2.0 * self.1.y + self.0.y) + t * (self.3.y - 2.0 * self.2.y + self.1.y)),
+        }
+    }
+
+    /// The closest point on a cubic is the root of a quintic, so this
+    /// samples the curve and then polishes every local minimum with
+    /// Newton's method, kept inside of the neighbouring samples by
+    /// falling back to bisection.
+    pub fn dist_to_point(&self, p: Point) -> f32 {
+        const SAMPLES: usize = 16;
+        let distances: Vec<f32> = (0 .. SAMPLES + 1)
+            .map(|i| p.distance_2(&self.point_at(i as f32 / SAMPLES as f32)))
+            .collect();
+
+        // Half the derivative of the squared distance, and its derivative.
+        let slope = |t: f32| {
+            let offset = self.point_at(t) - p;
+            let d1 = self.derivative_at(t);
+            (offset.dot(&d1), d1.dot(&d1) + offset.dot(&self.second_derivative_at(t)))
+        };
+
+        let mut best = distances.iter().fold(::std::f32::INFINITY, |a, &b| a.min(b));
+        for i in 0 .. SAMPLES + 1 {
+            let lower = if i == 0 { distances[i] } else { distances[i - 1] };
+            let upper = if i == SAMPLES { distances[i] } else { distances[i + 1] };
+            if distances[i] > lower || distances[i] > upper {
+                continue;
+            }
+
+            let mut lo = (i.max(1) - 1) as f32 / SAMPLES as f32;
+            let mut hi = (i + 1).min(SAMPLES) as f32 / SAMPLES as f32;
+            if slope(lo).0 > 0.0 || slope(hi).0 < 0.0 {
+                // The minimum is at an end of the curve.
+                continue;
+            }
+            let mut t = i as f32 / SAMPLES as f32;
+            for _ in 0 .. 24 {
+                let (g, dg) = slope(t);
+                if g < 0.0 { lo = t } else { hi = t }
+                let newton = t - g / dg;
+                t = if dg > 0.0 && newton > lo && newton < hi { newton } else { (lo + hi) / 2.0 };
+                if hi - lo < 1.0e-6 {
+                    break;
+                }
+            }
+            best = best.min(p.distance_2(&self.point_at(t)));
+        }
+        best.sqrt()
+    }
+
+    fn extrema(a: f32, b: f32, c: f32, d: f32) -> Vec<f32> {
+        // The derivative divided by 3.
+        let (a, b, c, d) = (a as f64, b as f64, c as f64, d as f64);
+        interior(solve_quadratic(-a + 3.0 * b - 3.0 * c + d, 2.0 * (a - 2.0 * b + c), b - a))
+    }
+
+    pub fn bounding_box(&self) -> Rect {
+        let mut bb = Rect::from_points(&self.0, &self.3);
+        let mut ts = CubicBezier::extrema(self.0.x, self.1.x, self.2.x, self.3.x);
+        ts.extend(self.y_extrema());
+        for t in ts {
+            bb.expand_to_include(&self.point_at(t));
+        }
+        bb
+    }
+
+    fn y_extrema(&self) -> Vec<f32> {
+        CubicBezier::extrema(self.0.y, self.1.y, self.2.y, self.3.y)
+    }
+}
+
+impl Segment {
+    pub fn point_at(&self, t: f32) -> Point {
+        match *self {
+            Segment::Line(Line(a, b)) => Point { x: a.x + (b.x - a.x) * t, y: a.y + (b.y - a.y) * t },
+            Segment::Arc(ref arc) => arc.point_at(t),
+            Segment::Quad(ref quad) => quad.point_at(t),
+            Segment::Cubic(ref cubic) => cubic.point_at(t),
+        }
+    }
+
+    pub fn start(&self) -> Point {
+        self.point_at(0.0)
+    }
+
+    pub fn end(&self) -> Point {
+        match *self {
+            Segment::Line(Line(_, b)) => b,
+            Segment::Quad(QuadBezier(_, _, c)) => c,
+            Segment::Cubic(CubicBezier(_, _, _, d)) => d,
+            Segment::Arc(ref arc) => arc.end(),
+        }
+    }
+
+    pub fn dist_to_point(&self, p: Point) -> f32 {
+        match *self {
+            Segment::Line(ref line) => line.dist_to_point(p),
+            Segment::Arc(ref arc) => arc.dist_to_point(p),
+            Segment::Quad(ref quad) => quad.dist_to_point(p),
+            Segment::Cubic(ref cubic) => cubic.dist_to_point(p),
+        }
+    }
+
+    pub fn bounding_box(&self) -> Rect {
+        match *self {
+            Segment::Line(ref line) => line.bounding_box(),
+            Segment::Arc(ref arc) => arc.bounding_box(),
+            Segment::Quad(ref quad) => quad.bounding_box(),
+            Segment::Cubic(ref cubic) => cubic.bounding_box(),
+        }
+    }
+
+    /// How many times the segment crosses the ray from `p` towards +x,
+    /// counting crossings in the +y direction as 1 and the others as -1.
+    ///
+    /// The segment is split into pieces where y only goes one way, and
+    /// each piece is treated like a line: it counts if its ends are on
+    /// different sides of the ray, with the start included and the end
+    /// excluded so that neighbouring pieces don't count twice.
+    pub fn winding(&self, p: Point) -> i32 {
+        let mut breaks = match *self {
+            Segment::Line(_) => vec![],
+            Segment::Arc(ref arc) => arc.y_extrema(),
+            Segment::Quad(ref quad) => quad.y_extrema(),
+            Segment::Cubic(ref cubic) => cubic.y_extrema(),
+        };
+        breaks.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        breaks.insert(0, 0.0);
+        breaks.push(1.0);
+
+        let mut winding = 0;
+        for piece in breaks.windows(2) {
+            let (t0, t1) = (piece[0], piece[1]);
+            let (a, b) = (self.point_at(t0), self.point_at(t1));
+            if (a.y <= p.y) == (b.y <= p.y) {
+                continue;
+            }
+            // Most segments are nowhere near the ray.
+            if a.x <= p.x && b.x <= p.x && !self.bulges(a, b) {
+                continue;
+            }
+
+            // Bisect for where this monotonic piece crosses the ray.
+            let (mut lo, mut hi) = (t0, t1);
+            let rising = b.y > a.y;
+            let mut x = a.x + (b.x - a.x) * (p.y - a.y) / (b.y - a.y);
+            if let Segment::Line(_) = *self {
+                // Lines are exact already.
+            } else {
+                for _ in 0 .. 32 {
+                    let mid = (lo + hi) / 2.0;
+                    let m = self.point_at(mid);
+                    x = m.x;
+                    if (m.y <= p.y) == (a.y <= p.y) { lo = mid } else { hi = mid }
+                }
+            }
+            if x > p.x {
+                winding += if rising { 1 } else { -1 };
+            }
+        }
+        winding
+    }
+
+    /// True if the curve could stray to the right of both of its ends.
+    fn bulges(&self, a: Point, b: Point) -> bool {
+        match *self {
+            Segment::Line(_) => false,
+            _ => self.bounding_box().right() > a.x.max(b.x),
+        }
+    }
+}
+
 pub mod simd {
     use simd::*;
 
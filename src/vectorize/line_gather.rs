@@ -24,6 +24,8 @@ impl QuadTreeProducer for () {
     fn make_empty(&mut self, _rect: Rect, _fill: f32) -> Self::Tree {  }
 }
 
+/// `v` is a sample that's already been divided by the shape's Lipschitz
+/// constant, which makes it a lower bound on the distance to an edge.
 #[inline]
 fn should_early_return<P: QuadTreeProducer>(p: &mut P, v: f32, rect: Rect) -> Option<P::Tree> {
     let furthest = {
@@ -42,12 +44,12 @@ fn should_early_return<P: QuadTreeProducer>(p: &mut P, v: f32, rect: Rect) -> Op
     }
 }
 
-fn gather_final<S: ?Sized, P>(p: &mut P, shape: &S, rect: Rect, out: &mut Vec<Line>, a: f32, b: f32, c: f32, d: f32) -> P::Tree
+fn gather_final<S: ?Sized, P>(p: &mut P, shape: &S, lipschitz: f32, rect: Rect, out: &mut Vec<Line>, a: f32, b: f32, c: f32, d: f32) -> P::Tree
 where S: Implicit, P: QuadTreeProducer {
     let midpoint = rect.midpoint();
     let m = shape.sample(midpoint);
 
-    if let Some(early_return) = should_early_return(p, m, rect) {
+    if let Some(early_return) = should_early_return(p, m / lipschitz, rect) {
         return early_return;
     }
 
@@ -71,12 +73,12 @@ where S: Implicit, P: QuadTreeProducer {
 // A N B
 // W M E
 // D S C
-fn gather<S: ?Sized, P: QuadTreeProducer>(p: &mut P, shape: &S, rect: Rect, depth: u32, out: &mut Vec<Line>) -> P::Tree
+fn gather<S: ?Sized, P: QuadTreeProducer>(p: &mut P, shape: &S, lipschitz: f32, rect: Rect, depth: u32, out: &mut Vec<Line>) -> P::Tree
 where S: Implicit, P: QuadTreeProducer {
     let midpoint = rect.midpoint();
     let m = shape.sample(midpoint);
 
-    if let Some(early_return) = should_early_return(p, m, rect) {
+    if let Some(early_return) = should_early_return(p, m / lipschitz, rect) {
         return early_return;
     }
 
@@ -100,17 +102,17 @@ where S: Implicit, P: QuadTreeProducer {
         let w = shape.sample(west);
         ::flame::end("sampling");
 
-        let xa = gather_final(p, shape, nw_quad, out, a, n, m, w);
-        let xb = gather_final(p, shape, ne_quad, out, n, b, e, m);
-        let xc = gather_final(p, shape, se_quad, out, m, e, c, s);
-        let xd = gather_final(p, shape, sw_quad, out, w, m, s, d);
+        let xa = gather_final(p, shape, lipschitz, nw_quad, out, a, n, m, w);
+        let xb = gather_final(p, shape, lipschitz, ne_quad, out, n, b, e, m);
+        let xc = gather_final(p, shape, lipschitz, se_quad, out, m, e, c, s);
+        let xd = gather_final(p, shape, lipschitz, sw_quad, out, w, m, s, d);
 
         p.make_branch(rect, xa, xb, xc, xd)
     } else {
-        let xa = gather(p, shape, nw_quad, depth - 1, out);
-        let xb = gather(p, shape, ne_quad, depth - 1, out);
-        let xc = gather(p, shape, se_quad, depth - 1, out);
-        let xd = gather(p, shape, sw_quad, depth - 1, out);
+        let xa = gather(p, shape, lipschitz, nw_quad, depth - 1, out);
+        let xb = gather(p, shape, lipschitz, ne_quad, depth - 1, out);
+        let xc = gather(p, shape, lipschitz, se_quad, depth - 1, out);
+        let xd = gather(p, shape, lipschitz, sw_quad, depth - 1, out);
 
         p.make_branch(rect, xa, xb, xc, xd)
     }
@@ -129,6 +131,7 @@ where S: Implicit + Sync, P: QuadTreeProducer {
     let h = h + h / 6.0;
     let bounding_box = Rect::centered_with_radius(&bb.midpoint(), w.max(h));
 
-    let tree = gather(p, shape, bounding_box, depth, &mut out);
+    let lipschitz = shape.lipschitz_constant().max(::std::f32::MIN_POSITIVE);
+    let tree = gather(p, shape, lipschitz, bounding_box, depth, &mut out);
     (tree, out)
 }
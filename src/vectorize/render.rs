@@ -10,10 +10,24 @@ use ::Implicit;
 use itertools::Itertools;
 use flame;
 
+/// Decides which parts of a set of contours are considered "inside"
+/// when a shape is filled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FillRule {
+    /// A point is filled if a ray from it crosses an odd number of contours.
+    EvenOdd,
+    /// A point is filled if the contours wind around it a non-zero number
+    /// of times.
+    NonZero,
+}
+
 #[derive(Clone)]
 pub enum RenderMode {
     /// The shape is filled in and completely solid.
-    Solid,
+    ///
+    /// Each outer contour is grouped with the holes directly inside
+    /// of it and the group is filled using the given rule.
+    Solid(FillRule),
     /// The shape is traced with an outline.
     Outline,
     /// The shape is traced with a dashed outline.
@@ -43,10 +57,23 @@ pub enum RenderMode {
 
 #[derive(Clone)]
 pub enum OutputMode {
-    Solid(Vec<Vec<Point>>),
+    Solid(Vec<FilledRegion>, FillRule),
     Outline(Vec<Vec<Point>>),
-    DashedLine(Vec<DashedData>)
+    DashedLine(Vec<DashedData>),
+    /// Open lines that are drawn exactly as they were given.
+    Strokes(Vec<Vec<Point>>),
 }
+
+/// An outer contour along with all of the holes cut out of it.
+///
+/// The outer contour is wound clockwise and the holes are wound
+/// counter-clockwise so that both fill rules produce the same result.
+#[derive(Clone, Debug)]
+pub struct FilledRegion {
+    pub outer: Vec<Point>,
+    pub holes: Vec<Vec<Point>>,
+}
+
 pub struct SegmentIter<'a> {
     data: &'a DashedData,
     last_segment_idx: usize,
@@ -137,7 +164,7 @@ fn transform(points: Vec<Vec<Point>>, mode: &RenderMode) -> OutputMode {
     }
 
     match mode {
-        &RenderMode::Solid => OutputMode::Solid(points),
+        &RenderMode::Solid(rule) => OutputMode::Solid(group_regions(points), rule),
         &RenderMode::Outline => OutputMode::Outline(points),
         &RenderMode::BasicDashed(ref dash) => {
             OutputMode::DashedLine(points.into_iter()
@@ -201,12 +228,19 @@ where A: Implicit + Sync {
 }
 
 fn correct_spin(points: &mut [Point]) {
+    if points.is_empty() {
+        return;
+    }
+
     let is_clockwise = {
         let mut total = 0.0;
-        for slice in points.windows(2) {
-            let a = slice[0];
-            let b = slice[1];
+        // Include the edge from the last point back to the first.
+        let mut j = points.len() - 1;
+        for i in 0 .. points.len() {
+            let a = points[j];
+            let b = points[i];
             total += (b.x - a.x) * (b.y + a.y);
+            j = i;
         }
         total > 0.0
     };
@@ -216,6 +250,79 @@ fn correct_spin(points: &mut [Point]) {
     }
 }
 
+fn contains_point(poly: &[Point], p: Point) -> bool {
+    let mut inside = false;
+    let mut j = poly.len() - 1;
+    for i in 0 .. poly.len() {
+        let a = poly[i];
+        let b = poly[j];
+        if (a.y > p.y) != (b.y > p.y) &&
+           p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn area(poly: &[Point]) -> f32 {
+    let mut total = 0.0;
+    let mut j = poly.len() - 1;
+    for i in 0 .. poly.len() {
+        total += poly[j].x * poly[i].y - poly[i].x * poly[j].y;
+        j = i;
+    }
+    (total / 2.0).abs()
+}
+
+/// Sorts a pile of non-intersecting contours into outer contours and
+/// the holes that belong to them.
+///
+/// A contour nested inside of an even number of other contours is an
+/// outer boundary; an odd number makes it a hole in the smallest contour
+/// that surrounds it.
+fn group_regions(mut contours: Vec<Vec<Point>>) -> Vec<FilledRegion> {
+    contours.retain(|c| c.len() > 2);
+
+    let areas: Vec<f32> = contours.iter().map(|c| area(c)).collect();
+    let containers: Vec<Vec<usize>> = contours.iter().enumerate().map(|(i, c)| {
+        (0 .. contours.len()).filter(|&j| {
+            j != i && contains_point(&contours[j], c[0])
+        }).collect()
+    }).collect();
+
+    let mut region_of = vec![None; contours.len()];
+    let mut regions = vec![];
+    for (i, contour) in contours.iter().enumerate() {
+        if containers[i].len() % 2 == 0 {
+            let mut outer = contour.clone();
+            correct_spin(&mut outer);
+            region_of[i] = Some(regions.len());
+            regions.push(FilledRegion { outer: outer, holes: vec![] });
+        }
+    }
+
+    for (i, contour) in contours.iter().enumerate() {
+        if containers[i].len() % 2 == 1 {
+            let parent = containers[i].iter().cloned().fold(None, |best: Option<usize>, j| {
+                match best {
+                    Some(b) if areas[b] <= areas[j] => Some(b),
+                    _ => Some(j),
+                }
+            });
+
+            if let Some(region) = parent.and_then(|p| region_of[p]) {
+                let mut hole = contour.clone();
+                correct_spin(&mut hole);
+                hole.reverse();
+                regions[region].holes.push(hole);
+            }
+        }
+    }
+
+    regions
+}
+
 fn rotate<T>(slice: &mut [T], at: usize) {
     {
         let (a, b) = slice.split_at_mut(at);
@@ -226,6 +333,27 @@ fn rotate<T>(slice: &mut [T], at: usize) {
     slice.reverse();
 }
 
+#[test]
+fn holes_are_grouped_with_their_outer_contour() {
+    fn square(x: f32, y: f32, size: f32) -> Vec<Point> {
+        vec![Point { x: x, y: y },
+             Point { x: x + size, y: y },
+             Point { x: x + size, y: y + size },
+             Point { x: x, y: y + size }]
+    }
+
+    let regions = group_regions(vec![
+        square(2.0, 2.0, 6.0),
+        square(0.0, 0.0, 10.0),
+        square(4.0, 4.0, 2.0),
+        square(20.0, 0.0, 10.0),
+    ]);
+
+    assert_eq!(regions.len(), 3);
+    let with_hole = regions.iter().filter(|r| r.holes.len() == 1).count();
+    assert_eq!(with_hole, 1);
+}
+
 #[test]
 fn rotation_is_correct() {
     let mut slice = [0, 1, 2, 3, 4, 5];
@@ -233,3 +361,52 @@ fn rotation_is_correct() {
     assert_eq!(slice[0], 3);
 }
 
+
+#[test]
+fn outer_contours_and_holes_wind_in_opposite_directions() {
+    // Twice the signed area, which is positive when the points wind
+    // counter-clockwise with y pointing up.
+    fn winding(poly: &[Point]) -> f32 {
+        let mut total = 0.0;
+        let mut j = poly.len() - 1;
+        for i in 0 .. poly.len() {
+            total += poly[j].x * poly[i].y - poly[i].x * poly[j].y;
+            j = i;
+        }
+        total
+    }
+    fn square(x: f32, y: f32, size: f32, reversed: bool) -> Vec<Point> {
+        let mut points = vec![Point { x: x, y: y },
+                              Point { x: x + size, y: y },
+                              Point { x: x + size, y: y + size },
+                              Point { x: x, y: y + size }];
+        if reversed {
+            points.reverse();
+        }
+        points
+    }
+
+    // Every contour is given in both directions, so the output can't
+    // just be keeping the input's winding.  All of the winding of the
+    // sliver is in its closing edge.
+    for &reversed in &[false, true] {
+        let mut sliver = vec![Point { x: 20.0, y: 0.0 }, Point { x: 30.0, y: 0.0 }, Point { x: 30.0, y: 1.0 }];
+        if reversed {
+            sliver.reverse();
+        }
+        let regions = group_regions(vec![
+            square(0.0, 0.0, 10.0, reversed),
+            square(2.0, 2.0, 6.0, !reversed),
+            square(4.0, 4.0, 2.0, reversed),
+            sliver,
+        ]);
+        assert_eq!(regions.len(), 3);
+        for region in &regions {
+            assert!(winding(&region.outer) < 0.0);
+            for hole in &region.holes {
+                assert!(winding(hole) > 0.0);
+            }
+        }
+        assert_eq!(regions.iter().map(|r| r.holes.len()).sum::<usize>(), 1);
+    }
+}